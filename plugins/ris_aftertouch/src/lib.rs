@@ -0,0 +1,522 @@
+use nih_plug::prelude::*;
+use rismidi::{params::CcNumberParam, HeldNotes, MidiChannel, NUM_MIDI_CHANNELS, NUM_MIDI_NOTES};
+use std::sync::Arc;
+
+const MIDI_CHANNEL_FROM_NIH_PLUG: &str = "MIDI channels from nih_plug must be in range 0..=15";
+
+struct RisAftertouch {
+    params: Arc<RisAftertouchParams>,
+
+    /// The notes which are currently held. Poly pressure is only valid for these.
+    held_notes: HeldNotes,
+
+    /// The last poly pressure of every note, see [`note_index`].
+    poly_pressure: Vec<f32>,
+
+    /// The last channel-wide pressure received, per channel.
+    channel_pressure: [f32; NUM_MIDI_CHANNELS as usize],
+
+    /// The last aggregated poly pressure sent, per channel. This is [`None`] until poly pressure
+    /// has been received on the channel, so that playing notes alone does not send any pressure.
+    sent_aggregate: [Option<f32>; NUM_MIDI_CHANNELS as usize],
+}
+
+#[derive(Params)]
+struct RisAftertouchParams {
+    #[id = "source"]
+    pub source: EnumParam<PressureKind>,
+
+    #[id = "target"]
+    pub target: EnumParam<PressureKind>,
+
+    #[nested(id_prefix = "cc")]
+    pub cc: CcNumberParam,
+
+    #[id = "aggregation"]
+    pub aggregation: EnumParam<Aggregation>,
+
+    #[id = "keep_original"]
+    pub keep_original: BoolParam,
+}
+
+/// The kinds of events that can carry pressure.
+#[derive(Enum, Clone, Copy, Debug, PartialEq, Eq)]
+enum PressureKind {
+    #[id = "channel_pressure"]
+    #[name = "Channel Pressure"]
+    ChannelPressure,
+
+    #[id = "poly_pressure"]
+    #[name = "Poly Pressure"]
+    PolyPressure,
+
+    #[id = "cc"]
+    #[name = "CC"]
+    Cc,
+}
+
+/// How the pressures of all held notes are combined into a single value.
+#[derive(Enum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Aggregation {
+    #[id = "max"]
+    #[name = "Maximum"]
+    Maximum,
+
+    #[id = "average"]
+    #[name = "Average"]
+    Average,
+}
+
+/// How events are converted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Conversion {
+    source: PressureKind,
+    target: PressureKind,
+    cc: u8,
+    aggregation: Aggregation,
+    keep_original: bool,
+}
+
+impl Default for RisAftertouch {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(RisAftertouchParams::default()),
+            held_notes: HeldNotes::new(),
+            poly_pressure: vec![0.0; NUM_MIDI_NOTES as usize * NUM_MIDI_CHANNELS as usize],
+            channel_pressure: [0.0; NUM_MIDI_CHANNELS as usize],
+            sent_aggregate: [None; NUM_MIDI_CHANNELS as usize],
+        }
+    }
+}
+
+impl Default for RisAftertouchParams {
+    fn default() -> Self {
+        Self {
+            source: EnumParam::new("Source", PressureKind::PolyPressure),
+            target: EnumParam::new("Target", PressureKind::ChannelPressure),
+            cc: CcNumberParam::new("CC", 1),
+            aggregation: EnumParam::new("Aggregation", Aggregation::Maximum),
+            keep_original: BoolParam::new("Keep Original", false),
+        }
+    }
+}
+
+/// The index of a note in the per-note tables.
+fn note_index(channel: MidiChannel, note: u8) -> usize {
+    channel.to_0_based() as usize * NUM_MIDI_NOTES as usize + note as usize
+}
+
+impl Conversion {
+    /// Gets the channel, note (for poly pressure) and pressure of `event`, if it is of the source
+    /// kind.
+    fn source_pressure(&self, event: &NoteEvent) -> Option<(u8, Option<u8>, f32)> {
+        match (self.source, *event) {
+            (
+                PressureKind::ChannelPressure,
+                NoteEvent::MidiChannelPressure {
+                    channel, pressure, ..
+                },
+            ) => Some((channel, None, pressure)),
+            (
+                PressureKind::PolyPressure,
+                NoteEvent::PolyPressure {
+                    channel,
+                    note,
+                    pressure,
+                    ..
+                },
+            ) => Some((channel, Some(note), pressure)),
+            (
+                PressureKind::Cc,
+                NoteEvent::MidiCC {
+                    channel, cc, value, ..
+                },
+            ) if cc == self.cc => Some((channel, None, value)),
+            _ => None,
+        }
+    }
+
+    /// Creates a channel-wide event of the target kind.
+    fn channel_event(&self, timing: u32, channel: u8, pressure: f32) -> NoteEvent {
+        match self.target {
+            PressureKind::Cc => NoteEvent::MidiCC {
+                timing,
+                channel,
+                cc: self.cc,
+                value: pressure,
+            },
+            _ => NoteEvent::MidiChannelPressure {
+                timing,
+                channel,
+                pressure,
+            },
+        }
+    }
+}
+
+impl RisAftertouch {
+    /// Gets the conversion settings from the current parameter values.
+    fn current_conversion(&self) -> Conversion {
+        Conversion {
+            source: self.params.source.value(),
+            target: self.params.target.value(),
+            cc: self.params.cc.value(),
+            aggregation: self.params.aggregation.value(),
+            keep_original: self.params.keep_original.value(),
+        }
+    }
+
+    /// Combines the poly pressures of all notes held on `channel`.
+    fn aggregate(&self, channel: MidiChannel, aggregation: Aggregation) -> f32 {
+        let pressures = self
+            .held_notes
+            .on_channel(channel)
+            .map(|held| self.poly_pressure[note_index(channel, held.note)]);
+
+        match aggregation {
+            Aggregation::Maximum => pressures.fold(0.0, f32::max),
+            Aggregation::Average => {
+                let (sum, count) = pressures.fold((0.0, 0), |(sum, count), pressure| {
+                    (sum + pressure, count + 1)
+                });
+                if count > 0 {
+                    sum / count as f32
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+
+    /// Sends the combined poly pressure of `channel`, if it has changed.
+    fn send_aggregate(
+        &mut self,
+        timing: u32,
+        channel: u8,
+        conversion: Conversion,
+        send: &mut impl FnMut(NoteEvent),
+    ) {
+        let midi_channel =
+            MidiChannel::try_from_0_based(channel.into()).expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+        let pressure = self.aggregate(midi_channel, conversion.aggregation);
+
+        let sent = &mut self.sent_aggregate[midi_channel.to_0_based() as usize];
+        if *sent != Some(pressure) {
+            *sent = Some(pressure);
+            send(conversion.channel_event(timing, channel, pressure));
+        }
+    }
+
+    /// Passes the converted events for `in_event` to `send`.
+    fn process_event(
+        &mut self,
+        in_event: NoteEvent,
+        conversion: Conversion,
+        mut send: impl FnMut(NoteEvent),
+    ) {
+        let collapses_poly = conversion.source == PressureKind::PolyPressure
+            && conversion.target != PressureKind::PolyPressure;
+        let spreads_to_poly = conversion.source != PressureKind::PolyPressure
+            && conversion.target == PressureKind::PolyPressure;
+
+        match in_event {
+            NoteEvent::NoteOn {
+                timing,
+                channel,
+                note,
+                ..
+            }
+            | NoteEvent::NoteOff {
+                timing,
+                channel,
+                note,
+                ..
+            } => {
+                let midi_channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+                let is_note_on = matches!(in_event, NoteEvent::NoteOn { .. });
+                self.held_notes.handle_event(&in_event);
+                if let Some(poly_pressure) =
+                    self.poly_pressure.get_mut(note_index(midi_channel, note))
+                {
+                    *poly_pressure = 0.0;
+                }
+                send(in_event);
+
+                let pressure = self.channel_pressure[midi_channel.to_0_based() as usize];
+                if spreads_to_poly && is_note_on && pressure > 0.0 {
+                    send(NoteEvent::PolyPressure {
+                        timing,
+                        voice_id: None,
+                        channel,
+                        note,
+                        pressure,
+                    });
+                }
+                let received_poly =
+                    self.sent_aggregate[midi_channel.to_0_based() as usize].is_some();
+                if collapses_poly && received_poly {
+                    self.send_aggregate(timing, channel, conversion, &mut send);
+                }
+
+                return;
+            }
+            _ => (),
+        }
+
+        let Some((channel, note, pressure)) = conversion.source_pressure(&in_event) else {
+            send(in_event);
+            return;
+        };
+        if conversion.source == conversion.target {
+            send(in_event);
+            return;
+        }
+
+        let timing = in_event.timing();
+        let midi_channel =
+            MidiChannel::try_from_0_based(channel.into()).expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+        match (note, conversion.target) {
+            (Some(note), _) => {
+                // Poly pressure for notes which are not held is meaningless.
+                if self.held_notes.is_held(midi_channel, note) {
+                    self.poly_pressure[note_index(midi_channel, note)] = pressure;
+                    self.send_aggregate(timing, channel, conversion, &mut send);
+                }
+            }
+            (None, PressureKind::PolyPressure) => {
+                self.channel_pressure[midi_channel.to_0_based() as usize] = pressure;
+                for held in self.held_notes.on_channel(midi_channel) {
+                    send(NoteEvent::PolyPressure {
+                        timing,
+                        voice_id: None,
+                        channel,
+                        note: held.note,
+                        pressure,
+                    });
+                }
+            }
+            (None, _) => send(conversion.channel_event(timing, channel, pressure)),
+        }
+
+        if conversion.keep_original {
+            send(in_event);
+        }
+    }
+}
+
+impl Plugin for RisAftertouch {
+    const NAME: &'static str = env!("CARGO_PKG_NAME");
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const VENDOR: &'static str = "Simon Leiner";
+    const EMAIL: &'static str = "rismidi@leiner.me";
+
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const DEFAULT_INPUT_CHANNELS: u32 = 0;
+    const DEFAULT_OUTPUT_CHANNELS: u32 = 0;
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::MidiCCs;
+
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn accepts_bus_config(&self, config: &BusConfig) -> bool {
+        let no_aux_busses = AuxiliaryIOConfig {
+            num_busses: 0,
+            num_channels: 0,
+        };
+
+        config.num_input_channels == 0
+            && config.num_output_channels == 0
+            && config.aux_input_busses == no_aux_busses
+            && config.aux_output_busses == no_aux_busses
+    }
+
+    fn reset(&mut self) {
+        self.held_notes.clear();
+        self.poly_pressure.fill(0.0);
+        self.channel_pressure = [0.0; NUM_MIDI_CHANNELS as usize];
+        self.sent_aggregate = [None; NUM_MIDI_CHANNELS as usize];
+    }
+
+    fn process(
+        &mut self,
+        _buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        while let Some(in_event) = context.next_event() {
+            let conversion = self.current_conversion();
+            self.process_event(in_event, conversion, |event| context.send_event(event));
+        }
+
+        ProcessStatus::Normal
+    }
+}
+
+impl ClapPlugin for RisAftertouch {
+    const CLAP_ID: &'static str = "me.leiner.ris.aftertouch";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some(env!("CARGO_PKG_DESCRIPTION"));
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] =
+        &[ClapFeature::Custom("MIDI"), ClapFeature::Utility];
+}
+
+impl Vst3Plugin for RisAftertouch {
+    const VST3_CLASS_ID: [u8; 16] = *b"risAftertouch...";
+    const VST3_CATEGORIES: &'static str = "Fx|Tools";
+}
+
+nih_export_clap!(RisAftertouch);
+nih_export_vst3!(RisAftertouch);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rismidi::testing::{
+        cc, channel_pressure, collect_events, note_off, note_on, poly_pressure,
+    };
+
+    const POLY_TO_CHANNEL: Conversion = Conversion {
+        source: PressureKind::PolyPressure,
+        target: PressureKind::ChannelPressure,
+        cc: 1,
+        aggregation: Aggregation::Maximum,
+        keep_original: false,
+    };
+
+    #[test]
+    fn poly_pressure_is_collapsed() {
+        for (aggregation, expected) in [(Aggregation::Maximum, 0.5), (Aggregation::Average, 0.25)] {
+            let mut processor = RisAftertouch::default();
+            let conversion = Conversion {
+                aggregation,
+                ..POLY_TO_CHANNEL
+            };
+
+            processor.process_event(note_on(0, 0, 60, 0.5), conversion, |_| ());
+            processor.process_event(note_on(0, 0, 64, 0.5), conversion, |_| ());
+
+            let in_event = poly_pressure(0, 0, 64, 0.5);
+            let out_events =
+                collect_events(|send| processor.process_event(in_event, conversion, send));
+            assert_eq!(out_events, vec![channel_pressure(0, 0, expected)]);
+
+            let in_event = note_off(0, 0, 64);
+            let out_events =
+                collect_events(|send| processor.process_event(in_event, conversion, send));
+            assert_eq!(
+                out_events,
+                vec![note_off(0, 0, 64), channel_pressure(0, 0, 0.0)]
+            );
+        }
+    }
+
+    #[test]
+    fn notes_without_poly_pressure_send_no_pressure() {
+        let mut processor = RisAftertouch::default();
+
+        let in_event = note_on(0, 0, 60, 0.5);
+        let out_events =
+            collect_events(|send| processor.process_event(in_event, POLY_TO_CHANNEL, send));
+        assert_eq!(out_events, vec![note_on(0, 0, 60, 0.5)]);
+
+        let in_event = note_off(0, 0, 60);
+        let out_events =
+            collect_events(|send| processor.process_event(in_event, POLY_TO_CHANNEL, send));
+        assert_eq!(out_events, vec![note_off(0, 0, 60)]);
+
+        // Poly pressure on another channel does not change that.
+        processor.process_event(note_on(0, 1, 60, 0.5), POLY_TO_CHANNEL, |_| ());
+        processor.process_event(poly_pressure(0, 1, 60, 0.5), POLY_TO_CHANNEL, |_| ());
+
+        let in_event = note_on(0, 0, 60, 0.5);
+        let out_events =
+            collect_events(|send| processor.process_event(in_event, POLY_TO_CHANNEL, send));
+        assert_eq!(out_events, vec![note_on(0, 0, 60, 0.5)]);
+    }
+
+    #[test]
+    fn poly_pressure_of_released_notes_is_ignored() {
+        let mut processor = RisAftertouch::default();
+        let conversion = Conversion {
+            keep_original: true,
+            ..POLY_TO_CHANNEL
+        };
+
+        processor.process_event(note_on(0, 0, 60, 0.5), conversion, |_| ());
+        processor.process_event(note_off(0, 0, 60), conversion, |_| ());
+
+        let in_event = poly_pressure(0, 0, 60, 0.5);
+        let out_events = collect_events(|send| processor.process_event(in_event, conversion, send));
+        assert_eq!(out_events, vec![poly_pressure(0, 0, 60, 0.5)]);
+    }
+
+    #[test]
+    fn channel_pressure_is_spread_to_held_notes() {
+        let mut processor = RisAftertouch::default();
+        let conversion = Conversion {
+            source: PressureKind::ChannelPressure,
+            target: PressureKind::PolyPressure,
+            ..POLY_TO_CHANNEL
+        };
+
+        processor.process_event(note_on(0, 0, 60, 0.5), conversion, |_| ());
+        processor.process_event(note_on(0, 0, 64, 0.5), conversion, |_| ());
+
+        let in_event = channel_pressure(0, 0, 0.5);
+        let out_events = collect_events(|send| processor.process_event(in_event, conversion, send));
+        assert_eq!(
+            out_events,
+            vec![poly_pressure(0, 0, 60, 0.5), poly_pressure(0, 0, 64, 0.5)]
+        );
+
+        let in_event = note_on(0, 0, 67, 0.5);
+        let out_events = collect_events(|send| processor.process_event(in_event, conversion, send));
+        assert_eq!(
+            out_events,
+            vec![note_on(0, 0, 67, 0.5), poly_pressure(0, 0, 67, 0.5)]
+        );
+    }
+
+    #[test]
+    fn channel_pressure_and_ccs_are_converted() {
+        let mut processor = RisAftertouch::default();
+        let to_cc = Conversion {
+            source: PressureKind::ChannelPressure,
+            target: PressureKind::Cc,
+            cc: 2,
+            ..POLY_TO_CHANNEL
+        };
+
+        let in_event = channel_pressure(0, 0, 0.5);
+        let out_events = collect_events(|send| processor.process_event(in_event, to_cc, send));
+        assert_eq!(out_events, vec![cc(0, 0, 2, 0.5)]);
+
+        let from_cc = Conversion {
+            source: PressureKind::Cc,
+            target: PressureKind::ChannelPressure,
+            keep_original: true,
+            ..to_cc
+        };
+
+        let in_event = cc(0, 0, 2, 0.5);
+        let out_events = collect_events(|send| processor.process_event(in_event, from_cc, send));
+        assert_eq!(
+            out_events,
+            vec![channel_pressure(0, 0, 0.5), cc(0, 0, 2, 0.5)]
+        );
+
+        let in_event = cc(0, 0, 3, 0.5);
+        let out_events = collect_events(|send| processor.process_event(in_event, from_cc, send));
+        assert_eq!(out_events, vec![cc(0, 0, 3, 0.5)]);
+    }
+}
@@ -0,0 +1,590 @@
+use nih_plug::prelude::*;
+use rismidi::{
+    BeatClock, EventScheduler, HasTiming, HeldNote, HeldNotes, MidiChannel, NoteValue,
+    OptionalMidiChannelParam, Rng, NUM_MIDI_CHANNELS, NUM_MIDI_NOTES,
+};
+use std::sync::Arc;
+
+const MIDI_CHANNEL_FROM_NIH_PLUG: &str = "MIDI channels from nih_plug must be in range 0..=15";
+
+const MAX_OCTAVES: u8 = 4;
+
+/// The longest possible pattern: every note on every channel, in every octave.
+const MAX_PATTERN_LEN: usize =
+    NUM_MIDI_NOTES as usize * NUM_MIDI_CHANNELS as usize * MAX_OCTAVES as usize;
+
+/// Only the note-offs of the arpeggiated notes are scheduled, and there is usually only one of
+/// them pending at a time.
+const SCHEDULER_CAPACITY: usize = 64;
+
+/// The seed for the "Random" mode, so that renders are reproducible.
+const RANDOM_SEED: u64 = 0;
+
+struct RisArpeggiator {
+    params: Arc<RisArpeggiatorParams>,
+    sample_rate: f32,
+    clock: BeatClock,
+    scheduler: EventScheduler,
+
+    /// The notes whose keys are currently pressed.
+    held_notes: HeldNotes,
+
+    /// The notes that are played while latch is enabled: the last chord, even if it has been
+    /// released already.
+    latched_notes: HeldNotes,
+
+    /// The notes to choose from for the next step. This is rebuilt for every step, we only keep
+    /// it around to avoid allocations.
+    pattern: Vec<HeldNote>,
+
+    /// The number of steps since the arpeggiator has started playing the current notes.
+    position: usize,
+
+    /// The index of the last step played, so that it is not played twice if the host's position
+    /// jitters around a step boundary.
+    last_step: Option<i64>,
+
+    rng: Rng,
+
+    /// Whether the plugin has been reset, so that the pending note-offs need to be sent.
+    flush_pending: bool,
+}
+
+#[derive(Params)]
+struct RisArpeggiatorParams {
+    #[id = "mode"]
+    pub mode: EnumParam<ArpMode>,
+
+    #[id = "octaves"]
+    pub octaves: IntParam,
+
+    #[id = "rate"]
+    pub rate: EnumParam<NoteValue>,
+
+    #[id = "gate"]
+    pub gate: FloatParam,
+
+    #[id = "latch"]
+    pub latch: BoolParam,
+
+    #[nested(id_prefix = "output_channel")]
+    pub output_channel: OptionalMidiChannelParam,
+}
+
+/// The order in which the held notes are played.
+#[derive(Enum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ArpMode {
+    #[id = "up"]
+    #[name = "Up"]
+    Up,
+
+    #[id = "down"]
+    #[name = "Down"]
+    Down,
+
+    #[id = "up_down"]
+    #[name = "Up/Down"]
+    UpDown,
+
+    #[id = "random"]
+    #[name = "Random"]
+    Random,
+
+    #[id = "as_played"]
+    #[name = "As Played"]
+    AsPlayed,
+}
+
+/// The parameter values that determine which note is played for a step.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ArpSettings {
+    mode: ArpMode,
+    octaves: u8,
+    latch: bool,
+    output_channel: Option<MidiChannel>,
+}
+
+impl Default for RisArpeggiator {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(RisArpeggiatorParams::default()),
+            sample_rate: 44100.0,
+            clock: BeatClock::new(),
+            scheduler: EventScheduler::with_capacity(SCHEDULER_CAPACITY),
+            held_notes: HeldNotes::new(),
+            latched_notes: HeldNotes::new(),
+            pattern: Vec::with_capacity(MAX_PATTERN_LEN),
+            position: 0,
+            last_step: None,
+            rng: Rng::new(RANDOM_SEED),
+            flush_pending: false,
+        }
+    }
+}
+
+impl Default for RisArpeggiatorParams {
+    fn default() -> Self {
+        Self {
+            mode: EnumParam::new("Mode", ArpMode::Up),
+            octaves: IntParam::new(
+                "Octaves",
+                1,
+                IntRange::Linear {
+                    min: 1,
+                    max: MAX_OCTAVES as i32,
+                },
+            ),
+            rate: EnumParam::new("Rate", NoteValue::Sixteenth),
+            gate: FloatParam::new(
+                "Gate",
+                0.5,
+                FloatRange::Linear {
+                    min: 0.05,
+                    max: 1.0,
+                },
+            )
+            .with_unit(" %")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            latch: BoolParam::new("Latch", false),
+            output_channel: OptionalMidiChannelParam::new("Output Channel", None)
+                .with_none_selected_description("Same as Input"),
+        }
+    }
+}
+
+impl RisArpeggiator {
+    fn current_settings(&self) -> ArpSettings {
+        ArpSettings {
+            mode: self.params.mode.value(),
+            octaves: self.params.octaves.value() as u8,
+            latch: self.params.latch.value(),
+            output_channel: self.params.output_channel.value(),
+        }
+    }
+
+    /// Keeps track of the played notes. Returns `true` if the event was a note event, which is
+    /// consumed by the arpeggiator.
+    fn handle_note_event(&mut self, event: &NoteEvent) -> bool {
+        match *event {
+            NoteEvent::NoteOn {
+                timing: _,
+                voice_id: _,
+                channel,
+                note,
+                velocity,
+            } => {
+                let channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+
+                // A new chord after all keys have been released replaces the latched one.
+                if self.held_notes.is_empty() {
+                    self.latched_notes.clear();
+                }
+
+                self.held_notes.note_on(channel, note, velocity);
+                self.latched_notes.note_on(channel, note, velocity);
+                true
+            }
+            NoteEvent::NoteOff {
+                timing: _,
+                voice_id: _,
+                channel,
+                note,
+                velocity: _,
+            } => {
+                let channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+
+                self.held_notes.note_off(channel, note);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Fills [`Self::pattern`] with the notes to choose from, in the order they are played.
+    fn build_pattern(&mut self, settings: ArpSettings) {
+        let source = if settings.latch {
+            &self.latched_notes
+        } else {
+            &self.held_notes
+        };
+
+        self.pattern.clear();
+        for octave in 0..settings.octaves {
+            for held in source.iter() {
+                let note = held.note as u32 + 12 * octave as u32;
+                if note < NUM_MIDI_NOTES as u32 {
+                    self.pattern.push(HeldNote {
+                        note: note as u8,
+                        ..*held
+                    });
+                }
+            }
+        }
+
+        match settings.mode {
+            ArpMode::Up | ArpMode::UpDown => self
+                .pattern
+                .sort_unstable_by_key(|held| (held.note, held.channel)),
+            ArpMode::Down => self
+                .pattern
+                .sort_unstable_by_key(|held| (NUM_MIDI_NOTES - held.note, held.channel)),
+            ArpMode::Random | ArpMode::AsPlayed => (),
+        }
+    }
+
+    /// Chooses the note for the next step, if any notes are held.
+    fn next_note(&mut self, settings: ArpSettings) -> Option<HeldNote> {
+        self.build_pattern(settings);
+
+        let len = self.pattern.len();
+        if len == 0 {
+            // Start from the beginning when the next notes are played.
+            self.position = 0;
+            return None;
+        }
+
+        let index = match settings.mode {
+            ArpMode::Random => self.rng.below(len),
+            ArpMode::UpDown if len > 1 => {
+                // Don't repeat the highest and lowest note when changing directions.
+                let period = 2 * len - 2;
+                let index = self.position % period;
+                if index < len {
+                    index
+                } else {
+                    period - index
+                }
+            }
+            _ => self.position % len,
+        };
+        self.position = self.position.wrapping_add(1);
+
+        Some(self.pattern[index])
+    }
+
+    /// Plays the next step at `timing`. Returns the note-on to send; the corresponding note-off is
+    /// scheduled `note_length` samples later.
+    fn play_step(
+        &mut self,
+        timing: u32,
+        note_length: u64,
+        settings: ArpSettings,
+    ) -> Option<NoteEvent> {
+        if self.scheduler.remaining_capacity() == 0 {
+            return None;
+        }
+
+        let held = self.next_note(settings)?;
+        let channel = settings.output_channel.unwrap_or(held.channel).to_0_based();
+
+        let note_off = NoteEvent::NoteOff {
+            timing,
+            voice_id: None,
+            channel,
+            note: held.note,
+            velocity: 0.0,
+        };
+        self.scheduler
+            .schedule(timing as u64 + note_length, note_off)
+            .ok()?;
+
+        Some(NoteEvent::NoteOn {
+            timing,
+            voice_id: None,
+            channel,
+            note: held.note,
+            velocity: held.velocity,
+        })
+    }
+
+    /// Sends all note-offs due before `in_event`, then passes `in_event` on unless it is consumed
+    /// by the arpeggiator.
+    fn forward_event(&mut self, in_event: NoteEvent, context: &mut impl ProcessContext<Self>) {
+        while let Some(note_off) = self.scheduler.pop_before(in_event.timing()) {
+            context.send_event(note_off);
+        }
+
+        if !self.handle_note_event(&in_event) {
+            context.send_event(in_event);
+        }
+    }
+}
+
+impl Plugin for RisArpeggiator {
+    const NAME: &'static str = env!("CARGO_PKG_NAME");
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const VENDOR: &'static str = "Simon Leiner";
+    const EMAIL: &'static str = "rismidi@leiner.me";
+
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const DEFAULT_INPUT_CHANNELS: u32 = 0;
+    const DEFAULT_OUTPUT_CHANNELS: u32 = 0;
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::MidiCCs;
+
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn accepts_bus_config(&self, config: &BusConfig) -> bool {
+        let no_aux_busses = AuxiliaryIOConfig {
+            num_busses: 0,
+            num_channels: 0,
+        };
+
+        config.num_input_channels == 0
+            && config.num_output_channels == 0
+            && config.aux_input_busses == no_aux_busses
+            && config.aux_output_busses == no_aux_busses
+    }
+
+    fn initialize(
+        &mut self,
+        _bus_config: &BusConfig,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        self.sample_rate = buffer_config.sample_rate;
+
+        true
+    }
+
+    fn reset(&mut self) {
+        // We cannot send events from here, so the pending note-offs are sent with the next buffer.
+        self.clock = BeatClock::new();
+        self.held_notes.clear();
+        self.latched_notes.clear();
+        self.position = 0;
+        self.last_step = None;
+        self.rng = Rng::new(RANDOM_SEED);
+        self.flush_pending = true;
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        if std::mem::take(&mut self.flush_pending) {
+            while let Some(note_off) = self.scheduler.pop_before(u32::MAX) {
+                context.send_event(note_off.with_timing(0));
+            }
+        }
+
+        let num_samples = buffer.samples() as u32;
+        let transport = context.transport();
+        self.clock.update(
+            transport.playing,
+            transport.pos_beats(),
+            transport.tempo,
+            self.sample_rate,
+        );
+
+        // The steps of this buffer depend on the rate, so it can only change between buffers.
+        let rate = self.params.rate.value();
+        let step_length = self.clock.beats_to_samples(rate.beats());
+
+        let mut next_event = context.next_event();
+        for step in self.clock.steps(rate, num_samples) {
+            // Notes played at the same time as the step are already part of it.
+            while let Some(in_event) = next_event.filter(|event| event.timing() <= step.timing) {
+                self.forward_event(in_event, context);
+                next_event = context.next_event();
+            }
+
+            if self.last_step == Some(step.index) {
+                continue;
+            }
+            self.last_step = Some(step.index);
+
+            while let Some(note_off) = self.scheduler.pop_before(step.timing + 1) {
+                context.send_event(note_off);
+            }
+            let settings = self.current_settings();
+            let note_length = ((step_length * self.params.gate.value() as f64) as u64).max(1);
+            if let Some(note_on) = self.play_step(step.timing, note_length, settings) {
+                context.send_event(note_on);
+            }
+        }
+
+        while let Some(in_event) = next_event {
+            self.forward_event(in_event, context);
+            next_event = context.next_event();
+        }
+
+        while let Some(note_off) = self.scheduler.pop_before(num_samples) {
+            context.send_event(note_off);
+        }
+        self.scheduler.advance(num_samples);
+        self.clock.advance(num_samples);
+
+        // Latched notes keep on playing, even if there is no more input.
+        ProcessStatus::KeepAlive
+    }
+}
+
+impl ClapPlugin for RisArpeggiator {
+    const CLAP_ID: &'static str = "me.leiner.ris.arpeggiator";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some(env!("CARGO_PKG_DESCRIPTION"));
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] =
+        &[ClapFeature::Custom("MIDI"), ClapFeature::Utility];
+}
+
+impl Vst3Plugin for RisArpeggiator {
+    const VST3_CLASS_ID: [u8; 16] = *b"risArpeggiator..";
+    const VST3_CATEGORIES: &'static str = "Fx|Tools";
+}
+
+nih_export_clap!(RisArpeggiator);
+nih_export_vst3!(RisArpeggiator);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rismidi::testing::{note_off, note_on};
+
+    const SETTINGS: ArpSettings = ArpSettings {
+        mode: ArpMode::Up,
+        octaves: 1,
+        latch: false,
+        output_channel: None,
+    };
+
+    fn next_notes(processor: &mut RisArpeggiator, settings: ArpSettings, count: usize) -> Vec<u8> {
+        (0..count)
+            .filter_map(|_| processor.next_note(settings))
+            .map(|held| held.note)
+            .collect()
+    }
+
+    #[test]
+    fn up_mode_spans_octaves() {
+        let mut processor = RisArpeggiator::default();
+        processor.handle_note_event(&note_on(0, 0, 64, 0.5));
+        processor.handle_note_event(&note_on(0, 0, 60, 0.5));
+
+        let settings = ArpSettings {
+            octaves: 2,
+            ..SETTINGS
+        };
+        assert_eq!(
+            next_notes(&mut processor, settings, 5),
+            vec![60, 64, 72, 76, 60]
+        );
+    }
+
+    #[test]
+    fn up_down_mode_does_not_repeat_turning_points() {
+        let mut processor = RisArpeggiator::default();
+        for note in [67, 60, 64] {
+            processor.handle_note_event(&note_on(0, 0, note, 0.5));
+        }
+
+        let settings = ArpSettings {
+            mode: ArpMode::UpDown,
+            ..SETTINGS
+        };
+        assert_eq!(
+            next_notes(&mut processor, settings, 6),
+            vec![60, 64, 67, 64, 60, 64]
+        );
+    }
+
+    #[test]
+    fn as_played_mode_keeps_order() {
+        let mut processor = RisArpeggiator::default();
+        for note in [67, 60, 64] {
+            processor.handle_note_event(&note_on(0, 0, note, 0.5));
+        }
+
+        let settings = ArpSettings {
+            mode: ArpMode::AsPlayed,
+            ..SETTINGS
+        };
+        assert_eq!(
+            next_notes(&mut processor, settings, 4),
+            vec![67, 60, 64, 67]
+        );
+    }
+
+    #[test]
+    fn latch_keeps_released_chord_until_next_chord() {
+        let mut processor = RisArpeggiator::default();
+        let settings = ArpSettings {
+            latch: true,
+            ..SETTINGS
+        };
+
+        for note in [60, 64] {
+            processor.handle_note_event(&note_on(0, 0, note, 0.5));
+        }
+        for note in [60, 64] {
+            processor.handle_note_event(&note_off(0, 0, note));
+        }
+        assert_eq!(next_notes(&mut processor, settings, 2), vec![60, 64]);
+        assert_eq!(next_notes(&mut processor, SETTINGS, 1), Vec::<u8>::new());
+
+        processor.handle_note_event(&note_on(0, 0, 70, 0.5));
+        assert_eq!(next_notes(&mut processor, settings, 2), vec![70, 70]);
+    }
+
+    #[test]
+    fn steps_are_sent_to_output_channel_with_note_off() {
+        let mut processor = RisArpeggiator::default();
+        processor.handle_note_event(&note_on(0, 0, 60, 0.5));
+
+        let settings = ArpSettings {
+            output_channel: Some(MidiChannel::Channel5),
+            ..SETTINGS
+        };
+        let played = processor.play_step(100, 50, settings);
+        assert_eq!(
+            played,
+            Some(NoteEvent::NoteOn {
+                timing: 100,
+                voice_id: None,
+                channel: 4,
+                note: 60,
+                velocity: 0.5,
+            })
+        );
+
+        assert_eq!(processor.scheduler.pop_before(150), None);
+        assert_eq!(
+            processor.scheduler.pop_before(151),
+            Some(NoteEvent::NoteOff {
+                timing: 150,
+                voice_id: None,
+                channel: 4,
+                note: 60,
+                velocity: 0.0,
+            })
+        );
+    }
+
+    #[test]
+    fn note_offs_are_kept_on_reset() {
+        let mut processor = RisArpeggiator::default();
+        processor.handle_note_event(&note_on(0, 0, 60, 0.5));
+        processor.play_step(100, 50, SETTINGS);
+
+        processor.reset();
+        assert!(processor.flush_pending);
+        assert_eq!(
+            processor.scheduler.pop_before(u32::MAX),
+            Some(note_off(150, 0, 60))
+        );
+    }
+}
@@ -0,0 +1,353 @@
+use nih_plug::prelude::*;
+use rismidi::{params::CcNumberParam, MidiChannel, OptionalMidiChannelParam};
+use std::sync::Arc;
+
+const MIDI_CHANNEL_FROM_NIH_PLUG: &str = "MIDI channels from nih_plug must be in range 0..=15";
+
+/// The number of independent mappings.
+const NUM_SLOTS: usize = 8;
+
+struct RisCcRemap {
+    params: Arc<RisCcRemapParams>,
+}
+
+#[derive(Params)]
+struct RisCcRemapParams {
+    #[nested(array, group = "Slot")]
+    pub slots: [SlotParams; NUM_SLOTS],
+}
+
+/// The parameters of a single mapping.
+#[derive(Params)]
+struct SlotParams {
+    #[id = "enabled"]
+    pub enabled: BoolParam,
+
+    #[nested(id_prefix = "channel")]
+    pub channel: OptionalMidiChannelParam,
+
+    #[nested(id_prefix = "in_cc")]
+    pub in_cc: CcNumberParam,
+
+    #[id = "in_min"]
+    pub in_min: FloatParam,
+
+    #[id = "in_max"]
+    pub in_max: FloatParam,
+
+    #[nested(id_prefix = "out_cc")]
+    pub out_cc: CcNumberParam,
+
+    #[id = "out_min"]
+    pub out_min: FloatParam,
+
+    #[id = "out_max"]
+    pub out_max: FloatParam,
+
+    #[id = "invert"]
+    pub invert: BoolParam,
+}
+
+/// Translates one MIDI CC into another.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct CcMapping {
+    channel: Option<MidiChannel>,
+    in_cc: u8,
+    in_range: (f32, f32),
+    out_cc: u8,
+    out_range: (f32, f32),
+    invert: bool,
+}
+
+impl Default for RisCcRemap {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(RisCcRemapParams::default()),
+        }
+    }
+}
+
+impl Default for RisCcRemapParams {
+    fn default() -> Self {
+        Self {
+            slots: std::array::from_fn(|idx| SlotParams::new(idx + 1)),
+        }
+    }
+}
+
+/// Creates a parameter for a CC value, shown in percent.
+fn cc_value_param(name: String, default: f32) -> FloatParam {
+    FloatParam::new(name, default, FloatRange::Linear { min: 0.0, max: 1.0 })
+        .with_unit(" %")
+        .with_value_to_string(formatters::v2s_f32_percentage(0))
+        .with_string_to_value(formatters::s2v_f32_percentage())
+}
+
+impl SlotParams {
+    /// Creates the parameters for the slot with the given (1-based) number.
+    fn new(slot: usize) -> Self {
+        Self {
+            enabled: BoolParam::new(format!("Slot {slot} Enabled"), false),
+            channel: OptionalMidiChannelParam::new(format!("Slot {slot} Channel"), None)
+                .with_none_selected_description("All"),
+            in_cc: CcNumberParam::new(format!("Slot {slot} Input CC"), 1),
+            in_min: cc_value_param(format!("Slot {slot} Input Min"), 0.0),
+            in_max: cc_value_param(format!("Slot {slot} Input Max"), 1.0),
+            out_cc: CcNumberParam::new(format!("Slot {slot} Output CC"), 1),
+            out_min: cc_value_param(format!("Slot {slot} Output Min"), 0.0),
+            out_max: cc_value_param(format!("Slot {slot} Output Max"), 1.0),
+            invert: BoolParam::new(format!("Slot {slot} Invert"), false),
+        }
+    }
+
+    /// Gets the mapping from the current parameter values, if the slot is enabled.
+    fn mapping(&self) -> Option<CcMapping> {
+        if !self.enabled.value() {
+            return None;
+        }
+
+        Some(CcMapping {
+            channel: self.channel.value(),
+            in_cc: self.in_cc.value(),
+            in_range: (self.in_min.value(), self.in_max.value()),
+            out_cc: self.out_cc.value(),
+            out_range: (self.out_min.value(), self.out_max.value()),
+            invert: self.invert.value(),
+        })
+    }
+}
+
+impl CcMapping {
+    /// Returns `true` if the mapping applies to the given CC.
+    fn matches(&self, channel: MidiChannel, cc: u8) -> bool {
+        cc == self.in_cc && (self.channel.is_none() || self.channel == Some(channel))
+    }
+
+    /// Maps a (normalized) CC value from the input range to the output range. Values outside
+    /// of the input range are limited to it.
+    fn map_value(&self, value: f32) -> f32 {
+        let (in_min, in_max) = self.in_range;
+        let position = if in_max != in_min {
+            ((value - in_min) / (in_max - in_min)).clamp(0.0, 1.0)
+        } else if value >= in_min {
+            1.0
+        } else {
+            0.0
+        };
+        let position = if self.invert {
+            1.0 - position
+        } else {
+            position
+        };
+
+        let (out_min, out_max) = self.out_range;
+        out_min + position * (out_max - out_min)
+    }
+}
+
+impl RisCcRemap {
+    /// Gets all enabled mappings from the current parameter values.
+    fn current_mappings(&self) -> [Option<CcMapping>; NUM_SLOTS] {
+        std::array::from_fn(|idx| self.params.slots[idx].mapping())
+    }
+
+    /// Passes the remapped events for `in_event` to `send`. A CC can be matched by several
+    /// mappings, in which case one event is sent for each of them.
+    fn process_event(
+        &mut self,
+        in_event: NoteEvent,
+        mappings: &[Option<CcMapping>],
+        mut send: impl FnMut(NoteEvent),
+    ) {
+        let NoteEvent::MidiCC {
+            timing,
+            channel,
+            cc,
+            value,
+        } = in_event
+        else {
+            send(in_event);
+            return;
+        };
+
+        let in_channel =
+            MidiChannel::try_from_0_based(channel.into()).expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+        let mut matched = false;
+        for mapping in mappings
+            .iter()
+            .flatten()
+            .filter(|mapping| mapping.matches(in_channel, cc))
+        {
+            matched = true;
+            send(NoteEvent::MidiCC {
+                timing,
+                channel,
+                cc: mapping.out_cc,
+                value: mapping.map_value(value),
+            });
+        }
+
+        if !matched {
+            send(in_event);
+        }
+    }
+}
+
+impl Plugin for RisCcRemap {
+    const NAME: &'static str = env!("CARGO_PKG_NAME");
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const VENDOR: &'static str = "Simon Leiner";
+    const EMAIL: &'static str = "rismidi@leiner.me";
+
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const DEFAULT_INPUT_CHANNELS: u32 = 0;
+    const DEFAULT_OUTPUT_CHANNELS: u32 = 0;
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::MidiCCs;
+
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn accepts_bus_config(&self, config: &BusConfig) -> bool {
+        let no_aux_busses = AuxiliaryIOConfig {
+            num_busses: 0,
+            num_channels: 0,
+        };
+
+        config.num_input_channels == 0
+            && config.num_output_channels == 0
+            && config.aux_input_busses == no_aux_busses
+            && config.aux_output_busses == no_aux_busses
+    }
+
+    fn process(
+        &mut self,
+        _buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        while let Some(in_event) = context.next_event() {
+            let mappings = self.current_mappings();
+            self.process_event(in_event, &mappings, |event| context.send_event(event));
+        }
+
+        ProcessStatus::Normal
+    }
+}
+
+impl ClapPlugin for RisCcRemap {
+    const CLAP_ID: &'static str = "me.leiner.ris.cc_remap";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some(env!("CARGO_PKG_DESCRIPTION"));
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] =
+        &[ClapFeature::Custom("MIDI"), ClapFeature::Utility];
+}
+
+impl Vst3Plugin for RisCcRemap {
+    const VST3_CLASS_ID: [u8; 16] = *b"risCcRemap......";
+    const VST3_CATEGORIES: &'static str = "Fx|Tools";
+}
+
+nih_export_clap!(RisCcRemap);
+nih_export_vst3!(RisCcRemap);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rismidi::testing::{cc, collect_events};
+
+    const MOD_TO_FILTER: CcMapping = CcMapping {
+        channel: None,
+        in_cc: 1,
+        in_range: (0.0, 1.0),
+        out_cc: 74,
+        out_range: (0.0, 1.0),
+        invert: false,
+    };
+
+    #[test]
+    fn values_are_scaled_between_ranges() {
+        let mapping = CcMapping {
+            in_range: (0.5, 1.0),
+            out_range: (0.25, 0.75),
+            ..MOD_TO_FILTER
+        };
+
+        let in_event = cc(0, 0, 1, 0.75);
+        let out_events = collect_events(|send| {
+            RisCcRemap::default().process_event(in_event, &[Some(mapping)], send)
+        });
+        assert_eq!(out_events, vec![cc(0, 0, 74, 0.5)]);
+
+        let in_event = cc(0, 0, 1, 0.25);
+        let out_events = collect_events(|send| {
+            RisCcRemap::default().process_event(in_event, &[Some(mapping)], send)
+        });
+        assert_eq!(out_events, vec![cc(0, 0, 74, 0.25)]);
+    }
+
+    #[test]
+    fn values_can_be_inverted() {
+        let mapping = CcMapping {
+            invert: true,
+            ..MOD_TO_FILTER
+        };
+
+        let in_event = cc(0, 0, 1, 0.25);
+        let out_events = collect_events(|send| {
+            RisCcRemap::default().process_event(in_event, &[Some(mapping)], send)
+        });
+        assert_eq!(out_events, vec![cc(0, 0, 74, 0.75)]);
+    }
+
+    #[test]
+    fn mappings_can_be_restricted_to_a_channel() {
+        let mapping = CcMapping {
+            channel: Some(MidiChannel::Channel3),
+            ..MOD_TO_FILTER
+        };
+
+        let in_event = cc(0, 2, 1, 0.5);
+        let out_events = collect_events(|send| {
+            RisCcRemap::default().process_event(in_event, &[Some(mapping)], send)
+        });
+        assert_eq!(out_events, vec![cc(0, 2, 74, 0.5)]);
+
+        let in_event = cc(0, 3, 1, 0.5);
+        let out_events = collect_events(|send| {
+            RisCcRemap::default().process_event(in_event, &[Some(mapping)], send)
+        });
+        assert_eq!(out_events, vec![cc(0, 3, 1, 0.5)]);
+    }
+
+    #[test]
+    fn one_cc_can_be_sent_to_several_ccs() {
+        let mappings = [
+            Some(MOD_TO_FILTER),
+            None,
+            Some(CcMapping {
+                out_cc: 71,
+                invert: true,
+                ..MOD_TO_FILTER
+            }),
+        ];
+
+        let in_event = cc(0, 0, 1, 1.0);
+        let out_events =
+            collect_events(|send| RisCcRemap::default().process_event(in_event, &mappings, send));
+        assert_eq!(out_events, vec![cc(0, 0, 74, 1.0), cc(0, 0, 71, 0.0)]);
+
+        let in_event = cc(0, 0, 2, 1.0);
+        let out_events =
+            collect_events(|send| RisCcRemap::default().process_event(in_event, &mappings, send));
+        assert_eq!(out_events, vec![cc(0, 0, 2, 1.0)]);
+    }
+}
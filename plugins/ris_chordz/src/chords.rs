@@ -0,0 +1,177 @@
+use rismidi::NUM_MIDI_NOTES;
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// The maximum number of notes in a chord.
+pub const MAX_CHORD_NOTES: usize = 8;
+
+/// A single note of a chord, relative to the key that triggers the chord.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChordMember {
+    /// Distance to the triggering key, in semitones.
+    pub interval: i8,
+
+    /// Velocity relative to the velocity of the triggering key.
+    pub velocity: f32,
+}
+
+/// A set of notes that is played for a single key.
+///
+/// The notes are stored in a fixed-size array, so that chords can be copied around in
+/// [`nih_plug::prelude::Plugin::process`] without allocating.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Chord {
+    members: [Option<ChordMember>; MAX_CHORD_NOTES],
+}
+
+impl Chord {
+    /// A chord which only consists of the triggering key itself.
+    pub fn single_note() -> Self {
+        Self::from_intervals(&[0])
+    }
+
+    /// Creates a chord from the given intervals, all of them at the velocity of the triggering
+    /// key. Intervals beyond [`MAX_CHORD_NOTES`] are ignored.
+    pub fn from_intervals(intervals: &[i8]) -> Self {
+        let mut chord = Self {
+            members: [None; MAX_CHORD_NOTES],
+        };
+        for (member, &interval) in chord.members.iter_mut().zip(intervals) {
+            *member = Some(ChordMember {
+                interval,
+                velocity: 1.0,
+            });
+        }
+
+        chord
+    }
+
+    /// Creates a chord from its notes by position, where positions may be empty. Returns
+    /// [`None`] if all positions are empty.
+    pub fn from_slots(slots: [Option<ChordMember>; MAX_CHORD_NOTES]) -> Option<Self> {
+        slots
+            .iter()
+            .any(Option::is_some)
+            .then_some(Self { members: slots })
+    }
+
+    /// Creates a chord from notes that have been played together. The lowest note is the
+    /// triggering key, and the loudest note is played at the velocity of the triggering key.
+    /// Returns the triggering key along with the chord, or [`None`] if no notes were given.
+    pub fn learn(notes: &[(u8, f32)]) -> Option<(u8, Self)> {
+        let root = notes.iter().map(|&(note, _)| note).min()?;
+        let max_velocity = notes
+            .iter()
+            .map(|&(_, velocity)| velocity)
+            .fold(0.0f32, f32::max);
+
+        let mut chord = Self {
+            members: [None; MAX_CHORD_NOTES],
+        };
+        for (member, &(note, velocity)) in chord.members.iter_mut().zip(notes) {
+            *member = Some(ChordMember {
+                interval: (note - root) as i8,
+                velocity: if max_velocity > 0.0 {
+                    velocity / max_velocity
+                } else {
+                    1.0
+                },
+            });
+        }
+
+        Some((root, chord))
+    }
+
+    /// Iterates over the notes of the chord.
+    pub fn members(&self) -> impl Iterator<Item = &ChordMember> + '_ {
+        self.members.iter().flatten()
+    }
+
+    /// The notes of the chord by position, including empty positions.
+    pub fn slots(&self) -> [Option<ChordMember>; MAX_CHORD_NOTES] {
+        self.members
+    }
+}
+
+/// All chords known to the plugin. This is what gets persisted with the plugin's state.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChordDefinitions {
+    /// The chord that is played for every key, unless chords are assigned per key.
+    pub global: Chord,
+
+    /// The chord for each MIDI note, if any. Keys without a chord are passed through.
+    #[serde(deserialize_with = "deserialize_per_key")]
+    pub per_key: Vec<Option<Chord>>,
+}
+
+impl ChordDefinitions {
+    /// Gets the chord for the given key.
+    pub fn chord_for(&self, note: u8, per_key: bool) -> Option<Chord> {
+        if per_key {
+            self.per_key.get(note as usize).copied().flatten()
+        } else {
+            Some(self.global)
+        }
+    }
+
+    /// Stores a chord as the global chord and as the chord of its triggering key.
+    pub fn insert(&mut self, root: u8, chord: Chord) {
+        self.global = chord;
+        if let Some(key_chord) = self.per_key.get_mut(root as usize) {
+            *key_chord = Some(chord);
+        }
+    }
+
+    /// Gets the chord of `key`, or the global chord if `key` is [`None`].
+    pub fn get(&self, key: Option<u8>) -> Option<Chord> {
+        match key {
+            Some(key) => self.chord_for(key, true),
+            None => Some(self.global),
+        }
+    }
+
+    /// Replaces the chord of `key`, or the global chord if `key` is [`None`]. The global chord
+    /// cannot be removed, so it is kept if `chord` is [`None`].
+    pub fn set(&mut self, key: Option<u8>, chord: Option<Chord>) {
+        match key {
+            Some(key) => {
+                if let Some(key_chord) = self.per_key.get_mut(key as usize) {
+                    *key_chord = chord;
+                }
+            }
+            None => {
+                if let Some(chord) = chord {
+                    self.global = chord;
+                }
+            }
+        }
+    }
+
+    /// Copies all chords into `target` without allocating. Keys which `self` has no chord for
+    /// lose their chord in `target`.
+    pub fn copy_to(&self, target: &mut ChordDefinitions) {
+        target.global = self.global;
+        for (note, target_chord) in target.per_key.iter_mut().enumerate() {
+            *target_chord = self.per_key.get(note).copied().flatten();
+        }
+    }
+}
+
+/// Reads the chords per key, with exactly one entry per MIDI note. This way, the chords can
+/// always be copied in full, even if the state was saved by another version or edited by hand.
+fn deserialize_per_key<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Option<Chord>>, D::Error> {
+    let mut per_key = Vec::<Option<Chord>>::deserialize(deserializer)?;
+    per_key.resize(NUM_MIDI_NOTES as usize, None);
+    Ok(per_key)
+}
+
+impl Default for ChordDefinitions {
+    fn default() -> Self {
+        Self {
+            // A major triad
+            global: Chord::from_intervals(&[0, 4, 7]),
+            per_key: vec![None; NUM_MIDI_NOTES as usize],
+        }
+    }
+}
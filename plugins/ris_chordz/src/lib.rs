@@ -0,0 +1,728 @@
+mod chords;
+
+use chords::{Chord, ChordDefinitions, ChordMember, MAX_CHORD_NOTES};
+use nih_plug::prelude::*;
+use rismidi::{HeldNotes, MidiChannel, MIN_VELOCITY, NUM_MIDI_CHANNELS, NUM_MIDI_NOTES};
+use std::sync::{Arc, RwLock};
+
+const MIDI_CHANNEL_FROM_NIH_PLUG: &str = "MIDI channels from nih_plug must be in range 0..=15";
+
+/// The value of the "Edit Key" parameter for editing the global chord.
+const EDIT_GLOBAL: i32 = -1;
+
+/// The notes that have been sent for a held key, or all [`None`] if the key is not held.
+type SoundingChord = [Option<u8>; MAX_CHORD_NOTES];
+
+struct RisChordz {
+    params: Arc<RisChordzParams>,
+
+    /// A copy of the persisted chords, so that they can be read without waiting for a lock.
+    chords: ChordDefinitions,
+
+    /// Whether `chords` contains a learned chord which has not been persisted yet.
+    chords_changed: bool,
+
+    /// The notes sent for each held key, indexed by [`note_index`]. Note-offs are based on this
+    /// instead of the current chords, because those might have changed since the note-on.
+    sounding: Vec<SoundingChord>,
+
+    /// For each output note, the number of held keys that play it, indexed by [`note_index`].
+    /// A note-off is only sent once no key plays the note anymore.
+    note_counts: Vec<u8>,
+
+    /// The keys held while learning. Once all of them are released, the chord is stored.
+    learn_keys: HeldNotes,
+    captured: [(u8, f32); MAX_CHORD_NOTES],
+    num_captured: usize,
+
+    /// The values of the chord note parameters during the last buffer, or [`None`] after a
+    /// reset. Only changes of these parameters are applied, so that they do not overwrite
+    /// learned chords or chords loaded with the plugin's state.
+    last_edit: Option<ChordEdit>,
+}
+
+#[derive(Params)]
+struct RisChordzParams {
+    #[id = "per_key"]
+    pub per_key: BoolParam,
+
+    #[id = "learn"]
+    pub learn: BoolParam,
+
+    #[id = "edit_key"]
+    pub edit_key: IntParam,
+
+    #[nested(array, group = "Chord Note")]
+    pub chord_notes: [ChordNoteParams; MAX_CHORD_NOTES],
+
+    #[persist = "chords"]
+    pub chords: RwLock<ChordDefinitions>,
+}
+
+/// The parameters of a single note of the edited chord.
+#[derive(Params)]
+struct ChordNoteParams {
+    #[id = "enabled"]
+    pub enabled: BoolParam,
+
+    #[id = "interval"]
+    pub interval: IntParam,
+
+    #[id = "velocity"]
+    pub velocity: FloatParam,
+}
+
+/// The values of the chord note parameters, together with the key whose chord they edit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ChordEdit {
+    /// The key whose chord is edited, or [`None`] for the global chord.
+    key: Option<u8>,
+
+    notes: [ChordNoteEdit; MAX_CHORD_NOTES],
+}
+
+/// The values of the parameters of a single chord note.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ChordNoteEdit {
+    enabled: bool,
+    interval: i8,
+    velocity: f32,
+}
+
+impl Default for RisChordz {
+    fn default() -> Self {
+        let num_notes = NUM_MIDI_NOTES as usize * NUM_MIDI_CHANNELS as usize;
+
+        Self {
+            params: Arc::new(RisChordzParams::default()),
+            chords: ChordDefinitions::default(),
+            chords_changed: false,
+            sounding: vec![[None; MAX_CHORD_NOTES]; num_notes],
+            note_counts: vec![0; num_notes],
+            learn_keys: HeldNotes::new(),
+            captured: [(0, 0.0); MAX_CHORD_NOTES],
+            num_captured: 0,
+            last_edit: None,
+        }
+    }
+}
+
+impl Default for RisChordzParams {
+    fn default() -> Self {
+        Self {
+            per_key: BoolParam::new("Per Key", false),
+            learn: BoolParam::new("Learn", false),
+            edit_key: IntParam::new(
+                "Edit Key",
+                EDIT_GLOBAL,
+                IntRange::Linear {
+                    min: EDIT_GLOBAL,
+                    max: NUM_MIDI_NOTES as i32 - 1,
+                },
+            )
+            .with_value_to_string(Arc::new(|key| match key {
+                EDIT_GLOBAL => String::from("Global"),
+                key => key.to_string(),
+            }))
+            .with_string_to_value(Arc::new(|string| match string.trim() {
+                "Global" => Some(EDIT_GLOBAL),
+                string => string.parse().ok(),
+            })),
+            // The same major triad as the default chord.
+            chord_notes: std::array::from_fn(|idx| match idx {
+                0 => ChordNoteParams::new(idx + 1, true, 0),
+                1 => ChordNoteParams::new(idx + 1, true, 4),
+                2 => ChordNoteParams::new(idx + 1, true, 7),
+                _ => ChordNoteParams::new(idx + 1, false, 0),
+            }),
+            chords: RwLock::new(ChordDefinitions::default()),
+        }
+    }
+}
+
+impl ChordNoteParams {
+    /// Creates the parameters for the chord note with the given (1-based) number.
+    fn new(number: usize, enabled: bool, interval: i32) -> Self {
+        Self {
+            enabled: BoolParam::new(format!("Chord Note {number} Enabled"), enabled),
+            interval: IntParam::new(
+                format!("Chord Note {number} Interval"),
+                interval,
+                IntRange::Linear { min: -48, max: 48 },
+            )
+            .with_unit(" st"),
+            velocity: FloatParam::new(
+                format!("Chord Note {number} Velocity"),
+                1.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit(" %")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+        }
+    }
+
+    /// Gets the chord note from the current parameter values.
+    fn edit(&self) -> ChordNoteEdit {
+        ChordNoteEdit {
+            enabled: self.enabled.value(),
+            interval: self.interval.value() as i8,
+            velocity: self.velocity.value(),
+        }
+    }
+}
+
+/// The position of a note in the per-note tables of [`RisChordz`].
+fn note_index(channel: MidiChannel, note: u8) -> usize {
+    channel.to_0_based() as usize * NUM_MIDI_NOTES as usize + note as usize
+}
+
+impl RisChordz {
+    /// Gets the edited chord from the current parameter values.
+    fn current_edit(&self) -> ChordEdit {
+        let edit_key = self.params.edit_key.value();
+
+        ChordEdit {
+            key: (edit_key != EDIT_GLOBAL).then_some(edit_key as u8),
+            notes: std::array::from_fn(|idx| self.params.chord_notes[idx].edit()),
+        }
+    }
+
+    /// Applies the chord note parameters which have changed since the last buffer to the chord
+    /// of the edited key. The parameters do not show the stored chord of a key, so only the
+    /// changed values are written, and switching keys changes nothing.
+    fn apply_edit(&mut self, edit: ChordEdit) {
+        let Some(last_edit) = self.last_edit.replace(edit) else {
+            return;
+        };
+        if last_edit.key != edit.key || last_edit == edit {
+            return;
+        }
+
+        let mut slots = self
+            .chords
+            .get(edit.key)
+            .map_or([None; MAX_CHORD_NOTES], |chord| chord.slots());
+        for ((slot, note), last_note) in slots.iter_mut().zip(edit.notes).zip(last_edit.notes) {
+            let member = ChordMember {
+                interval: note.interval,
+                velocity: note.velocity,
+            };
+            if note.enabled != last_note.enabled {
+                *slot = note.enabled.then_some(member);
+            } else if note.enabled && note != last_note {
+                let slot = slot.get_or_insert(member);
+                if note.interval != last_note.interval {
+                    slot.interval = note.interval;
+                }
+                if note.velocity != last_note.velocity {
+                    slot.velocity = note.velocity;
+                }
+            }
+        }
+
+        self.chords.set(edit.key, Chord::from_slots(slots));
+        self.chords_changed = true;
+    }
+
+    /// Exchanges chords with the persisted state: learned chords are stored, and chords loaded
+    /// by the host are picked up. If the state is locked right now, we try again next time.
+    fn sync_chords(&mut self) {
+        if self.chords_changed {
+            if let Ok(mut chords) = self.params.chords.try_write() {
+                self.chords.copy_to(&mut chords);
+                self.chords_changed = false;
+            }
+        } else if let Ok(chords) = self.params.chords.try_read() {
+            chords.copy_to(&mut self.chords);
+        }
+    }
+
+    /// Turns `in_event` into the events of its chord and passes them to `send`.
+    fn process_event(
+        &mut self,
+        in_event: NoteEvent,
+        per_key: bool,
+        learn: bool,
+        mut send: impl FnMut(NoteEvent),
+    ) {
+        match in_event {
+            NoteEvent::NoteOn {
+                timing,
+                voice_id: _,
+                channel,
+                note,
+                velocity,
+            } if note < NUM_MIDI_NOTES => {
+                let in_channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+
+                // If the key is already held, its previous chord ends here. Otherwise, it would
+                // never get its note-offs.
+                self.release_chord(timing, in_channel, note, 0.0, &mut send);
+
+                let chord = if learn {
+                    self.capture(in_channel, note, velocity);
+                    Chord::single_note()
+                } else {
+                    self.chords
+                        .chord_for(note, per_key)
+                        .unwrap_or_else(Chord::single_note)
+                };
+                self.play_chord(timing, in_channel, note, velocity, chord, &mut send);
+            }
+            NoteEvent::NoteOff {
+                timing,
+                voice_id: _,
+                channel,
+                note,
+                velocity,
+            } if note < NUM_MIDI_NOTES => {
+                let in_channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+
+                if self.learn_keys.note_off(in_channel, note).is_some()
+                    && self.learn_keys.is_empty()
+                {
+                    // If learning was switched off while the chord was held, it is discarded.
+                    if learn {
+                        self.store_captured_chord();
+                    } else {
+                        self.num_captured = 0;
+                    }
+                }
+
+                if !self.release_chord(timing, in_channel, note, velocity, &mut send) {
+                    send(in_event);
+                }
+            }
+            _ => send(in_event),
+        }
+    }
+
+    /// Remembers a note played while learning.
+    fn capture(&mut self, channel: MidiChannel, note: u8, velocity: f32) {
+        // A new chord starts once all keys of the previous one have been released.
+        if self.learn_keys.is_empty() {
+            self.num_captured = 0;
+        }
+        self.learn_keys.note_on(channel, note, velocity);
+
+        // The captured notes are kept sorted, so that chords are played from the bottom up.
+        let captured = &mut self.captured[..self.num_captured];
+        if let Err(position) = captured.binary_search_by_key(&note, |&(note, _)| note) {
+            if self.num_captured < MAX_CHORD_NOTES {
+                self.num_captured += 1;
+                self.captured[position..self.num_captured].rotate_right(1);
+                self.captured[position] = (note, velocity);
+            }
+        }
+    }
+
+    /// Stores the chord that has been played while learning.
+    fn store_captured_chord(&mut self) {
+        if let Some((root, chord)) = Chord::learn(&self.captured[..self.num_captured]) {
+            self.chords.insert(root, chord);
+            self.chords_changed = true;
+        }
+        self.num_captured = 0;
+    }
+
+    /// Sends the note-ons of `chord`, triggered by a key.
+    fn play_chord(
+        &mut self,
+        timing: u32,
+        channel: MidiChannel,
+        note: u8,
+        velocity: f32,
+        chord: Chord,
+        send: &mut impl FnMut(NoteEvent),
+    ) {
+        let mut sounding: SoundingChord = [None; MAX_CHORD_NOTES];
+        let mut num_sounding = 0;
+
+        for member in chord.members() {
+            let chord_note = note as i32 + member.interval as i32;
+            if !(0..NUM_MIDI_NOTES as i32).contains(&chord_note) {
+                continue;
+            }
+            let chord_note = chord_note as u8;
+            if sounding[..num_sounding].contains(&Some(chord_note)) {
+                continue;
+            }
+
+            sounding[num_sounding] = Some(chord_note);
+            num_sounding += 1;
+            self.note_counts[note_index(channel, chord_note)] += 1;
+
+            send(NoteEvent::NoteOn {
+                timing,
+                voice_id: None,
+                channel: channel.to_0_based(),
+                note: chord_note,
+                velocity: (velocity * member.velocity).clamp(MIN_VELOCITY, 1.0),
+            });
+        }
+
+        self.sounding[note_index(channel, note)] = sounding;
+    }
+
+    /// Sends the note-offs for the chord of a released key. Notes which are still played by
+    /// other keys are kept. Returns `false` if the key was not held.
+    fn release_chord(
+        &mut self,
+        timing: u32,
+        channel: MidiChannel,
+        note: u8,
+        velocity: f32,
+        send: &mut impl FnMut(NoteEvent),
+    ) -> bool {
+        let sounding = std::mem::replace(
+            &mut self.sounding[note_index(channel, note)],
+            [None; MAX_CHORD_NOTES],
+        );
+        if sounding.iter().all(Option::is_none) {
+            return false;
+        }
+
+        for chord_note in sounding.into_iter().flatten() {
+            let count = &mut self.note_counts[note_index(channel, chord_note)];
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                send(NoteEvent::NoteOff {
+                    timing,
+                    voice_id: None,
+                    channel: channel.to_0_based(),
+                    note: chord_note,
+                    velocity,
+                });
+            }
+        }
+
+        true
+    }
+}
+
+impl Plugin for RisChordz {
+    const NAME: &'static str = env!("CARGO_PKG_NAME");
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const VENDOR: &'static str = "Simon Leiner";
+    const EMAIL: &'static str = "rismidi@leiner.me";
+
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const DEFAULT_INPUT_CHANNELS: u32 = 0;
+    const DEFAULT_OUTPUT_CHANNELS: u32 = 0;
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::MidiCCs;
+
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn accepts_bus_config(&self, config: &BusConfig) -> bool {
+        let no_aux_busses = AuxiliaryIOConfig {
+            num_busses: 0,
+            num_channels: 0,
+        };
+
+        config.num_input_channels == 0
+            && config.num_output_channels == 0
+            && config.aux_input_busses == no_aux_busses
+            && config.aux_output_busses == no_aux_busses
+    }
+
+    fn reset(&mut self) {
+        self.sounding.fill([None; MAX_CHORD_NOTES]);
+        self.note_counts.fill(0);
+        self.learn_keys.clear();
+        self.num_captured = 0;
+        // This is also called after the plugin's state has been loaded.
+        self.last_edit = None;
+    }
+
+    fn process(
+        &mut self,
+        _buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        let edit = self.current_edit();
+        self.apply_edit(edit);
+        self.sync_chords();
+
+        while let Some(in_event) = context.next_event() {
+            let per_key = self.params.per_key.value();
+            let learn = self.params.learn.value();
+            self.process_event(in_event, per_key, learn, |event| context.send_event(event));
+        }
+
+        ProcessStatus::Normal
+    }
+}
+
+impl ClapPlugin for RisChordz {
+    const CLAP_ID: &'static str = "me.leiner.ris.chordz";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some(env!("CARGO_PKG_DESCRIPTION"));
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] =
+        &[ClapFeature::Custom("MIDI"), ClapFeature::Utility];
+}
+
+impl Vst3Plugin for RisChordz {
+    const VST3_CLASS_ID: [u8; 16] = *b"risChordz.......";
+    const VST3_CATEGORIES: &'static str = "Fx|Tools";
+}
+
+nih_export_clap!(RisChordz);
+nih_export_vst3!(RisChordz);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rismidi::testing::{collect_events, note_off, note_on};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn keys_play_the_global_chord() {
+        let mut processor = RisChordz::default();
+        processor.chords.global = Chord::learn(&[(0, 1.0), (4, 0.5), (7, 1.0)]).unwrap().1;
+
+        let in_event = note_on(0, 0, 60, 0.8);
+        let out_events =
+            collect_events(|send| processor.process_event(in_event, false, false, send));
+        assert_eq!(
+            out_events,
+            vec![
+                note_on(0, 0, 60, 0.8),
+                note_on(0, 0, 64, 0.4),
+                note_on(0, 0, 67, 0.8)
+            ]
+        );
+    }
+
+    #[test]
+    fn note_offs_follow_the_chord_of_their_note_on() {
+        let mut processor = RisChordz::default();
+
+        processor.process_event(note_on(0, 0, 60, 1.0), false, false, |_| ());
+        processor.chords.global = Chord::from_intervals(&[0, 3, 7, 10]);
+
+        let in_event = note_off(0, 0, 60);
+        let out_events =
+            collect_events(|send| processor.process_event(in_event, false, false, send));
+        assert_eq!(
+            out_events,
+            vec![note_off(0, 0, 60), note_off(0, 0, 64), note_off(0, 0, 67)]
+        );
+    }
+
+    #[test]
+    fn shared_notes_are_released_with_the_last_key() {
+        let mut processor = RisChordz::default();
+
+        processor.process_event(note_on(0, 0, 60, 1.0), false, false, |_| ());
+        processor.process_event(note_on(0, 0, 64, 1.0), false, false, |_| ());
+
+        let in_event = note_off(0, 0, 60);
+        let out_events =
+            collect_events(|send| processor.process_event(in_event, false, false, send));
+        assert_eq!(out_events, vec![note_off(0, 0, 60), note_off(0, 0, 67)]);
+
+        let in_event = note_off(0, 0, 64);
+        let out_events =
+            collect_events(|send| processor.process_event(in_event, false, false, send));
+        assert_eq!(
+            out_events,
+            vec![note_off(0, 0, 64), note_off(0, 0, 68), note_off(0, 0, 71)]
+        );
+    }
+
+    #[test]
+    fn learned_chords_are_assigned_to_their_lowest_key() {
+        let mut processor = RisChordz::default();
+        let mut out_events = Vec::new();
+
+        for event in [
+            note_on(0, 0, 65, 0.5),
+            note_on(0, 0, 62, 1.0),
+            note_on(0, 0, 69, 1.0),
+            note_off(0, 0, 62),
+            note_off(0, 0, 65),
+            note_off(0, 0, 69),
+        ] {
+            processor.process_event(event, true, true, |event| out_events.push(event));
+        }
+
+        // While learning, notes are passed through.
+        assert_eq!(out_events.len(), 6);
+        assert!(processor.chords_changed);
+
+        let in_event = note_on(0, 0, 50, 1.0);
+        let out_events =
+            collect_events(|send| processor.process_event(in_event, true, false, send));
+        assert_eq!(out_events, vec![note_on(0, 0, 50, 1.0)]);
+
+        let in_event = note_on(0, 0, 62, 1.0);
+        let out_events =
+            collect_events(|send| processor.process_event(in_event, true, false, send));
+        assert_eq!(
+            out_events,
+            vec![
+                note_on(0, 0, 62, 1.0),
+                note_on(0, 0, 65, 0.5),
+                note_on(0, 0, 69, 1.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn chords_are_only_learned_while_learn_is_enabled() {
+        let mut processor = RisChordz::default();
+
+        processor.process_event(note_on(0, 0, 60, 1.0), true, true, |_| ());
+        processor.process_event(note_on(0, 0, 63, 1.0), true, true, |_| ());
+        processor.process_event(note_off(0, 0, 60), true, false, |_| ());
+        processor.process_event(note_off(0, 0, 63), true, false, |_| ());
+
+        assert!(!processor.chords_changed);
+        assert_eq!(processor.chords, ChordDefinitions::default());
+    }
+
+    const DISABLED: ChordNoteEdit = ChordNoteEdit {
+        enabled: false,
+        interval: 0,
+        velocity: 1.0,
+    };
+
+    fn member(interval: i8, velocity: f32) -> Option<ChordMember> {
+        Some(ChordMember { interval, velocity })
+    }
+
+    #[test]
+    fn chords_are_edited_when_the_chord_notes_change() {
+        let mut processor = RisChordz::default();
+        let mut edit = ChordEdit {
+            key: None,
+            notes: [DISABLED; MAX_CHORD_NOTES],
+        };
+        edit.notes[1] = ChordNoteEdit {
+            enabled: true,
+            interval: 3,
+            velocity: 1.0,
+        };
+
+        // The chord notes which are set up when the plugin starts are not applied.
+        processor.apply_edit(edit);
+        assert_eq!(processor.chords, ChordDefinitions::default());
+
+        // Only the changed values are written, the interval of the major third is kept.
+        edit.notes[1].velocity = 0.5;
+        processor.apply_edit(edit);
+        assert_eq!(
+            processor.chords.global.slots(),
+            [
+                member(0, 1.0),
+                member(4, 0.5),
+                member(7, 1.0),
+                None,
+                None,
+                None,
+                None,
+                None
+            ]
+        );
+        assert!(processor.chords_changed);
+
+        edit.notes[2].enabled = true;
+        processor.apply_edit(edit);
+        edit.notes[2].enabled = false;
+        processor.apply_edit(edit);
+        assert_eq!(processor.chords.global.members().count(), 2);
+    }
+
+    #[test]
+    fn switching_the_edit_key_keeps_the_chords() {
+        let mut processor = RisChordz::default();
+        let mut edit = ChordEdit {
+            key: None,
+            notes: [DISABLED; MAX_CHORD_NOTES],
+        };
+        edit.notes[0].enabled = true;
+        processor.apply_edit(edit);
+
+        edit.key = Some(60);
+        processor.apply_edit(edit);
+        assert!(!processor.chords_changed);
+        assert_eq!(processor.chords.chord_for(60, true), None);
+
+        // The note enabled for the previously edited key is not written to the new key.
+        edit.notes[1] = ChordNoteEdit {
+            enabled: true,
+            interval: 7,
+            velocity: 0.5,
+        };
+        processor.apply_edit(edit);
+        assert_eq!(
+            processor
+                .chords
+                .chord_for(60, true)
+                .map(|chord| chord.slots()),
+            Some([None, member(7, 0.5), None, None, None, None, None, None])
+        );
+        assert_eq!(processor.chords.global, Chord::from_intervals(&[0, 4, 7]));
+
+        // Keys without chord notes are passed through.
+        edit.notes[1].enabled = false;
+        processor.apply_edit(edit);
+        assert_eq!(processor.chords.chord_for(60, true), None);
+    }
+
+    #[test]
+    fn chords_are_persisted() {
+        let mut processor = RisChordz::default();
+        processor
+            .chords
+            .insert(48, Chord::from_intervals(&[0, 7, 12]));
+        processor.chords_changed = true;
+        processor.sync_chords();
+
+        let serialized = processor.params.serialize_fields();
+        let restored = RisChordzParams::default();
+        restored.deserialize_fields(&serialized);
+
+        assert_eq!(*restored.chords.read().unwrap(), processor.chords);
+    }
+
+    #[test]
+    fn chords_of_every_key_are_restored() {
+        let mut processor = RisChordz::default();
+        processor.chords.insert(127, Chord::single_note());
+
+        // State saved with fewer keys than there are MIDI notes.
+        let single_note =
+            r#"{"members":[{"interval":0,"velocity":1.0},null,null,null,null,null,null,null]}"#;
+        let serialized = BTreeMap::from([(
+            "chords".to_string(),
+            format!(r#"{{"global":{single_note},"per_key":[{single_note}]}}"#),
+        )]);
+        processor.params.deserialize_fields(&serialized);
+        processor.sync_chords();
+
+        assert_eq!(
+            processor.chords.chord_for(0, true),
+            Some(Chord::single_note())
+        );
+        assert_eq!(processor.chords.chord_for(127, true), None);
+        assert_eq!(
+            processor.params.chords.read().unwrap().per_key.len(),
+            NUM_MIDI_NOTES as usize
+        );
+    }
+}
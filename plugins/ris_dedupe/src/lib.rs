@@ -0,0 +1,349 @@
+use nih_plug::prelude::*;
+use rismidi::{
+    is_parameter_number_cc, HeldNotes, MidiChannel, MAX_7_BIT, NUM_MIDI_CCS, NUM_MIDI_CHANNELS,
+};
+use std::sync::Arc;
+
+const MIDI_CHANNEL_FROM_NIH_PLUG: &str = "MIDI channels from nih_plug must be in range 0..=15";
+
+/// CC 120–127 are channel mode messages, which are never dropped.
+const FIRST_CHANNEL_MODE_CC: u8 = 120;
+
+/// The channel mode messages which end all notes on their channel.
+const CC_ALL_SOUND_OFF: u8 = 120;
+const CC_ALL_NOTES_OFF: u8 = 123;
+
+struct RisDedupe {
+    params: Arc<RisDedupeParams>,
+    held_notes: HeldNotes,
+
+    /// The raw value which was sent last for each CC, by [`cc_index`].
+    cc_values: Vec<Option<u8>>,
+}
+
+#[derive(Params)]
+struct RisDedupeParams {
+    #[id = "notes"]
+    pub notes: BoolParam,
+
+    #[id = "ccs"]
+    pub ccs: BoolParam,
+}
+
+/// Which kinds of duplicates are dropped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Filters {
+    notes: bool,
+    ccs: bool,
+}
+
+impl Default for RisDedupe {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(RisDedupeParams::default()),
+            held_notes: HeldNotes::new(),
+            cc_values: vec![None; NUM_MIDI_CHANNELS as usize * NUM_MIDI_CCS as usize],
+        }
+    }
+}
+
+impl Default for RisDedupeParams {
+    fn default() -> Self {
+        Self {
+            notes: BoolParam::new("Notes", true),
+            ccs: BoolParam::new("CCs", true),
+        }
+    }
+}
+
+/// The position of a CC in [`RisDedupe::cc_values`].
+fn cc_index(channel: MidiChannel, cc: u8) -> usize {
+    channel.to_0_based() as usize * NUM_MIDI_CCS as usize + cc as usize
+}
+
+impl RisDedupe {
+    /// Gets the filters from the current parameter values.
+    fn current_filters(&self) -> Filters {
+        Filters {
+            notes: self.params.notes.value(),
+            ccs: self.params.ccs.value(),
+        }
+    }
+
+    /// Returns `in_event`, unless it is a duplicate which should be dropped.
+    fn transform_event(&mut self, in_event: NoteEvent, filters: Filters) -> Option<NoteEvent> {
+        let in_channel = |channel: u8| {
+            MidiChannel::try_from_0_based(channel.into()).expect(MIDI_CHANNEL_FROM_NIH_PLUG)
+        };
+
+        // The held notes and CC values are tracked even if their filter is disabled, so that
+        // enabling it does not drop the note-offs of notes which are already held.
+        match in_event {
+            NoteEvent::NoteOn {
+                channel,
+                note,
+                velocity,
+                ..
+            } => {
+                let newly_held = self.held_notes.note_on(in_channel(channel), note, velocity);
+                (newly_held || !filters.notes).then_some(in_event)
+            }
+            NoteEvent::NoteOff { channel, note, .. } => {
+                let released = self.held_notes.note_off(in_channel(channel), note);
+                (released.is_some() || !filters.notes).then_some(in_event)
+            }
+            NoteEvent::MidiCC { channel, cc, .. } if cc >= FIRST_CHANNEL_MODE_CC => {
+                // Notes ended this way must not be dropped when they are played again.
+                if cc == CC_ALL_SOUND_OFF || cc == CC_ALL_NOTES_OFF {
+                    self.held_notes.release_channel(in_channel(channel));
+                }
+                Some(in_event)
+            }
+            // Repeated data entries and increments are meaningful, and the same value may be
+            // written to a newly selected parameter.
+            NoteEvent::MidiCC { cc, .. } if is_parameter_number_cc(cc) => Some(in_event),
+            NoteEvent::MidiCC {
+                channel, cc, value, ..
+            } => {
+                let raw = (value * MAX_7_BIT as f32)
+                    .round()
+                    .clamp(0.0, MAX_7_BIT as f32) as u8;
+                let previous = self.cc_values[cc_index(in_channel(channel), cc)].replace(raw);
+                (previous != Some(raw) || !filters.ccs).then_some(in_event)
+            }
+            _ => Some(in_event),
+        }
+    }
+}
+
+impl Plugin for RisDedupe {
+    const NAME: &'static str = env!("CARGO_PKG_NAME");
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const VENDOR: &'static str = "Simon Leiner";
+    const EMAIL: &'static str = "rismidi@leiner.me";
+
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const DEFAULT_INPUT_CHANNELS: u32 = 0;
+    const DEFAULT_OUTPUT_CHANNELS: u32 = 0;
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::MidiCCs;
+
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn accepts_bus_config(&self, config: &BusConfig) -> bool {
+        let no_aux_busses = AuxiliaryIOConfig {
+            num_busses: 0,
+            num_channels: 0,
+        };
+
+        config.num_input_channels == 0
+            && config.num_output_channels == 0
+            && config.aux_input_busses == no_aux_busses
+            && config.aux_output_busses == no_aux_busses
+    }
+
+    fn reset(&mut self) {
+        // The held notes are kept, so that the note-offs of keys held across the reset are not
+        // dropped.
+        self.cc_values.fill(None);
+    }
+
+    fn process(
+        &mut self,
+        _buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        while let Some(in_event) = context.next_event() {
+            let filters = self.current_filters();
+            if let Some(out_event) = self.transform_event(in_event, filters) {
+                context.send_event(out_event);
+            }
+        }
+
+        ProcessStatus::Normal
+    }
+}
+
+impl ClapPlugin for RisDedupe {
+    const CLAP_ID: &'static str = "me.leiner.ris.dedupe";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some(env!("CARGO_PKG_DESCRIPTION"));
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] =
+        &[ClapFeature::Custom("MIDI"), ClapFeature::Utility];
+}
+
+impl Vst3Plugin for RisDedupe {
+    const VST3_CLASS_ID: [u8; 16] = *b"risDedupe.......";
+    const VST3_CATEGORIES: &'static str = "Fx|Tools";
+}
+
+nih_export_clap!(RisDedupe);
+nih_export_vst3!(RisDedupe);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rismidi::testing::{cc, note_off, note_on};
+
+    const ALL: Filters = Filters {
+        notes: true,
+        ccs: true,
+    };
+
+    fn process(
+        processor: &mut RisDedupe,
+        in_events: &[NoteEvent],
+        filters: Filters,
+    ) -> Vec<NoteEvent> {
+        in_events
+            .iter()
+            .filter_map(|&in_event| processor.transform_event(in_event, filters))
+            .collect()
+    }
+
+    #[test]
+    fn duplicate_notes_are_dropped() {
+        let mut processor = RisDedupe::default();
+
+        let in_events = [
+            note_on(0, 0, 60, 0.5),
+            note_on(0, 0, 60, 0.5),
+            note_on(0, 1, 60, 0.5),
+            note_off(0, 0, 60),
+            note_off(0, 0, 60),
+            note_off(0, 0, 62),
+        ];
+
+        assert_eq!(
+            process(&mut processor, &in_events, ALL),
+            vec![
+                note_on(0, 0, 60, 0.5),
+                note_on(0, 1, 60, 0.5),
+                note_off(0, 0, 60)
+            ]
+        );
+    }
+
+    #[test]
+    fn repeated_cc_values_are_dropped() {
+        let mut processor = RisDedupe::default();
+
+        let in_events = [
+            cc(0, 0, 1, 0.5),
+            cc(0, 0, 1, 0.5),
+            cc(0, 0, 2, 0.5),
+            cc(0, 1, 1, 0.5),
+            cc(0, 0, 1, 0.6),
+            cc(0, 0, 1, 0.5),
+        ];
+
+        assert_eq!(
+            process(&mut processor, &in_events, ALL),
+            vec![
+                cc(0, 0, 1, 0.5),
+                cc(0, 0, 2, 0.5),
+                cc(0, 1, 1, 0.5),
+                cc(0, 0, 1, 0.6),
+                cc(0, 0, 1, 0.5)
+            ]
+        );
+    }
+
+    #[test]
+    fn disabled_filters_pass_everything() {
+        let mut processor = RisDedupe::default();
+        let none = Filters {
+            notes: false,
+            ccs: false,
+        };
+
+        let in_events = [
+            note_on(0, 0, 60, 0.5),
+            note_on(0, 0, 60, 0.5),
+            cc(0, 0, 1, 0.5),
+            cc(0, 0, 1, 0.5),
+        ];
+
+        assert_eq!(process(&mut processor, &in_events, none), in_events);
+
+        // The note is still tracked while the filter is disabled.
+        assert_eq!(
+            process(
+                &mut processor,
+                &[note_off(0, 0, 60), note_off(0, 0, 60)],
+                ALL
+            ),
+            vec![note_off(0, 0, 60)]
+        );
+    }
+
+    #[test]
+    fn channel_mode_messages_are_passed_through_and_end_notes() {
+        let mut processor = RisDedupe::default();
+
+        let in_events = [
+            note_on(0, 0, 60, 0.5),
+            note_on(0, 1, 60, 0.5),
+            cc(0, 0, 123, 0.0),
+            cc(0, 0, 123, 0.0),
+            note_on(0, 0, 60, 0.5),
+            note_on(0, 1, 60, 0.5),
+            cc(0, 1, 120, 0.0),
+            cc(0, 1, 120, 0.0),
+            note_on(0, 1, 60, 0.5),
+        ];
+
+        assert_eq!(
+            process(&mut processor, &in_events, ALL),
+            vec![
+                note_on(0, 0, 60, 0.5),
+                note_on(0, 1, 60, 0.5),
+                cc(0, 0, 123, 0.0),
+                cc(0, 0, 123, 0.0),
+                note_on(0, 0, 60, 0.5),
+                cc(0, 1, 120, 0.0),
+                cc(0, 1, 120, 0.0),
+                note_on(0, 1, 60, 0.5)
+            ]
+        );
+    }
+
+    #[test]
+    fn parameter_number_ccs_are_passed_through() {
+        let mut processor = RisDedupe::default();
+
+        let in_events = [
+            cc(0, 0, 101, 0.0),
+            cc(0, 0, 100, 0.0),
+            cc(0, 0, 6, 0.5),
+            cc(0, 0, 100, 1.0 / 127.0),
+            cc(0, 0, 6, 0.5),
+            cc(0, 0, 96, 0.0),
+            cc(0, 0, 96, 0.0),
+        ];
+
+        assert_eq!(process(&mut processor, &in_events, ALL), in_events);
+    }
+
+    #[test]
+    fn keys_held_across_a_reset_are_released() {
+        let mut processor = RisDedupe::default();
+        process(&mut processor, &[note_on(0, 0, 60, 0.5)], ALL);
+
+        processor.reset();
+        assert_eq!(
+            process(&mut processor, &[note_off(0, 0, 60)], ALL),
+            vec![note_off(0, 0, 60)]
+        );
+    }
+}
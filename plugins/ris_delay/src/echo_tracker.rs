@@ -0,0 +1,24 @@
+use crate::Echo;
+use rismidi::{MidiChannel, NUM_MIDI_CHANNELS, NUM_MIDI_NOTES};
+
+/// Remembers the echo settings for every held note, so that its echoes can be ended with exactly
+/// the same settings they were started with.
+pub struct EchoTracker {
+    echoes: [[Option<Echo>; NUM_MIDI_NOTES as usize]; NUM_MIDI_CHANNELS as usize],
+}
+
+impl EchoTracker {
+    pub fn new() -> EchoTracker {
+        EchoTracker {
+            echoes: [[None; NUM_MIDI_NOTES as usize]; NUM_MIDI_CHANNELS as usize],
+        }
+    }
+
+    pub fn insert(&mut self, note: u8, in_channel: MidiChannel, echo: Echo) {
+        self.echoes[in_channel.to_0_based() as usize][note as usize] = Some(echo);
+    }
+
+    pub fn remove(&mut self, note: u8, in_channel: MidiChannel) -> Option<Echo> {
+        self.echoes[in_channel.to_0_based() as usize][note as usize].take()
+    }
+}
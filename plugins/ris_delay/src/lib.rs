@@ -0,0 +1,532 @@
+mod echo_tracker;
+
+use echo_tracker::EchoTracker;
+use nih_plug::prelude::*;
+use rismidi::{
+    EventScheduler, HasTiming, MidiChannel, NoteValue, OptionalMidiChannelParam, MIN_VELOCITY,
+    NUM_MIDI_CHANNELS, NUM_MIDI_NOTES,
+};
+use std::sync::Arc;
+
+const MIDI_CHANNEL_FROM_NIH_PLUG: &str = "MIDI channels from nih_plug must be in range 0..=15";
+
+/// The maximum number of echo events that can be pending at the same time.
+const SCHEDULER_CAPACITY: usize = 4096;
+
+/// The maximum number of times a note can be repeated.
+const MAX_REPEATS: usize = 16;
+
+struct RisDelay {
+    params: Arc<RisDelayParams>,
+    sample_rate: f32,
+    scheduler: EventScheduler,
+    echo_tracker: EchoTracker,
+
+    /// The number of echo note-offs that still have to be scheduled once the held notes are
+    /// released. We keep room for them in the scheduler, so that no echo is left hanging.
+    pending_note_offs: usize,
+
+    /// Whether the plugin has been reset, so that the pending note-offs need to be sent.
+    flush_pending: bool,
+}
+
+#[derive(Params)]
+struct RisDelayParams {
+    #[id = "sync"]
+    pub sync: BoolParam,
+
+    #[id = "time"]
+    pub time: FloatParam,
+
+    #[id = "note_value"]
+    pub note_value: EnumParam<NoteValue>,
+
+    #[id = "repeats"]
+    pub repeats: IntParam,
+
+    #[id = "velocity_decay"]
+    pub velocity_decay: FloatParam,
+
+    #[id = "transpose"]
+    pub transpose: IntParam,
+
+    #[nested(id_prefix = "echo_channel")]
+    pub echo_channel: OptionalMidiChannelParam,
+
+    #[nested(array, group = "Repeat")]
+    pub repeat_channels: [RepeatParams; MAX_REPEATS],
+}
+
+/// The parameters of a single repeat.
+#[derive(Params)]
+struct RepeatParams {
+    #[nested(id_prefix = "channel")]
+    pub channel: OptionalMidiChannelParam,
+}
+
+/// The settings for the echoes of a single note.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Echo {
+    /// Time between two repeats, in samples.
+    delay: u64,
+    repeats: u8,
+    velocity_decay: f32,
+    transpose: i32,
+
+    /// The output channel of each repeat, or [`None`] for the channel of the original note.
+    channels: [Option<MidiChannel>; MAX_REPEATS],
+}
+
+impl Echo {
+    /// The note played by the given repeat (starting at 1), if it is a valid MIDI note.
+    fn note(&self, note: u8, repeat: u8) -> Option<u8> {
+        let echo_note = note as i32 + self.transpose * repeat as i32;
+        (0..NUM_MIDI_NOTES as i32)
+            .contains(&echo_note)
+            .then_some(echo_note as u8)
+    }
+
+    /// The channel of the given repeat (starting at 1), for a note received on `in_channel`.
+    fn channel(&self, in_channel: MidiChannel, repeat: u8) -> MidiChannel {
+        self.channels[repeat as usize - 1].unwrap_or(in_channel)
+    }
+
+    /// The velocity of the given repeat (starting at 1).
+    fn velocity(&self, velocity: f32, repeat: u8) -> f32 {
+        velocity * (1.0 - self.velocity_decay).powi(repeat as i32)
+    }
+
+    /// The number of repeats that are actually audible, i.e. before the echo leaves the range of
+    /// MIDI notes or fades out.
+    fn audible_repeats(&self, note: u8, velocity: f32) -> u8 {
+        (1..=self.repeats)
+            .take_while(|&repeat| {
+                self.note(note, repeat).is_some() && self.velocity(velocity, repeat) >= MIN_VELOCITY
+            })
+            .count() as u8
+    }
+}
+
+impl Default for RisDelay {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(RisDelayParams::default()),
+            sample_rate: 44100.0,
+            scheduler: EventScheduler::with_capacity(SCHEDULER_CAPACITY),
+            echo_tracker: EchoTracker::new(),
+            pending_note_offs: 0,
+            flush_pending: false,
+        }
+    }
+}
+
+impl Default for RisDelayParams {
+    fn default() -> Self {
+        Self {
+            sync: BoolParam::new("Tempo Sync", true),
+            time: FloatParam::new(
+                "Time",
+                250.0,
+                FloatRange::Skewed {
+                    min: 1.0,
+                    max: 2000.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_step_size(1.0),
+            note_value: EnumParam::new("Note Value", NoteValue::Eighth),
+            repeats: IntParam::new(
+                "Repeats",
+                3,
+                IntRange::Linear {
+                    min: 1,
+                    max: MAX_REPEATS as i32,
+                },
+            ),
+            velocity_decay: FloatParam::new(
+                "Velocity Decay",
+                0.25,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit(" %")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            transpose: IntParam::new("Transpose", 0, IntRange::Linear { min: -24, max: 24 })
+                .with_unit(" st"),
+            echo_channel: OptionalMidiChannelParam::new("Echo Channel", None)
+                .with_none_selected_description("Same as Input"),
+            repeat_channels: std::array::from_fn(|idx| RepeatParams::new(idx + 1)),
+        }
+    }
+}
+
+impl RepeatParams {
+    /// Creates the parameters for the repeat with the given (1-based) number.
+    fn new(repeat: usize) -> Self {
+        Self {
+            channel: OptionalMidiChannelParam::new(format!("Repeat {repeat} Channel"), None)
+                .with_none_selected_description("Echo Channel"),
+        }
+    }
+}
+
+impl RisDelay {
+    /// Gets the echo settings from the current parameter values.
+    fn current_echo(&self, tempo: Option<f64>) -> Echo {
+        let delay = if self.params.sync.value() {
+            let tempo = tempo.unwrap_or(NoteValue::FALLBACK_TEMPO);
+            self.params
+                .note_value
+                .value()
+                .samples(tempo, self.sample_rate)
+        } else {
+            self.params.time.value() as f64 / 1000.0 * self.sample_rate as f64
+        };
+
+        Echo {
+            delay: (delay.round() as u64).max(1),
+            repeats: self.params.repeats.value() as u8,
+            velocity_decay: self.params.velocity_decay.value(),
+            transpose: self.params.transpose.value(),
+            channels: std::array::from_fn(|idx| {
+                let echo_channel = self.params.echo_channel.value();
+                self.params.repeat_channels[idx]
+                    .channel
+                    .value()
+                    .or(echo_channel)
+            }),
+        }
+    }
+
+    /// Schedules the echoes for `in_event`, which is passed through unchanged by the caller.
+    fn schedule_echoes(&mut self, in_event: &NoteEvent, echo: Echo) {
+        match *in_event {
+            NoteEvent::NoteOn {
+                timing,
+                voice_id: _,
+                channel,
+                note,
+                velocity,
+            } => {
+                let in_channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+
+                // If the note is already held, its previous echoes end here. Otherwise, they
+                // would never get their note-offs.
+                self.end_echoes(timing, in_channel, note, 0.0);
+
+                // Every repeat takes up two events in the scheduler: one note-on and one note-off.
+                let free_slots = self
+                    .scheduler
+                    .remaining_capacity()
+                    .saturating_sub(self.pending_note_offs);
+                let repeats = echo
+                    .audible_repeats(note, velocity)
+                    .min((free_slots / 2).min(u8::MAX as usize) as u8);
+                if repeats == 0 {
+                    return;
+                }
+
+                let echo = Echo { repeats, ..echo };
+                for repeat in 1..=repeats {
+                    if let Some(echo_note) = echo.note(note, repeat) {
+                        let echo_event = NoteEvent::NoteOn {
+                            timing,
+                            voice_id: None,
+                            channel: echo.channel(in_channel, repeat).to_0_based(),
+                            note: echo_note,
+                            velocity: echo.velocity(velocity, repeat),
+                        };
+                        let due = timing as u64 + repeat as u64 * echo.delay;
+                        let scheduled = self.scheduler.schedule(due, echo_event);
+                        nih_debug_assert!(scheduled.is_ok());
+                    }
+                }
+
+                self.echo_tracker.insert(note, in_channel, echo);
+                self.pending_note_offs += repeats as usize;
+            }
+            NoteEvent::NoteOff {
+                timing,
+                voice_id: _,
+                channel,
+                note,
+                velocity,
+            } => {
+                let in_channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+
+                self.end_echoes(timing, in_channel, note, velocity);
+            }
+            _ => (),
+        }
+    }
+
+    /// Schedules the note-offs for all echoes of a note that has been released at `timing`.
+    fn end_echoes(&mut self, timing: u32, in_channel: MidiChannel, note: u8, velocity: f32) {
+        let Some(echo) = self.echo_tracker.remove(note, in_channel) else {
+            return;
+        };
+
+        self.pending_note_offs -= echo.repeats as usize;
+
+        for repeat in 1..=echo.repeats {
+            if let Some(echo_note) = echo.note(note, repeat) {
+                let echo_event = NoteEvent::NoteOff {
+                    timing,
+                    voice_id: None,
+                    channel: echo.channel(in_channel, repeat).to_0_based(),
+                    note: echo_note,
+                    velocity,
+                };
+                let due = timing as u64 + repeat as u64 * echo.delay;
+                let scheduled = self.scheduler.schedule(due, echo_event);
+                nih_debug_assert!(scheduled.is_ok());
+            }
+        }
+    }
+}
+
+impl Plugin for RisDelay {
+    const NAME: &'static str = env!("CARGO_PKG_NAME");
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const VENDOR: &'static str = "Simon Leiner";
+    const EMAIL: &'static str = "rismidi@leiner.me";
+
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const DEFAULT_INPUT_CHANNELS: u32 = 0;
+    const DEFAULT_OUTPUT_CHANNELS: u32 = 0;
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::MidiCCs;
+
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn accepts_bus_config(&self, config: &BusConfig) -> bool {
+        let no_aux_busses = AuxiliaryIOConfig {
+            num_busses: 0,
+            num_channels: 0,
+        };
+
+        config.num_input_channels == 0
+            && config.num_output_channels == 0
+            && config.aux_input_busses == no_aux_busses
+            && config.aux_output_busses == no_aux_busses
+    }
+
+    fn initialize(
+        &mut self,
+        _bus_config: &BusConfig,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        self.sample_rate = buffer_config.sample_rate;
+
+        true
+    }
+
+    fn reset(&mut self) {
+        // We cannot send events from here, so the pending note-offs are sent with the next buffer.
+        // Echoes that have not been played yet are dropped, and those of held notes are ended.
+        self.scheduler
+            .retain(|event| matches!(event, NoteEvent::NoteOff { .. }));
+        for channel in 0..NUM_MIDI_CHANNELS {
+            let in_channel =
+                MidiChannel::try_from_0_based(channel.into()).expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+            for note in 0..NUM_MIDI_NOTES {
+                self.end_echoes(0, in_channel, note, 0.0);
+            }
+        }
+        self.flush_pending = true;
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        if std::mem::take(&mut self.flush_pending) {
+            while let Some(note_off) = self.scheduler.pop_before(u32::MAX) {
+                context.send_event(note_off.with_timing(0));
+            }
+        }
+
+        let num_samples = buffer.samples() as u32;
+        let tempo = context.transport().tempo;
+
+        while let Some(in_event) = context.next_event() {
+            while let Some(echo_event) = self.scheduler.pop_before(in_event.timing()) {
+                context.send_event(echo_event);
+            }
+
+            let echo = self.current_echo(tempo);
+            self.schedule_echoes(&in_event, echo);
+            context.send_event(in_event);
+        }
+
+        while let Some(echo_event) = self.scheduler.pop_before(num_samples) {
+            context.send_event(echo_event);
+        }
+        self.scheduler.advance(num_samples);
+
+        // Pending echoes must be sent, even if there is no more input.
+        ProcessStatus::KeepAlive
+    }
+}
+
+impl ClapPlugin for RisDelay {
+    const CLAP_ID: &'static str = "me.leiner.ris.delay";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some(env!("CARGO_PKG_DESCRIPTION"));
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] =
+        &[ClapFeature::Custom("MIDI"), ClapFeature::Utility];
+}
+
+impl Vst3Plugin for RisDelay {
+    const VST3_CLASS_ID: [u8; 16] = *b"risDelay........";
+    const VST3_CATEGORIES: &'static str = "Fx|Tools";
+}
+
+nih_export_clap!(RisDelay);
+nih_export_vst3!(RisDelay);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rismidi::testing::{note_off, note_on};
+    use rismidi::HasChannel;
+
+    const ECHO: Echo = Echo {
+        delay: 100,
+        repeats: 3,
+        velocity_decay: 0.5,
+        transpose: 12,
+        channels: [None; MAX_REPEATS],
+    };
+
+    fn pending_events(processor: &mut RisDelay) -> Vec<NoteEvent> {
+        std::iter::from_fn(|| processor.scheduler.pop_before(u32::MAX)).collect()
+    }
+
+    #[test]
+    fn echoes_are_repeated_with_decay_and_transposition() {
+        let mut processor = RisDelay::default();
+
+        processor.schedule_echoes(&note_on(10, 2, 48, 1.0), ECHO);
+
+        let expected: Vec<NoteEvent> = [(110, 60, 0.5), (210, 72, 0.25), (310, 84, 0.125)]
+            .into_iter()
+            .map(|(timing, note, velocity)| NoteEvent::NoteOn {
+                timing,
+                voice_id: None,
+                channel: 2,
+                note,
+                velocity,
+            })
+            .collect();
+        assert_eq!(pending_events(&mut processor), expected);
+    }
+
+    #[test]
+    fn echoes_end_at_the_note_range() {
+        let mut processor = RisDelay::default();
+
+        processor.schedule_echoes(&note_on(0, 2, 100, 1.0), ECHO);
+        processor.schedule_echoes(&note_off(50, 2, 100), ECHO);
+
+        // 100 + 3 * 12 would be outside of the MIDI note range.
+        let notes: Vec<u8> = pending_events(&mut processor)
+            .into_iter()
+            .map(|event| match event {
+                NoteEvent::NoteOn { note, .. } | NoteEvent::NoteOff { note, .. } => note,
+                _ => panic!("unexpected event {event:?}"),
+            })
+            .collect();
+        assert_eq!(notes, vec![112, 112, 124, 124]);
+    }
+
+    #[test]
+    fn note_offs_follow_the_settings_of_their_note_on() {
+        let mut processor = RisDelay::default();
+
+        processor.schedule_echoes(&note_on(0, 2, 60, 1.0), ECHO);
+        let changed_echo = Echo {
+            delay: 30,
+            repeats: 8,
+            transpose: -1,
+            channels: [Some(MidiChannel::Channel16); MAX_REPEATS],
+            ..ECHO
+        };
+        processor.schedule_echoes(&note_off(50, 2, 60), changed_echo);
+
+        let note_offs: Vec<NoteEvent> = pending_events(&mut processor)
+            .into_iter()
+            .filter(|event| matches!(event, NoteEvent::NoteOff { .. }))
+            .collect();
+        let expected: Vec<NoteEvent> = [(150, 72), (250, 84), (350, 96)]
+            .into_iter()
+            .map(|(timing, note)| note_off(timing, 2, note))
+            .collect();
+        assert_eq!(note_offs, expected);
+        assert_eq!(processor.pending_note_offs, 0);
+    }
+
+    #[test]
+    fn echoes_are_sent_to_the_channel_of_their_repeat() {
+        let mut processor = RisDelay::default();
+        let mut echo = ECHO;
+        echo.channels[0] = Some(MidiChannel::Channel10);
+        echo.channels[2] = Some(MidiChannel::Channel11);
+
+        processor.schedule_echoes(&note_on(0, 2, 60, 1.0), echo);
+        processor.schedule_echoes(&note_off(50, 2, 60), echo);
+
+        let channels: Vec<MidiChannel> = pending_events(&mut processor)
+            .into_iter()
+            .map(|event| event.get_channel().unwrap())
+            .collect();
+        assert_eq!(
+            channels,
+            vec![
+                MidiChannel::Channel10,
+                MidiChannel::Channel10,
+                MidiChannel::Channel3,
+                MidiChannel::Channel3,
+                MidiChannel::Channel11,
+                MidiChannel::Channel11
+            ]
+        );
+    }
+
+    #[test]
+    fn echoes_are_ended_on_reset() {
+        let mut processor = RisDelay::default();
+
+        processor.schedule_echoes(&note_on(0, 2, 48, 1.0), ECHO);
+        processor.schedule_echoes(&note_on(0, 2, 50, 1.0), ECHO);
+        processor.schedule_echoes(&note_off(50, 2, 50), ECHO);
+
+        processor.reset();
+        assert!(processor.flush_pending);
+        assert_eq!(processor.pending_note_offs, 0);
+        let mut notes: Vec<u8> = pending_events(&mut processor)
+            .into_iter()
+            .map(|event| match event {
+                NoteEvent::NoteOff { note, .. } => note,
+                _ => panic!("unexpected event {event:?}"),
+            })
+            .collect();
+        notes.sort_unstable();
+        assert_eq!(notes, vec![60, 62, 72, 74, 84, 86]);
+    }
+}
@@ -0,0 +1,467 @@
+use nih_plug::prelude::*;
+use rismidi::{
+    EventScheduler, HasTiming, MidiChannel, Rng, MIN_VELOCITY, NUM_MIDI_CHANNELS, NUM_MIDI_NOTES,
+};
+use std::sync::Arc;
+
+const MIDI_CHANNEL_FROM_NIH_PLUG: &str = "MIDI channels from nih_plug must be in range 0..=15";
+
+/// The maximum number of events that can be delayed at the same time.
+const SCHEDULER_CAPACITY: usize = 4096;
+
+struct RisHumanize {
+    params: Arc<RisHumanizeParams>,
+    sample_rate: f32,
+    scheduler: EventScheduler,
+    rng: Rng,
+
+    /// The seed `rng` has been started with, or [`None`] after a reset.
+    seed: Option<i32>,
+
+    /// The latency that has been reported to the host, in samples.
+    latency: u32,
+
+    /// The delay of each sounding note, by [`note_index`]. Its note-off is delayed by the same
+    /// amount, so that it stays after the note-on.
+    note_delays: Vec<Option<u64>>,
+
+    /// The time at which the last event other than a note was scheduled, relative to the start of
+    /// the current buffer. Later events are never scheduled before it, so that they are not
+    /// reordered when the latency decreases.
+    last_other_due: u64,
+
+    /// Whether the plugin has been reset, so that the pending note-offs need to be sent.
+    flush_pending: bool,
+}
+
+#[derive(Params)]
+struct RisHumanizeParams {
+    #[id = "timing"]
+    pub timing: FloatParam,
+
+    #[id = "velocity"]
+    pub velocity: FloatParam,
+
+    #[id = "seed"]
+    pub seed: IntParam,
+}
+
+/// How much notes are randomized.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Humanize {
+    /// The maximum time by which notes are moved in either direction, in samples. All events are
+    /// delayed by this amount, so that notes can be moved earlier.
+    latency: u32,
+
+    /// The maximum change of a note's velocity in either direction.
+    velocity: f32,
+}
+
+impl Default for RisHumanize {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(RisHumanizeParams::default()),
+            sample_rate: 44100.0,
+            scheduler: EventScheduler::with_capacity(SCHEDULER_CAPACITY),
+            rng: Rng::new(0),
+            seed: None,
+            latency: 0,
+            note_delays: vec![None; NUM_MIDI_CHANNELS as usize * NUM_MIDI_NOTES as usize],
+            last_other_due: 0,
+            flush_pending: false,
+        }
+    }
+}
+
+impl Default for RisHumanizeParams {
+    fn default() -> Self {
+        Self {
+            timing: FloatParam::new(
+                "Timing",
+                10.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 50.0,
+                },
+            )
+            .with_unit(" ms")
+            .with_step_size(0.1),
+            velocity: FloatParam::new("Velocity", 0.1, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_unit(" %")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            seed: IntParam::new("Seed", 0, IntRange::Linear { min: 0, max: 999 }),
+        }
+    }
+}
+
+fn note_index(channel: MidiChannel, note: u8) -> usize {
+    channel.to_0_based() as usize * NUM_MIDI_NOTES as usize + note as usize
+}
+
+impl RisHumanize {
+    /// Gets the humanization settings from the current parameter values.
+    fn current_humanize(&self) -> Humanize {
+        let latency = self.params.timing.value() as f64 / 1000.0 * self.sample_rate as f64;
+
+        Humanize {
+            latency: latency.round() as u32,
+            velocity: self.params.velocity.value(),
+        }
+    }
+
+    /// Restarts the random numbers if `seed` has changed, so that the results can be reproduced
+    /// without resetting the plugin.
+    fn apply_seed(&mut self, seed: i32) {
+        if self.seed != Some(seed) {
+            self.seed = Some(seed);
+            self.rng = Rng::new(seed as u64);
+        }
+    }
+
+    /// A random value in the range `-1.0..1.0`.
+    fn next_deviation(&mut self) -> f32 {
+        self.rng.next_f32() * 2.0 - 1.0
+    }
+
+    /// Schedules `in_event`, delayed by the latency and randomized if it is a note-on.
+    fn process_event(&mut self, mut in_event: NoteEvent, humanize: Humanize) {
+        let mut delay = humanize.latency as u64;
+        let mut is_note = true;
+
+        match &mut in_event {
+            NoteEvent::NoteOn {
+                channel,
+                note,
+                velocity,
+                ..
+            } => {
+                let channel = MidiChannel::try_from_0_based((*channel).into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+                let offset = (self.next_deviation() * humanize.latency as f32).round() as i64;
+                delay = (delay as i64 + offset).max(0) as u64;
+                self.note_delays[note_index(channel, *note)] = Some(delay);
+
+                let deviation = self.next_deviation() * humanize.velocity;
+                *velocity = (*velocity + deviation).clamp(MIN_VELOCITY, 1.0);
+            }
+            NoteEvent::NoteOff { channel, note, .. } => {
+                let channel = MidiChannel::try_from_0_based((*channel).into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+                if let Some(note_delay) = self.note_delays[note_index(channel, *note)].take() {
+                    delay = note_delay;
+                }
+            }
+            NoteEvent::PolyPressure { channel, note, .. } => {
+                let channel = MidiChannel::try_from_0_based((*channel).into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+                if let Some(note_delay) = self.note_delays[note_index(channel, *note)] {
+                    delay = note_delay;
+                }
+            }
+            _ => is_note = false,
+        }
+
+        let mut due = in_event.timing() as u64 + delay;
+        if !is_note {
+            due = due.max(self.last_other_due);
+            self.last_other_due = due;
+        }
+
+        // The events of an earlier press of the same key must not end the new note, so they are
+        // sent before it.
+        if let NoteEvent::NoteOn { channel, note, .. } = in_event {
+            self.scheduler.bring_forward(due, |event| match *event {
+                NoteEvent::NoteOn {
+                    channel: c,
+                    note: n,
+                    ..
+                }
+                | NoteEvent::NoteOff {
+                    channel: c,
+                    note: n,
+                    ..
+                }
+                | NoteEvent::PolyPressure {
+                    channel: c,
+                    note: n,
+                    ..
+                } => c == channel && n == note,
+                _ => false,
+            });
+        }
+
+        let scheduled = self.scheduler.schedule(due, in_event);
+        nih_debug_assert!(scheduled.is_ok());
+    }
+}
+
+impl Plugin for RisHumanize {
+    const NAME: &'static str = env!("CARGO_PKG_NAME");
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const VENDOR: &'static str = "Simon Leiner";
+    const EMAIL: &'static str = "rismidi@leiner.me";
+
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const DEFAULT_INPUT_CHANNELS: u32 = 0;
+    const DEFAULT_OUTPUT_CHANNELS: u32 = 0;
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::MidiCCs;
+
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn accepts_bus_config(&self, config: &BusConfig) -> bool {
+        let no_aux_busses = AuxiliaryIOConfig {
+            num_busses: 0,
+            num_channels: 0,
+        };
+
+        config.num_input_channels == 0
+            && config.num_output_channels == 0
+            && config.aux_input_busses == no_aux_busses
+            && config.aux_output_busses == no_aux_busses
+    }
+
+    fn initialize(
+        &mut self,
+        _bus_config: &BusConfig,
+        buffer_config: &BufferConfig,
+        context: &mut impl InitContext<Self>,
+    ) -> bool {
+        self.sample_rate = buffer_config.sample_rate;
+        self.latency = self.current_humanize().latency;
+        context.set_latency_samples(self.latency);
+
+        true
+    }
+
+    fn reset(&mut self) {
+        // We cannot send events from here, so the pending note-offs are sent with the next buffer.
+        // All other delayed events are dropped.
+        self.scheduler
+            .retain(|event| matches!(event, NoteEvent::NoteOff { .. }));
+        self.flush_pending = true;
+        self.last_other_due = 0;
+        self.seed = None;
+        self.apply_seed(self.params.seed.value());
+        self.note_delays.fill(None);
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        if std::mem::take(&mut self.flush_pending) {
+            while let Some(note_off) = self.scheduler.pop_before(u32::MAX) {
+                context.send_event(note_off.with_timing(0));
+            }
+        }
+
+        let num_samples = buffer.samples() as u32;
+        self.apply_seed(self.params.seed.value());
+
+        // The latency must not change within a buffer, so the settings are only updated here.
+        let humanize = self.current_humanize();
+        if humanize.latency != self.latency {
+            self.latency = humanize.latency;
+            context.set_latency_samples(self.latency);
+        }
+
+        while let Some(in_event) = context.next_event() {
+            while let Some(out_event) = self.scheduler.pop_before(in_event.timing()) {
+                context.send_event(out_event);
+            }
+
+            self.process_event(in_event, humanize);
+        }
+
+        while let Some(out_event) = self.scheduler.pop_before(num_samples) {
+            context.send_event(out_event);
+        }
+        self.scheduler.advance(num_samples);
+        self.last_other_due = self.last_other_due.saturating_sub(num_samples as u64);
+
+        // Delayed events must be sent, even if there is no more input.
+        ProcessStatus::KeepAlive
+    }
+}
+
+impl ClapPlugin for RisHumanize {
+    const CLAP_ID: &'static str = "me.leiner.ris.humanize";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some(env!("CARGO_PKG_DESCRIPTION"));
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] =
+        &[ClapFeature::Custom("MIDI"), ClapFeature::Utility];
+}
+
+impl Vst3Plugin for RisHumanize {
+    const VST3_CLASS_ID: [u8; 16] = *b"risHumanize.....";
+    const VST3_CATEGORIES: &'static str = "Fx|Tools";
+}
+
+nih_export_clap!(RisHumanize);
+nih_export_vst3!(RisHumanize);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rismidi::testing::{cc, note_off, note_on};
+
+    const HUMANIZE: Humanize = Humanize {
+        latency: 100,
+        velocity: 0.2,
+    };
+
+    fn process(
+        processor: &mut RisHumanize,
+        in_events: &[NoteEvent],
+        humanize: Humanize,
+    ) -> Vec<NoteEvent> {
+        for &in_event in in_events {
+            processor.process_event(in_event, humanize);
+        }
+        std::iter::from_fn(|| processor.scheduler.pop_before(u32::MAX)).collect()
+    }
+
+    #[test]
+    fn notes_are_randomized_within_range() {
+        let mut processor = RisHumanize::default();
+
+        let in_events: Vec<NoteEvent> = (0..100).map(|i| note_on(i * 1000, 0, 60, 0.5)).collect();
+
+        for (in_event, out_event) in
+            in_events
+                .iter()
+                .zip(process(&mut processor, &in_events, HUMANIZE))
+        {
+            let NoteEvent::NoteOn { velocity, .. } = out_event else {
+                panic!("unexpected event {out_event:?}");
+            };
+            assert!((0.3..=0.7).contains(&velocity));
+            assert!((in_event.timing()..=in_event.timing() + 200).contains(&out_event.timing()));
+        }
+    }
+
+    #[test]
+    fn note_offs_keep_the_note_length() {
+        let mut processor = RisHumanize::default();
+
+        let out_events = process(
+            &mut processor,
+            &[note_on(0, 0, 60, 0.5), note_off(50, 0, 60)],
+            HUMANIZE,
+        );
+
+        assert_eq!(out_events.len(), 2);
+        assert_eq!(out_events[1].timing() - out_events[0].timing(), 50);
+    }
+
+    #[test]
+    fn other_events_are_delayed_by_the_latency() {
+        let mut processor = RisHumanize::default();
+        assert_eq!(
+            process(&mut processor, &[cc(10, 0, 1, 0.5)], HUMANIZE),
+            vec![cc(110, 0, 1, 0.5)]
+        );
+    }
+
+    #[test]
+    fn renders_are_reproducible() {
+        let in_events: Vec<NoteEvent> = (0..10)
+            .map(|i| note_on(i * 1000, 0, 60 + i as u8, 0.5))
+            .collect();
+
+        let mut processor = RisHumanize::default();
+        processor.reset();
+        let first_render = process(&mut processor, &in_events, HUMANIZE);
+        processor.reset();
+        let second_render = process(&mut processor, &in_events, HUMANIZE);
+
+        assert_eq!(first_render, second_render);
+    }
+
+    #[test]
+    fn other_events_are_not_reordered_when_the_latency_decreases() {
+        let mut processor = RisHumanize::default();
+        let shorter = Humanize {
+            latency: 10,
+            ..HUMANIZE
+        };
+
+        processor.process_event(cc(0, 0, 1, 0.5), HUMANIZE);
+        processor.process_event(cc(20, 0, 1, 0.6), shorter);
+        processor.process_event(cc(200, 0, 1, 0.7), shorter);
+
+        let out_events: Vec<NoteEvent> =
+            std::iter::from_fn(|| processor.scheduler.pop_before(u32::MAX)).collect();
+        assert_eq!(
+            out_events,
+            vec![cc(100, 0, 1, 0.5), cc(100, 0, 1, 0.6), cc(210, 0, 1, 0.7)]
+        );
+    }
+
+    #[test]
+    fn only_note_offs_are_kept_on_reset() {
+        let mut processor = RisHumanize::default();
+
+        processor.process_event(note_on(0, 0, 60, 0.5), HUMANIZE);
+        processor.process_event(note_off(10, 0, 60), HUMANIZE);
+        processor.process_event(cc(10, 0, 1, 0.5), HUMANIZE);
+
+        processor.reset();
+        assert!(processor.flush_pending);
+        let pending: Vec<NoteEvent> =
+            std::iter::from_fn(|| processor.scheduler.pop_before(u32::MAX)).collect();
+        assert_eq!(pending.len(), 1);
+        assert!(matches!(pending[0], NoteEvent::NoteOff { note: 60, .. }));
+    }
+
+    #[test]
+    fn changing_the_seed_restarts_the_random_numbers() {
+        let in_events: Vec<NoteEvent> = (0..10)
+            .map(|i| note_on(i * 1000, 0, 60 + i as u8, 0.5))
+            .collect();
+
+        let mut processor = RisHumanize::default();
+        processor.apply_seed(1);
+        let first_render = process(&mut processor, &in_events, HUMANIZE);
+        processor.apply_seed(2);
+        let second_render = process(&mut processor, &in_events, HUMANIZE);
+        processor.apply_seed(1);
+        let third_render = process(&mut processor, &in_events, HUMANIZE);
+
+        assert_ne!(first_render, second_render);
+        assert_eq!(first_render, third_render);
+    }
+
+    #[test]
+    fn note_offs_of_earlier_presses_do_not_end_new_notes() {
+        let mut processor = RisHumanize::default();
+        let in_events: Vec<NoteEvent> = (0..50)
+            .flat_map(|i| {
+                [
+                    note_on(i * 1000, 0, 60, 0.5),
+                    note_off(i * 1000 + 1, 0, 60),
+                    note_on(i * 1000 + 2, 0, 60, 0.5),
+                ]
+            })
+            .collect();
+
+        for presses in process(&mut processor, &in_events, HUMANIZE).chunks(3) {
+            assert!(matches!(presses[0], NoteEvent::NoteOn { .. }));
+            assert!(matches!(presses[1], NoteEvent::NoteOff { .. }));
+            assert!(matches!(presses[2], NoteEvent::NoteOn { .. }));
+        }
+    }
+}
@@ -0,0 +1,404 @@
+use nih_plug::prelude::*;
+use rismidi::{params::OptionalCcNumberParam, HeldNote, HeldNotes, MidiChannel, NUM_MIDI_CHANNELS};
+use std::sync::Arc;
+
+const MIDI_CHANNEL_FROM_NIH_PLUG: &str = "MIDI channels from nih_plug must be in range 0..=15";
+
+/// The toggle CC counts as pressed from this (normalized) value on, i.e. from a MIDI value of 64.
+const TOGGLE_THRESHOLD: f32 = 0.5;
+
+struct RisLatch {
+    params: Arc<RisLatchParams>,
+
+    /// The keys which are currently held down.
+    held_keys: HeldNotes,
+
+    /// The notes which are currently sounding at the output.
+    sounding: HeldNotes,
+
+    /// Whether notes are currently latched. This is switched by the toggle CC.
+    engaged: bool,
+
+    /// Whether the toggle CC is currently pressed, per channel.
+    toggle_pressed: [bool; NUM_MIDI_CHANNELS as usize],
+
+    /// Whether the plugin has been reset, so that all sounding notes need to be released.
+    flush_pending: bool,
+}
+
+#[derive(Params)]
+struct RisLatchParams {
+    #[id = "mode"]
+    pub mode: EnumParam<Mode>,
+
+    #[nested(id_prefix = "toggle_cc")]
+    pub toggle_cc: OptionalCcNumberParam,
+}
+
+/// What happens when a note is played while notes are latched.
+#[derive(Enum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    /// Every note-on starts or stops its note.
+    #[id = "toggle"]
+    #[name = "Toggle per Note"]
+    ToggleNote,
+
+    /// The first note-on after all keys have been released replaces the latched notes.
+    #[id = "replace"]
+    #[name = "Replace Chord"]
+    ReplaceChord,
+}
+
+/// How notes are latched.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Latch {
+    mode: Mode,
+    toggle_cc: Option<u8>,
+}
+
+impl Default for RisLatch {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(RisLatchParams::default()),
+            held_keys: HeldNotes::new(),
+            sounding: HeldNotes::new(),
+            engaged: true,
+            toggle_pressed: [false; NUM_MIDI_CHANNELS as usize],
+            flush_pending: false,
+        }
+    }
+}
+
+impl Default for RisLatchParams {
+    fn default() -> Self {
+        Self {
+            mode: EnumParam::new("Mode", Mode::ReplaceChord),
+            toggle_cc: OptionalCcNumberParam::new("Toggle CC", None)
+                .with_none_selected_description("Off"),
+        }
+    }
+}
+
+fn note_off(timing: u32, channel: MidiChannel, note: u8) -> NoteEvent {
+    NoteEvent::NoteOff {
+        timing,
+        voice_id: None,
+        channel: channel.to_0_based(),
+        note,
+        velocity: 0.0,
+    }
+}
+
+impl RisLatch {
+    /// Gets the latch settings from the current parameter values.
+    fn current_latch(&self) -> Latch {
+        Latch {
+            mode: self.params.mode.value(),
+            toggle_cc: self.params.toggle_cc.value(),
+        }
+    }
+
+    /// Stops the sounding notes, either only on `channel` or on all channels. If
+    /// `keep_held_keys` is set, notes whose keys are still held keep sounding.
+    fn release(
+        &mut self,
+        timing: u32,
+        channel: Option<MidiChannel>,
+        keep_held_keys: bool,
+        send: &mut impl FnMut(NoteEvent),
+    ) {
+        let held_keys = &self.held_keys;
+        let is_released = |note: &HeldNote| {
+            (channel.is_none() || channel == Some(note.channel))
+                && !(keep_held_keys && held_keys.is_held(note.channel, note.note))
+        };
+
+        loop {
+            let released = self.sounding.iter().find(|note| is_released(note)).copied();
+            let Some(note) = released else {
+                break;
+            };
+
+            self.sounding.note_off(note.channel, note.note);
+            send(note_off(timing, note.channel, note.note));
+        }
+    }
+
+    /// Passes the events resulting from `in_event` to `send`.
+    fn process_event(
+        &mut self,
+        in_event: NoteEvent,
+        latch: Latch,
+        mut send: impl FnMut(NoteEvent),
+    ) {
+        match in_event {
+            NoteEvent::NoteOn {
+                timing,
+                voice_id: _,
+                channel,
+                note,
+                velocity,
+            } => {
+                let channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+                let starts_chord = self.held_keys.on_channel(channel).next().is_none();
+                self.held_keys.note_on(channel, note, velocity);
+
+                if self.engaged && latch.mode == Mode::ReplaceChord && starts_chord {
+                    self.release(timing, Some(channel), false, &mut send);
+                }
+
+                // A sounding note which is played again is stopped. Unless it is toggled off, it
+                // is then started again, so that the notes do not pile up.
+                if self.sounding.note_off(channel, note).is_some() {
+                    send(note_off(timing, channel, note));
+                    if self.engaged && latch.mode == Mode::ToggleNote {
+                        return;
+                    }
+                }
+
+                self.sounding.note_on(channel, note, velocity);
+                send(in_event);
+            }
+            NoteEvent::NoteOff { channel, note, .. } => {
+                let channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+                self.held_keys.note_off(channel, note);
+
+                // While notes are latched, note-offs are swallowed.
+                if !self.engaged && self.sounding.note_off(channel, note).is_some() {
+                    send(in_event);
+                }
+            }
+            NoteEvent::MidiCC {
+                timing,
+                channel,
+                cc,
+                value,
+            } if latch.toggle_cc == Some(cc) => {
+                let channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+                let pressed = value >= TOGGLE_THRESHOLD;
+                let was_pressed = std::mem::replace(
+                    &mut self.toggle_pressed[channel.to_0_based() as usize],
+                    pressed,
+                );
+
+                if pressed && !was_pressed {
+                    self.engaged = !self.engaged;
+                    if !self.engaged {
+                        self.release(timing, None, true, &mut send);
+                    }
+                }
+            }
+            _ => send(in_event),
+        }
+    }
+}
+
+impl Plugin for RisLatch {
+    const NAME: &'static str = env!("CARGO_PKG_NAME");
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const VENDOR: &'static str = "Simon Leiner";
+    const EMAIL: &'static str = "rismidi@leiner.me";
+
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const DEFAULT_INPUT_CHANNELS: u32 = 0;
+    const DEFAULT_OUTPUT_CHANNELS: u32 = 0;
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::MidiCCs;
+
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn accepts_bus_config(&self, config: &BusConfig) -> bool {
+        let no_aux_busses = AuxiliaryIOConfig {
+            num_busses: 0,
+            num_channels: 0,
+        };
+
+        config.num_input_channels == 0
+            && config.num_output_channels == 0
+            && config.aux_input_busses == no_aux_busses
+            && config.aux_output_busses == no_aux_busses
+    }
+
+    fn reset(&mut self) {
+        // We cannot send events from here, so the latched notes are released with the next
+        // buffer.
+        self.held_keys.clear();
+        self.engaged = true;
+        self.toggle_pressed = [false; NUM_MIDI_CHANNELS as usize];
+        self.flush_pending = true;
+    }
+
+    fn process(
+        &mut self,
+        _buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        if std::mem::take(&mut self.flush_pending) {
+            self.release(0, None, false, &mut |event| context.send_event(event));
+        }
+
+        while let Some(in_event) = context.next_event() {
+            let latch = self.current_latch();
+            self.process_event(in_event, latch, |event| context.send_event(event));
+        }
+
+        ProcessStatus::Normal
+    }
+}
+
+impl ClapPlugin for RisLatch {
+    const CLAP_ID: &'static str = "me.leiner.ris.latch";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some(env!("CARGO_PKG_DESCRIPTION"));
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] =
+        &[ClapFeature::Custom("MIDI"), ClapFeature::Utility];
+}
+
+impl Vst3Plugin for RisLatch {
+    const VST3_CLASS_ID: [u8; 16] = *b"risLatch........";
+    const VST3_CATEGORIES: &'static str = "Fx|Tools";
+}
+
+nih_export_clap!(RisLatch);
+nih_export_vst3!(RisLatch);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rismidi::testing::{cc, collect_events, note_off, note_on};
+
+    const REPLACE: Latch = Latch {
+        mode: Mode::ReplaceChord,
+        toggle_cc: Some(64),
+    };
+
+    #[test]
+    fn chords_are_replaced_by_the_next_chord() {
+        let mut processor = RisLatch::default();
+        processor.process_event(note_on(0, 0, 60, 0.5), REPLACE, |_| ());
+        processor.process_event(note_on(0, 0, 64, 0.5), REPLACE, |_| ());
+
+        let in_event = note_off(0, 0, 60);
+        let out_events = collect_events(|send| processor.process_event(in_event, REPLACE, send));
+        assert_eq!(out_events, vec![]);
+
+        let in_event = note_on(0, 0, 67, 0.5);
+        let out_events = collect_events(|send| processor.process_event(in_event, REPLACE, send));
+        assert_eq!(out_events, vec![note_on(0, 0, 67, 0.5)]);
+
+        let in_event = note_off(0, 0, 64);
+        let out_events = collect_events(|send| processor.process_event(in_event, REPLACE, send));
+        assert_eq!(out_events, vec![]);
+
+        let in_event = note_off(0, 0, 67);
+        let out_events = collect_events(|send| processor.process_event(in_event, REPLACE, send));
+        assert_eq!(out_events, vec![]);
+
+        let in_event = note_on(0, 0, 62, 0.5);
+        let out_events = collect_events(|send| processor.process_event(in_event, REPLACE, send));
+        assert_eq!(
+            out_events,
+            vec![
+                note_off(0, 0, 60),
+                note_off(0, 0, 64),
+                note_off(0, 0, 67),
+                note_on(0, 0, 62, 0.5)
+            ]
+        );
+    }
+
+    #[test]
+    fn notes_are_toggled() {
+        let mut processor = RisLatch::default();
+        let toggle = Latch {
+            mode: Mode::ToggleNote,
+            ..REPLACE
+        };
+        processor.process_event(note_on(0, 0, 60, 0.5), toggle, |_| ());
+        processor.process_event(note_off(0, 0, 60), toggle, |_| ());
+
+        let in_event = note_on(0, 0, 64, 0.5);
+        let out_events = collect_events(|send| processor.process_event(in_event, toggle, send));
+        assert_eq!(out_events, vec![note_on(0, 0, 64, 0.5)]);
+
+        let in_event = note_on(0, 0, 60, 0.5);
+        let out_events = collect_events(|send| processor.process_event(in_event, toggle, send));
+        assert_eq!(out_events, vec![note_off(0, 0, 60)]);
+
+        let in_event = note_off(0, 0, 60);
+        let out_events = collect_events(|send| processor.process_event(in_event, toggle, send));
+        assert_eq!(out_events, vec![]);
+    }
+
+    #[test]
+    fn toggle_cc_switches_latching_off_and_on() {
+        let mut processor = RisLatch::default();
+        processor.process_event(note_on(0, 0, 60, 0.5), REPLACE, |_| ());
+        processor.process_event(note_on(0, 0, 64, 0.5), REPLACE, |_| ());
+        processor.process_event(note_off(0, 0, 60), REPLACE, |_| ());
+
+        // The key of 64 is still held, so it keeps playing.
+        let in_event = cc(0, 0, 64, 1.0);
+        let out_events = collect_events(|send| processor.process_event(in_event, REPLACE, send));
+        assert_eq!(out_events, vec![note_off(0, 0, 60)]);
+
+        let in_event = note_off(0, 0, 64);
+        let out_events = collect_events(|send| processor.process_event(in_event, REPLACE, send));
+        assert_eq!(out_events, vec![note_off(0, 0, 64)]);
+
+        let in_event = cc(0, 0, 64, 0.0);
+        let out_events = collect_events(|send| processor.process_event(in_event, REPLACE, send));
+        assert_eq!(out_events, vec![]);
+
+        let in_event = cc(0, 0, 63, 1.0);
+        let out_events = collect_events(|send| processor.process_event(in_event, REPLACE, send));
+        assert_eq!(out_events, vec![cc(0, 0, 63, 1.0)]);
+
+        processor.process_event(cc(0, 0, 64, 1.0), REPLACE, |_| ());
+        processor.process_event(note_on(0, 0, 62, 0.5), REPLACE, |_| ());
+
+        let in_event = note_off(0, 0, 62);
+        let out_events = collect_events(|send| processor.process_event(in_event, REPLACE, send));
+        assert_eq!(out_events, vec![]);
+    }
+
+    #[test]
+    fn latched_notes_are_released_after_reset() {
+        let mut processor = RisLatch::default();
+        processor.process_event(note_on(0, 0, 60, 0.5), REPLACE, |_| ());
+        processor.process_event(note_off(0, 0, 60), REPLACE, |_| ());
+
+        processor.reset();
+        let out_events =
+            collect_events(|send| processor.release(0, None, false, &mut |event| send(event)));
+        assert_eq!(out_events, vec![note_off(0, 0, 60)]);
+    }
+
+    #[test]
+    fn latching_is_switched_on_after_reset() {
+        let mut processor = RisLatch::default();
+        processor.process_event(cc(0, 0, 64, 1.0), REPLACE, |_| ());
+        assert!(!processor.engaged);
+
+        processor.reset();
+        assert!(processor.engaged);
+        processor.process_event(note_on(0, 0, 60, 0.5), REPLACE, |_| ());
+
+        let in_event = note_off(0, 0, 60);
+        let out_events = collect_events(|send| processor.process_event(in_event, REPLACE, send));
+        assert_eq!(out_events, vec![]);
+    }
+}
@@ -0,0 +1,495 @@
+use nih_plug::prelude::*;
+use rismidi::{
+    params::CcNumberParam, BeatClock, MidiChannel, NoteValue, Rng, MAX_14_BIT, MAX_7_BIT,
+};
+use std::f64::consts::TAU;
+use std::sync::Arc;
+
+/// The seed for the "S&H" shape, so that renders are reproducible.
+const RANDOM_SEED: u64 = 0;
+
+struct RisLfo {
+    params: Arc<RisLfoParams>,
+    sample_rate: f32,
+    clock: BeatClock,
+    rng: Rng,
+
+    /// The position of the free-running LFO at the start of the current buffer, in cycles.
+    free_cycles: f64,
+
+    /// The number of samples from the start of the current buffer until the next value is due.
+    next_output: u32,
+
+    /// The cycle in which the current random value of the "S&H" shape was chosen.
+    random_cycle: Option<i64>,
+    random_value: f32,
+
+    /// Where the last value was sent, and its raw MIDI value. Values are only sent if they change.
+    last_sent: Option<(Destination, u16)>,
+}
+
+#[derive(Params)]
+struct RisLfoParams {
+    #[id = "shape"]
+    pub shape: EnumParam<Shape>,
+
+    #[id = "sync"]
+    pub sync: BoolParam,
+
+    #[id = "rate"]
+    pub rate: FloatParam,
+
+    #[id = "note_value"]
+    pub note_value: EnumParam<NoteValue>,
+
+    #[id = "depth"]
+    pub depth: FloatParam,
+
+    #[id = "target"]
+    pub target: EnumParam<Target>,
+
+    #[nested(id_prefix = "cc")]
+    pub cc: CcNumberParam,
+
+    #[id = "channel"]
+    pub channel: EnumParam<MidiChannel>,
+
+    #[id = "max_rate"]
+    pub max_rate: IntParam,
+}
+
+/// The waveform of the LFO.
+#[derive(Enum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Shape {
+    #[id = "sine"]
+    #[name = "Sine"]
+    Sine,
+
+    #[id = "triangle"]
+    #[name = "Triangle"]
+    Triangle,
+
+    #[id = "saw"]
+    #[name = "Saw"]
+    Saw,
+
+    #[id = "square"]
+    #[name = "Square"]
+    Square,
+
+    /// A new random value for every cycle.
+    #[id = "sample_and_hold"]
+    #[name = "S&H"]
+    SampleAndHold,
+}
+
+/// The kind of event the LFO is sent as.
+#[derive(Enum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Target {
+    #[id = "cc"]
+    #[name = "CC"]
+    Cc,
+
+    #[id = "pitch_bend"]
+    #[name = "Pitch Bend"]
+    PitchBend,
+
+    #[id = "channel_pressure"]
+    #[name = "Channel Pressure"]
+    ChannelPressure,
+}
+
+/// Where the LFO's values are sent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Destination {
+    target: Target,
+    channel: MidiChannel,
+    cc: u8,
+}
+
+/// The parameter values that determine the LFO's output.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Lfo {
+    shape: Shape,
+    depth: f32,
+    destination: Destination,
+}
+
+impl Default for RisLfo {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(RisLfoParams::default()),
+            sample_rate: 44100.0,
+            clock: BeatClock::new(),
+            rng: Rng::new(RANDOM_SEED),
+            free_cycles: 0.0,
+            next_output: 0,
+            random_cycle: None,
+            random_value: 0.0,
+            last_sent: None,
+        }
+    }
+}
+
+impl Default for RisLfoParams {
+    fn default() -> Self {
+        Self {
+            shape: EnumParam::new("Shape", Shape::Sine),
+            sync: BoolParam::new("Tempo Sync", true),
+            rate: FloatParam::new(
+                "Rate",
+                1.0,
+                FloatRange::Skewed {
+                    min: 0.01,
+                    max: 20.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" Hz")
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            note_value: EnumParam::new("Note Value", NoteValue::Whole),
+            depth: FloatParam::new("Depth", 1.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_unit(" %")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            target: EnumParam::new("Target", Target::Cc),
+            cc: CcNumberParam::new("CC", 1),
+            channel: EnumParam::new("Channel", MidiChannel::Channel1),
+            max_rate: IntParam::new("Max Rate", 100, IntRange::Linear { min: 10, max: 1000 })
+                .with_unit(" /s"),
+        }
+    }
+}
+
+impl Shape {
+    /// The value of the waveform from -1 to 1 at `phase`, which is in the range `0.0..1.0`.
+    /// `random` is used as the value of the "S&H" shape.
+    fn value(&self, phase: f64, random: f32) -> f32 {
+        let value = match self {
+            Shape::Sine => (phase * TAU).sin(),
+            Shape::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Shape::Saw => 2.0 * phase - 1.0,
+            Shape::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Shape::SampleAndHold => random as f64,
+        };
+
+        value as f32
+    }
+}
+
+impl Target {
+    /// The highest raw MIDI value of the target.
+    fn max(&self) -> u16 {
+        match self {
+            Target::Cc | Target::ChannelPressure => MAX_7_BIT,
+            Target::PitchBend => MAX_14_BIT,
+        }
+    }
+}
+
+impl RisLfo {
+    /// Gets the LFO settings from the current parameter values.
+    fn current_lfo(&self) -> Lfo {
+        Lfo {
+            shape: self.params.shape.value(),
+            depth: self.params.depth.value(),
+            destination: Destination {
+                target: self.params.target.value(),
+                channel: self.params.channel.value(),
+                cc: self.params.cc.value(),
+            },
+        }
+    }
+
+    /// Returns the timing of the next value to send in a buffer of `num_samples` samples, sending
+    /// at most one value every `interval` samples. Returns `None` once the buffer is done.
+    fn next_output_timing(&mut self, num_samples: u32, interval: u32) -> Option<u32> {
+        if self.next_output >= num_samples {
+            self.next_output -= num_samples;
+            return None;
+        }
+
+        let timing = self.next_output;
+        self.next_output += interval;
+        Some(timing)
+    }
+
+    /// Computes the LFO's value at `cycles` and returns the event to send at `timing`, unless the
+    /// value has not changed since the last event.
+    fn next_event(&mut self, timing: u32, cycles: f64, lfo: Lfo) -> Option<NoteEvent> {
+        let cycle = cycles.floor() as i64;
+        if lfo.shape == Shape::SampleAndHold && self.random_cycle != Some(cycle) {
+            self.random_cycle = Some(cycle);
+            self.random_value = self.rng.next_f32() * 2.0 - 1.0;
+        }
+
+        let wave = lfo.shape.value(cycles - cycle as f64, self.random_value);
+        let value = 0.5 + 0.5 * lfo.depth * wave;
+
+        let Destination {
+            target,
+            channel,
+            cc,
+        } = lfo.destination;
+        let max = target.max();
+        let raw = (value * max as f32).round().clamp(0.0, max as f32) as u16;
+        if self.last_sent == Some((lfo.destination, raw)) {
+            return None;
+        }
+        self.last_sent = Some((lfo.destination, raw));
+
+        let channel = channel.to_0_based();
+        let value = raw as f32 / max as f32;
+        let event = match target {
+            Target::Cc => NoteEvent::MidiCC {
+                timing,
+                channel,
+                cc,
+                value,
+            },
+            Target::PitchBend => NoteEvent::MidiPitchBend {
+                timing,
+                channel,
+                value,
+            },
+            Target::ChannelPressure => NoteEvent::MidiChannelPressure {
+                timing,
+                channel,
+                pressure: value,
+            },
+        };
+
+        Some(event)
+    }
+}
+
+impl Plugin for RisLfo {
+    const NAME: &'static str = env!("CARGO_PKG_NAME");
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const VENDOR: &'static str = "Simon Leiner";
+    const EMAIL: &'static str = "rismidi@leiner.me";
+
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const DEFAULT_INPUT_CHANNELS: u32 = 0;
+    const DEFAULT_OUTPUT_CHANNELS: u32 = 0;
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::MidiCCs;
+
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn accepts_bus_config(&self, config: &BusConfig) -> bool {
+        let no_aux_busses = AuxiliaryIOConfig {
+            num_busses: 0,
+            num_channels: 0,
+        };
+
+        config.num_input_channels == 0
+            && config.num_output_channels == 0
+            && config.aux_input_busses == no_aux_busses
+            && config.aux_output_busses == no_aux_busses
+    }
+
+    fn initialize(
+        &mut self,
+        _bus_config: &BusConfig,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        self.sample_rate = buffer_config.sample_rate;
+
+        true
+    }
+
+    fn reset(&mut self) {
+        self.clock = BeatClock::new();
+        self.rng = Rng::new(RANDOM_SEED);
+        self.free_cycles = 0.0;
+        self.next_output = 0;
+        self.random_cycle = None;
+        self.last_sent = None;
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        let num_samples = buffer.samples() as u32;
+        let transport = context.transport();
+        self.clock.update(
+            transport.playing,
+            transport.pos_beats(),
+            transport.tempo,
+            self.sample_rate,
+        );
+
+        // When synced, the LFO's cycles are aligned to the host's song position.
+        let (start_cycles, cycles_per_sample) = if self.params.sync.value() {
+            let cycle_beats = self.params.note_value.value().beats();
+            (
+                self.clock.pos_beats() / cycle_beats,
+                1.0 / self.clock.beats_to_samples(cycle_beats),
+            )
+        } else {
+            (
+                self.free_cycles,
+                self.params.rate.value() as f64 / self.sample_rate as f64,
+            )
+        };
+        let interval =
+            ((self.sample_rate / self.params.max_rate.value() as f32).round() as u32).max(1);
+
+        let mut next_event = context.next_event();
+        while let Some(timing) = self.next_output_timing(num_samples, interval) {
+            while let Some(in_event) = next_event.filter(|event| event.timing() <= timing) {
+                context.send_event(in_event);
+                next_event = context.next_event();
+            }
+
+            let lfo = self.current_lfo();
+            let cycles = start_cycles + timing as f64 * cycles_per_sample;
+            if let Some(out_event) = self.next_event(timing, cycles, lfo) {
+                context.send_event(out_event);
+            }
+        }
+
+        while let Some(in_event) = next_event {
+            context.send_event(in_event);
+            next_event = context.next_event();
+        }
+
+        self.free_cycles = start_cycles + num_samples as f64 * cycles_per_sample;
+        self.clock.advance(num_samples);
+
+        // The LFO keeps on running, even if there is no input.
+        ProcessStatus::KeepAlive
+    }
+}
+
+impl ClapPlugin for RisLfo {
+    const CLAP_ID: &'static str = "me.leiner.ris.lfo";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some(env!("CARGO_PKG_DESCRIPTION"));
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] =
+        &[ClapFeature::Custom("MIDI"), ClapFeature::Utility];
+}
+
+impl Vst3Plugin for RisLfo {
+    const VST3_CLASS_ID: [u8; 16] = *b"risLfo..........";
+    const VST3_CATEGORIES: &'static str = "Fx|Tools";
+}
+
+nih_export_clap!(RisLfo);
+nih_export_vst3!(RisLfo);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAW_CC: Lfo = Lfo {
+        shape: Shape::Saw,
+        depth: 1.0,
+        destination: Destination {
+            target: Target::Cc,
+            channel: MidiChannel::Channel3,
+            cc: 74,
+        },
+    };
+
+    fn cc(timing: u32, raw: u16) -> NoteEvent {
+        NoteEvent::MidiCC {
+            timing,
+            channel: 2,
+            cc: 74,
+            value: raw as f32 / MAX_7_BIT as f32,
+        }
+    }
+
+    #[test]
+    fn shapes_span_the_full_range() {
+        for shape in [Shape::Sine, Shape::Triangle, Shape::Saw, Shape::Square] {
+            let values: Vec<f32> = (0..100)
+                .map(|i| shape.value(i as f64 / 100.0, 0.0))
+                .collect();
+            let min = values.iter().copied().fold(f32::MAX, f32::min);
+            let max = values.iter().copied().fold(f32::MIN, f32::max);
+            assert!(min <= -0.95 && max >= 0.95, "{shape:?}: {min}..{max}");
+        }
+    }
+
+    #[test]
+    fn unchanged_values_are_not_sent_again() {
+        let mut processor = RisLfo::default();
+
+        assert_eq!(processor.next_event(0, 0.25, SAW_CC), Some(cc(0, 32)));
+        assert_eq!(processor.next_event(10, 0.251, SAW_CC), None);
+        assert_eq!(processor.next_event(20, 1.75, SAW_CC), Some(cc(20, 95)));
+
+        // A new destination always gets the current value.
+        let pitch_bend = Lfo {
+            destination: Destination {
+                target: Target::PitchBend,
+                ..SAW_CC.destination
+            },
+            ..SAW_CC
+        };
+        assert_eq!(
+            processor.next_event(30, 0.5, pitch_bend),
+            Some(NoteEvent::MidiPitchBend {
+                timing: 30,
+                channel: 2,
+                value: 8192.0 / MAX_14_BIT as f32,
+            })
+        );
+    }
+
+    #[test]
+    fn sample_and_hold_changes_once_per_cycle() {
+        let mut processor = RisLfo::default();
+        let sample_and_hold = Lfo {
+            shape: Shape::SampleAndHold,
+            ..SAW_CC
+        };
+
+        assert!(processor.next_event(0, 0.1, sample_and_hold).is_some());
+        assert_eq!(processor.next_event(10, 0.9, sample_and_hold), None);
+        assert!(processor.next_event(20, 1.1, sample_and_hold).is_some());
+    }
+
+    #[test]
+    fn values_are_spaced_by_the_max_rate() {
+        let mut processor = RisLfo::default();
+        let num_samples = 100;
+        let interval = 30;
+
+        let mut timings = Vec::new();
+        for buffer in 0..4 {
+            let start = buffer * num_samples;
+            while let Some(timing) = processor.next_output_timing(num_samples, interval) {
+                let cycles = (start + timing) as f64 / 1000.0;
+                if let Some(event) = processor.next_event(timing, cycles, SAW_CC) {
+                    timings.push(start + event.timing());
+                }
+            }
+        }
+
+        // The spacing carries over from one buffer to the next.
+        assert_eq!(timings, (0..400).step_by(30).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,408 @@
+use nih_plug::prelude::*;
+use rismidi::{HeldNote, HeldNotes, MidiChannel, NUM_MIDI_CHANNELS};
+use std::sync::Arc;
+
+const MIDI_CHANNEL_FROM_NIH_PLUG: &str = "MIDI channels from nih_plug must be in range 0..=15";
+
+struct RisMono {
+    params: Arc<RisMonoParams>,
+
+    /// The notes which are currently held down at the input.
+    held_notes: HeldNotes,
+
+    /// The note which is currently sounding at the output, per channel.
+    sounding: [Option<HeldNote>; NUM_MIDI_CHANNELS as usize],
+}
+
+#[derive(Params)]
+struct RisMonoParams {
+    #[id = "priority"]
+    pub priority: EnumParam<Priority>,
+
+    #[id = "retrigger"]
+    pub retrigger: BoolParam,
+
+    #[id = "legato"]
+    pub legato: BoolParam,
+}
+
+/// Which note plays if several notes are held.
+#[derive(Enum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Priority {
+    #[id = "last"]
+    #[name = "Last"]
+    Last,
+
+    #[id = "highest"]
+    #[name = "Highest"]
+    Highest,
+
+    #[id = "lowest"]
+    #[name = "Lowest"]
+    Lowest,
+}
+
+/// How notes are made monophonic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Mode {
+    priority: Priority,
+    retrigger: bool,
+    legato: bool,
+}
+
+impl Default for RisMono {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(RisMonoParams::default()),
+            held_notes: HeldNotes::new(),
+            sounding: [None; NUM_MIDI_CHANNELS as usize],
+        }
+    }
+}
+
+impl Default for RisMonoParams {
+    fn default() -> Self {
+        Self {
+            priority: EnumParam::new("Priority", Priority::Last),
+            retrigger: BoolParam::new("Re-trigger Held Notes", true),
+            legato: BoolParam::new("Legato", false),
+        }
+    }
+}
+
+impl Priority {
+    /// Returns `true` if a newly played note takes over from the sounding one.
+    fn prefers(&self, new: u8, sounding: u8) -> bool {
+        match self {
+            Priority::Last => true,
+            Priority::Highest => new > sounding,
+            Priority::Lowest => new < sounding,
+        }
+    }
+
+    /// Chooses the note to play from `held`, which are given in the order they were played.
+    fn choose<'a>(&self, held: impl Iterator<Item = &'a HeldNote>) -> Option<HeldNote> {
+        let chosen = match self {
+            Priority::Last => held.last(),
+            Priority::Highest => held.max_by_key(|held| held.note),
+            Priority::Lowest => held.min_by_key(|held| held.note),
+        };
+
+        chosen.copied()
+    }
+}
+
+fn note_on(timing: u32, note: HeldNote) -> NoteEvent {
+    NoteEvent::NoteOn {
+        timing,
+        voice_id: None,
+        channel: note.channel.to_0_based(),
+        note: note.note,
+        velocity: note.velocity,
+    }
+}
+
+fn note_off(timing: u32, note: HeldNote) -> NoteEvent {
+    NoteEvent::NoteOff {
+        timing,
+        voice_id: None,
+        channel: note.channel.to_0_based(),
+        note: note.note,
+        velocity: 0.0,
+    }
+}
+
+impl RisMono {
+    /// Gets the mode from the current parameter values.
+    fn current_mode(&self) -> Mode {
+        Mode {
+            priority: self.params.priority.value(),
+            retrigger: self.params.retrigger.value(),
+            legato: self.params.legato.value(),
+        }
+    }
+
+    /// Replaces the note sounding on `channel` with `next`, if any.
+    fn switch_to(
+        &mut self,
+        timing: u32,
+        channel: MidiChannel,
+        next: Option<HeldNote>,
+        mode: Mode,
+        send: &mut impl FnMut(NoteEvent),
+    ) {
+        let previous = std::mem::replace(&mut self.sounding[channel.to_0_based() as usize], next);
+
+        // With legato, the notes overlap, so that synthesizers can glide between them.
+        if let (Some(previous), false) = (previous, mode.legato) {
+            send(note_off(timing, previous));
+        }
+        if let Some(next) = next {
+            send(note_on(timing, next));
+        }
+        if let (Some(previous), true) = (previous, mode.legato) {
+            send(note_off(timing, previous));
+        }
+    }
+
+    /// Passes the events resulting from `in_event` to `send`.
+    fn process_event(&mut self, in_event: NoteEvent, mode: Mode, mut send: impl FnMut(NoteEvent)) {
+        match in_event {
+            NoteEvent::NoteOn {
+                timing,
+                voice_id: _,
+                channel,
+                note,
+                velocity,
+            } => {
+                let channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+                if !self.held_notes.note_on(channel, note, velocity) {
+                    return;
+                }
+
+                let sounding = self.sounding[channel.to_0_based() as usize];
+                let takes_over = match sounding {
+                    Some(sounding) => mode.priority.prefers(note, sounding.note),
+                    None => true,
+                };
+                if takes_over {
+                    let next = HeldNote {
+                        channel,
+                        note,
+                        velocity,
+                    };
+                    self.switch_to(timing, channel, Some(next), mode, &mut send);
+                }
+            }
+            NoteEvent::NoteOff {
+                timing,
+                voice_id: _,
+                channel,
+                note,
+                velocity: _,
+            } => {
+                let channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+                self.held_notes.note_off(channel, note);
+
+                // Note-offs of notes which are held silently are swallowed.
+                let sounding = self.sounding[channel.to_0_based() as usize];
+                if matches!(sounding, Some(sounding) if sounding.note == note) {
+                    let next = if mode.retrigger {
+                        mode.priority.choose(self.held_notes.on_channel(channel))
+                    } else {
+                        None
+                    };
+                    self.switch_to(timing, channel, next, mode, &mut send);
+                }
+            }
+            NoteEvent::PolyPressure { channel, note, .. } => {
+                let channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+                let sounding = self.sounding[channel.to_0_based() as usize];
+                if matches!(sounding, Some(sounding) if sounding.note == note) {
+                    send(in_event);
+                }
+            }
+            _ => send(in_event),
+        }
+    }
+}
+
+impl Plugin for RisMono {
+    const NAME: &'static str = env!("CARGO_PKG_NAME");
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const VENDOR: &'static str = "Simon Leiner";
+    const EMAIL: &'static str = "rismidi@leiner.me";
+
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const DEFAULT_INPUT_CHANNELS: u32 = 0;
+    const DEFAULT_OUTPUT_CHANNELS: u32 = 0;
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::MidiCCs;
+
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn accepts_bus_config(&self, config: &BusConfig) -> bool {
+        let no_aux_busses = AuxiliaryIOConfig {
+            num_busses: 0,
+            num_channels: 0,
+        };
+
+        config.num_input_channels == 0
+            && config.num_output_channels == 0
+            && config.aux_input_busses == no_aux_busses
+            && config.aux_output_busses == no_aux_busses
+    }
+
+    fn reset(&mut self) {
+        self.held_notes.clear();
+        self.sounding = [None; NUM_MIDI_CHANNELS as usize];
+    }
+
+    fn process(
+        &mut self,
+        _buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        while let Some(in_event) = context.next_event() {
+            let mode = self.current_mode();
+            self.process_event(in_event, mode, |event| context.send_event(event));
+        }
+
+        ProcessStatus::Normal
+    }
+}
+
+impl ClapPlugin for RisMono {
+    const CLAP_ID: &'static str = "me.leiner.ris.mono";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some(env!("CARGO_PKG_DESCRIPTION"));
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] =
+        &[ClapFeature::Custom("MIDI"), ClapFeature::Utility];
+}
+
+impl Vst3Plugin for RisMono {
+    const VST3_CLASS_ID: [u8; 16] = *b"risMono.........";
+    const VST3_CATEGORIES: &'static str = "Fx|Tools";
+}
+
+nih_export_clap!(RisMono);
+nih_export_vst3!(RisMono);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rismidi::testing::{collect_events, note_off, note_on};
+
+    const LAST: Mode = Mode {
+        priority: Priority::Last,
+        retrigger: true,
+        legato: false,
+    };
+
+    #[test]
+    fn released_notes_retrigger_held_notes() {
+        let mut processor = RisMono::default();
+
+        let in_event = note_on(0, 0, 60, 0.5);
+        let out_events = collect_events(|send| processor.process_event(in_event, LAST, send));
+        assert_eq!(out_events, vec![note_on(0, 0, 60, 0.5)]);
+
+        let in_event = note_on(0, 0, 64, 0.5);
+        let out_events = collect_events(|send| processor.process_event(in_event, LAST, send));
+        assert_eq!(out_events, vec![note_off(0, 0, 60), note_on(0, 0, 64, 0.5)]);
+
+        let in_event = note_on(0, 0, 62, 0.5);
+        let out_events = collect_events(|send| processor.process_event(in_event, LAST, send));
+        assert_eq!(out_events, vec![note_off(0, 0, 64), note_on(0, 0, 62, 0.5)]);
+
+        let in_event = note_off(0, 0, 64);
+        let out_events = collect_events(|send| processor.process_event(in_event, LAST, send));
+        assert_eq!(out_events, vec![]);
+
+        let in_event = note_off(0, 0, 62);
+        let out_events = collect_events(|send| processor.process_event(in_event, LAST, send));
+        assert_eq!(out_events, vec![note_off(0, 0, 62), note_on(0, 0, 60, 0.5)]);
+
+        let in_event = note_off(0, 0, 60);
+        let out_events = collect_events(|send| processor.process_event(in_event, LAST, send));
+        assert_eq!(out_events, vec![note_off(0, 0, 60)]);
+    }
+
+    #[test]
+    fn highest_and_lowest_notes_take_priority() {
+        let mut processor = RisMono::default();
+        let highest = Mode {
+            priority: Priority::Highest,
+            ..LAST
+        };
+        processor.process_event(note_on(0, 0, 60, 0.5), highest, |_| ());
+
+        let in_event = note_on(0, 0, 55, 0.5);
+        let out_events = collect_events(|send| processor.process_event(in_event, highest, send));
+        assert_eq!(out_events, vec![]);
+
+        let in_event = note_on(0, 0, 67, 0.5);
+        let out_events = collect_events(|send| processor.process_event(in_event, highest, send));
+        assert_eq!(out_events, vec![note_off(0, 0, 60), note_on(0, 0, 67, 0.5)]);
+
+        let in_event = note_off(0, 0, 67);
+        let out_events = collect_events(|send| processor.process_event(in_event, highest, send));
+        assert_eq!(out_events, vec![note_off(0, 0, 67), note_on(0, 0, 60, 0.5)]);
+
+        let mut processor = RisMono::default();
+        let lowest = Mode {
+            priority: Priority::Lowest,
+            ..LAST
+        };
+        processor.process_event(note_on(0, 0, 60, 0.5), lowest, |_| ());
+
+        let in_event = note_on(0, 0, 67, 0.5);
+        let out_events = collect_events(|send| processor.process_event(in_event, lowest, send));
+        assert_eq!(out_events, vec![]);
+
+        let in_event = note_on(0, 0, 55, 0.5);
+        let out_events = collect_events(|send| processor.process_event(in_event, lowest, send));
+        assert_eq!(out_events, vec![note_off(0, 0, 60), note_on(0, 0, 55, 0.5)]);
+
+        let in_event = note_off(0, 0, 55);
+        let out_events = collect_events(|send| processor.process_event(in_event, lowest, send));
+        assert_eq!(out_events, vec![note_off(0, 0, 55), note_on(0, 0, 60, 0.5)]);
+    }
+
+    #[test]
+    fn legato_notes_overlap() {
+        let mut processor = RisMono::default();
+        let legato = Mode {
+            legato: true,
+            ..LAST
+        };
+        processor.process_event(note_on(0, 0, 60, 0.5), legato, |_| ());
+
+        let in_event = note_on(0, 0, 64, 0.5);
+        let out_events = collect_events(|send| processor.process_event(in_event, legato, send));
+        assert_eq!(out_events, vec![note_on(0, 0, 64, 0.5), note_off(0, 0, 60)]);
+
+        let in_event = note_off(0, 0, 64);
+        let out_events = collect_events(|send| processor.process_event(in_event, legato, send));
+        assert_eq!(out_events, vec![note_on(0, 0, 60, 0.5), note_off(0, 0, 64)]);
+    }
+
+    #[test]
+    fn held_notes_can_stay_silent() {
+        let mut processor = RisMono::default();
+        let no_retrigger = Mode {
+            retrigger: false,
+            ..LAST
+        };
+        processor.process_event(note_on(0, 0, 60, 0.5), no_retrigger, |_| ());
+        processor.process_event(note_on(0, 0, 64, 0.5), no_retrigger, |_| ());
+
+        let in_event = note_off(0, 0, 64);
+        let out_events =
+            collect_events(|send| processor.process_event(in_event, no_retrigger, send));
+        assert_eq!(out_events, vec![note_off(0, 0, 64)]);
+
+        let in_event = note_off(0, 0, 60);
+        let out_events =
+            collect_events(|send| processor.process_event(in_event, no_retrigger, send));
+        assert_eq!(out_events, vec![]);
+
+        let in_event = note_on(0, 0, 62, 0.5);
+        let out_events =
+            collect_events(|send| processor.process_event(in_event, no_retrigger, send));
+        assert_eq!(out_events, vec![note_on(0, 0, 62, 0.5)]);
+    }
+}
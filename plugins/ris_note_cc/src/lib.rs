@@ -0,0 +1,526 @@
+use nih_plug::prelude::*;
+use rismidi::{
+    params::CcNumberParam, MidiChannel, OptionalMidiChannelParam, MIN_VELOCITY, NUM_MIDI_CHANNELS,
+    NUM_MIDI_NOTES,
+};
+use std::sync::Arc;
+
+const MIDI_CHANNEL_FROM_NIH_PLUG: &str = "MIDI channels from nih_plug must be in range 0..=15";
+
+struct RisNoteCc {
+    params: Arc<RisNoteCcParams>,
+
+    /// For every note converted into a CC, the output channel and CC, see [`note_index`].
+    note_ccs: Vec<Option<(u8, u8)>>,
+
+    /// For every input channel, the output channel and note triggered by the CC, if it is above
+    /// the threshold.
+    cc_notes: [Option<(u8, u8)>; NUM_MIDI_CHANNELS as usize],
+
+    /// Whether the plugin has been reset, so that the notes triggered by CCs need to be released.
+    flush_pending: bool,
+}
+
+#[derive(Params)]
+struct RisNoteCcParams {
+    #[id = "direction"]
+    pub direction: EnumParam<Direction>,
+
+    #[nested(id_prefix = "in_channel")]
+    pub in_channel: OptionalMidiChannelParam,
+
+    #[nested(id_prefix = "out_channel")]
+    pub out_channel: OptionalMidiChannelParam,
+
+    #[nested(id_prefix = "cc")]
+    pub cc: CcNumberParam,
+
+    #[id = "value_source"]
+    pub value_source: EnumParam<ValueSource>,
+
+    #[id = "reset_on_note_off"]
+    pub reset_on_note_off: BoolParam,
+
+    #[id = "note"]
+    pub note: IntParam,
+
+    #[id = "threshold"]
+    pub threshold: FloatParam,
+
+    #[id = "velocity_from_value"]
+    pub velocity_from_value: BoolParam,
+
+    #[id = "velocity"]
+    pub velocity: FloatParam,
+
+    #[id = "keep_original"]
+    pub keep_original: BoolParam,
+}
+
+#[derive(Enum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Direction {
+    #[id = "note_to_cc"]
+    #[name = "Note to CC"]
+    NoteToCc,
+
+    #[id = "cc_to_note"]
+    #[name = "CC to Note"]
+    CcToNote,
+}
+
+/// Which property of a note-on becomes the CC value.
+#[derive(Enum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ValueSource {
+    #[id = "note"]
+    #[name = "Note Number"]
+    NoteNumber,
+
+    #[id = "velocity"]
+    #[name = "Velocity"]
+    Velocity,
+}
+
+/// How events are converted.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Conversion {
+    direction: Direction,
+    in_channel: Option<MidiChannel>,
+    out_channel: Option<MidiChannel>,
+    cc: u8,
+    value_source: ValueSource,
+    reset_on_note_off: bool,
+    note: u8,
+    threshold: f32,
+
+    /// The velocity of triggered notes, or [`None`] if the CC value is used.
+    velocity: Option<f32>,
+    keep_original: bool,
+}
+
+impl Default for RisNoteCc {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(RisNoteCcParams::default()),
+            note_ccs: vec![None; NUM_MIDI_NOTES as usize * NUM_MIDI_CHANNELS as usize],
+            cc_notes: [None; NUM_MIDI_CHANNELS as usize],
+            flush_pending: false,
+        }
+    }
+}
+
+/// Creates a parameter for a normalized value, shown in percent.
+fn percentage_param(name: &str, default: f32, min: f32) -> FloatParam {
+    FloatParam::new(name, default, FloatRange::Linear { min, max: 1.0 })
+        .with_unit(" %")
+        .with_value_to_string(formatters::v2s_f32_percentage(0))
+        .with_string_to_value(formatters::s2v_f32_percentage())
+}
+
+impl Default for RisNoteCcParams {
+    fn default() -> Self {
+        Self {
+            direction: EnumParam::new("Direction", Direction::NoteToCc),
+            in_channel: OptionalMidiChannelParam::new("Input Channel", None)
+                .with_none_selected_description("All"),
+            out_channel: OptionalMidiChannelParam::new("Output Channel", None)
+                .with_none_selected_description("Same"),
+            cc: CcNumberParam::new("CC", 20),
+            value_source: EnumParam::new("Value Source", ValueSource::Velocity),
+            reset_on_note_off: BoolParam::new("Reset on Note-Off", true),
+            note: IntParam::new(
+                "Note",
+                60,
+                IntRange::Linear {
+                    min: 0,
+                    max: NUM_MIDI_NOTES as i32 - 1,
+                },
+            ),
+            threshold: percentage_param("Threshold", 0.5, MIN_VELOCITY),
+            velocity_from_value: BoolParam::new("Velocity from Value", false),
+            velocity: percentage_param("Velocity", 100.0 / 127.0, MIN_VELOCITY),
+            keep_original: BoolParam::new("Keep Original", false),
+        }
+    }
+}
+
+/// The index of a note in [`RisNoteCc::note_ccs`].
+fn note_index(channel: MidiChannel, note: u8) -> usize {
+    channel.to_0_based() as usize * NUM_MIDI_NOTES as usize + note as usize
+}
+
+impl Conversion {
+    /// Returns `true` if events on `channel` are converted.
+    fn matches(&self, channel: MidiChannel) -> bool {
+        self.in_channel.is_none() || self.in_channel == Some(channel)
+    }
+
+    /// The (0-based) channel for the converted events of events on `in_channel`.
+    fn out_channel(&self, in_channel: u8) -> u8 {
+        match self.out_channel {
+            Some(out_channel) => out_channel.to_0_based(),
+            None => in_channel,
+        }
+    }
+}
+
+impl RisNoteCc {
+    /// Gets the conversion settings from the current parameter values.
+    fn current_conversion(&self) -> Conversion {
+        let params = &self.params;
+
+        Conversion {
+            direction: params.direction.value(),
+            in_channel: params.in_channel.value(),
+            out_channel: params.out_channel.value(),
+            cc: params.cc.value(),
+            value_source: params.value_source.value(),
+            reset_on_note_off: params.reset_on_note_off.value(),
+            note: params.note.value() as u8,
+            threshold: params.threshold.value(),
+            velocity: (!params.velocity_from_value.value()).then(|| params.velocity.value()),
+            keep_original: params.keep_original.value(),
+        }
+    }
+
+    /// Passes the converted events for `in_event` to `send`.
+    fn process_event(
+        &mut self,
+        in_event: NoteEvent,
+        conversion: Conversion,
+        mut send: impl FnMut(NoteEvent),
+    ) {
+        let converted = match (conversion.direction, in_event) {
+            (
+                Direction::NoteToCc,
+                NoteEvent::NoteOn {
+                    timing,
+                    voice_id: _,
+                    channel,
+                    note,
+                    velocity,
+                },
+            ) => {
+                let in_channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+                let matches = conversion.matches(in_channel) && note < NUM_MIDI_NOTES;
+                if matches {
+                    let out_channel = conversion.out_channel(channel);
+                    send(NoteEvent::MidiCC {
+                        timing,
+                        channel: out_channel,
+                        cc: conversion.cc,
+                        value: match conversion.value_source {
+                            ValueSource::NoteNumber => note as f32 / (NUM_MIDI_NOTES - 1) as f32,
+                            ValueSource::Velocity => velocity,
+                        },
+                    });
+                    self.note_ccs[note_index(in_channel, note)] =
+                        Some((out_channel, conversion.cc));
+                }
+
+                matches
+            }
+            (
+                _,
+                NoteEvent::NoteOff {
+                    timing,
+                    voice_id: _,
+                    channel,
+                    note,
+                    velocity: _,
+                },
+            ) => {
+                let in_channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+                // We use the CC of the note-on, in case the parameters have changed since.
+                let note_cc = self
+                    .note_ccs
+                    .get_mut(note_index(in_channel, note))
+                    .and_then(Option::take);
+                match note_cc {
+                    Some((out_channel, cc)) => {
+                        if conversion.reset_on_note_off {
+                            send(NoteEvent::MidiCC {
+                                timing,
+                                channel: out_channel,
+                                cc,
+                                value: 0.0,
+                            });
+                        }
+                        true
+                    }
+                    None => false,
+                }
+            }
+            (
+                Direction::CcToNote,
+                NoteEvent::MidiCC {
+                    timing,
+                    channel,
+                    cc,
+                    value,
+                },
+            ) => {
+                let in_channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+                let matches = cc == conversion.cc && conversion.matches(in_channel);
+                if matches {
+                    let cc_note = &mut self.cc_notes[in_channel.to_0_based() as usize];
+                    if value >= conversion.threshold && cc_note.is_none() {
+                        let out_channel = conversion.out_channel(channel);
+                        send(NoteEvent::NoteOn {
+                            timing,
+                            voice_id: None,
+                            channel: out_channel,
+                            note: conversion.note,
+                            velocity: conversion.velocity.unwrap_or(value).max(MIN_VELOCITY),
+                        });
+                        *cc_note = Some((out_channel, conversion.note));
+                    } else if value < conversion.threshold {
+                        if let Some((out_channel, note)) = cc_note.take() {
+                            send(NoteEvent::NoteOff {
+                                timing,
+                                voice_id: None,
+                                channel: out_channel,
+                                note,
+                                velocity: 0.0,
+                            });
+                        }
+                    }
+                }
+
+                matches
+            }
+            _ => false,
+        };
+
+        if !converted || conversion.keep_original {
+            send(in_event);
+        }
+    }
+
+    /// Passes note-offs for all notes triggered by CCs to `send`.
+    fn release_cc_notes(&mut self, timing: u32, mut send: impl FnMut(NoteEvent)) {
+        for (channel, note) in self.cc_notes.iter_mut().filter_map(Option::take) {
+            send(NoteEvent::NoteOff {
+                timing,
+                voice_id: None,
+                channel,
+                note,
+                velocity: 0.0,
+            });
+        }
+    }
+}
+
+impl Plugin for RisNoteCc {
+    const NAME: &'static str = env!("CARGO_PKG_NAME");
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const VENDOR: &'static str = "Simon Leiner";
+    const EMAIL: &'static str = "rismidi@leiner.me";
+
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const DEFAULT_INPUT_CHANNELS: u32 = 0;
+    const DEFAULT_OUTPUT_CHANNELS: u32 = 0;
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::MidiCCs;
+
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn accepts_bus_config(&self, config: &BusConfig) -> bool {
+        let no_aux_busses = AuxiliaryIOConfig {
+            num_busses: 0,
+            num_channels: 0,
+        };
+
+        config.num_input_channels == 0
+            && config.num_output_channels == 0
+            && config.aux_input_busses == no_aux_busses
+            && config.aux_output_busses == no_aux_busses
+    }
+
+    fn reset(&mut self) {
+        // We cannot send events from here, so the notes triggered by CCs are released with the
+        // next buffer.
+        self.note_ccs.fill(None);
+        self.flush_pending = true;
+    }
+
+    fn process(
+        &mut self,
+        _buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        let conversion = self.current_conversion();
+        if std::mem::take(&mut self.flush_pending) || conversion.direction != Direction::CcToNote {
+            self.release_cc_notes(0, |event| context.send_event(event));
+        }
+
+        while let Some(in_event) = context.next_event() {
+            let conversion = self.current_conversion();
+
+            // Notes triggered by CCs would hang otherwise.
+            if conversion.direction != Direction::CcToNote {
+                self.release_cc_notes(in_event.timing(), |event| context.send_event(event));
+            }
+
+            self.process_event(in_event, conversion, |event| context.send_event(event));
+        }
+
+        ProcessStatus::Normal
+    }
+}
+
+impl ClapPlugin for RisNoteCc {
+    const CLAP_ID: &'static str = "me.leiner.ris.note_cc";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some(env!("CARGO_PKG_DESCRIPTION"));
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] =
+        &[ClapFeature::Custom("MIDI"), ClapFeature::Utility];
+}
+
+impl Vst3Plugin for RisNoteCc {
+    const VST3_CLASS_ID: [u8; 16] = *b"risNoteCc.......";
+    const VST3_CATEGORIES: &'static str = "Fx|Tools";
+}
+
+nih_export_clap!(RisNoteCc);
+nih_export_vst3!(RisNoteCc);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rismidi::testing::{cc, collect_events, note_off, note_on};
+
+    const NOTE_TO_CC: Conversion = Conversion {
+        direction: Direction::NoteToCc,
+        in_channel: None,
+        out_channel: None,
+        cc: 20,
+        value_source: ValueSource::Velocity,
+        reset_on_note_off: true,
+        note: 36,
+        threshold: 0.5,
+        velocity: Some(1.0),
+        keep_original: false,
+    };
+
+    #[test]
+    fn notes_become_ccs() {
+        let mut processor = RisNoteCc::default();
+
+        let in_event = note_on(0, 0, 60, 0.5);
+        let out_events = collect_events(|send| processor.process_event(in_event, NOTE_TO_CC, send));
+        assert_eq!(out_events, vec![cc(0, 0, 20, 0.5)]);
+
+        let by_note_number = Conversion {
+            value_source: ValueSource::NoteNumber,
+            out_channel: Some(MidiChannel::Channel10),
+            reset_on_note_off: false,
+            ..NOTE_TO_CC
+        };
+
+        let in_event = note_on(0, 1, 127, 0.5);
+        let out_events =
+            collect_events(|send| processor.process_event(in_event, by_note_number, send));
+        assert_eq!(out_events, vec![cc(0, 9, 20, 1.0)]);
+
+        let in_event = note_off(0, 1, 127);
+        let out_events =
+            collect_events(|send| processor.process_event(in_event, by_note_number, send));
+        assert_eq!(out_events, vec![]);
+    }
+
+    #[test]
+    fn note_offs_reset_the_cc_of_their_note_on() {
+        let mut processor = RisNoteCc::default();
+        processor.process_event(note_on(0, 0, 60, 0.5), NOTE_TO_CC, |_| ());
+
+        let other_cc = Conversion {
+            cc: 21,
+            ..NOTE_TO_CC
+        };
+
+        let in_event = note_off(0, 0, 60);
+        let out_events = collect_events(|send| processor.process_event(in_event, other_cc, send));
+        assert_eq!(out_events, vec![cc(0, 0, 20, 0.0)]);
+
+        let in_event = note_off(0, 0, 61);
+        let out_events = collect_events(|send| processor.process_event(in_event, other_cc, send));
+        assert_eq!(out_events, vec![note_off(0, 0, 61)]);
+    }
+
+    #[test]
+    fn ccs_crossing_the_threshold_trigger_notes() {
+        let mut processor = RisNoteCc::default();
+        let conversion = Conversion {
+            direction: Direction::CcToNote,
+            velocity: None,
+            ..NOTE_TO_CC
+        };
+
+        let in_event = cc(0, 0, 20, 0.25);
+        let out_events = collect_events(|send| processor.process_event(in_event, conversion, send));
+        assert_eq!(out_events, vec![]);
+
+        let in_event = cc(0, 0, 20, 0.75);
+        let out_events = collect_events(|send| processor.process_event(in_event, conversion, send));
+        assert_eq!(out_events, vec![note_on(0, 0, 36, 0.75)]);
+
+        let in_event = cc(0, 0, 20, 1.0);
+        let out_events = collect_events(|send| processor.process_event(in_event, conversion, send));
+        assert_eq!(out_events, vec![]);
+
+        let in_event = cc(0, 0, 20, 0.0);
+        let out_events = collect_events(|send| processor.process_event(in_event, conversion, send));
+        assert_eq!(out_events, vec![note_off(0, 0, 36)]);
+    }
+
+    #[test]
+    fn other_channels_and_ccs_pass_through() {
+        let mut processor = RisNoteCc::default();
+        let conversion = Conversion {
+            direction: Direction::CcToNote,
+            in_channel: Some(MidiChannel::Channel2),
+            keep_original: true,
+            ..NOTE_TO_CC
+        };
+
+        let in_event = cc(0, 1, 20, 1.0);
+        let out_events = collect_events(|send| processor.process_event(in_event, conversion, send));
+        assert_eq!(out_events, vec![note_on(0, 1, 36, 1.0), cc(0, 1, 20, 1.0)]);
+
+        let in_event = cc(0, 0, 20, 1.0);
+        let out_events = collect_events(|send| processor.process_event(in_event, conversion, send));
+        assert_eq!(out_events, vec![cc(0, 0, 20, 1.0)]);
+
+        let in_event = cc(0, 1, 21, 1.0);
+        let out_events = collect_events(|send| processor.process_event(in_event, conversion, send));
+        assert_eq!(out_events, vec![cc(0, 1, 21, 1.0)]);
+    }
+
+    #[test]
+    fn notes_triggered_by_ccs_are_kept_on_reset() {
+        let mut processor = RisNoteCc::default();
+        let conversion = Conversion {
+            direction: Direction::CcToNote,
+            ..NOTE_TO_CC
+        };
+        processor.process_event(cc(0, 0, 20, 1.0), conversion, |_| ());
+
+        processor.reset();
+        assert!(processor.flush_pending);
+        let out_events = collect_events(|send| processor.release_cc_notes(0, send));
+        assert_eq!(out_events, vec![note_off(0, 0, 36)]);
+    }
+}
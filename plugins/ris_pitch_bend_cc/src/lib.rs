@@ -0,0 +1,483 @@
+use nih_plug::prelude::*;
+use rismidi::{
+    lsb_cc, params::CcNumberParam, HighResCcDecoder, HighResCcEncoder, MidiChannel, MAX_14_BIT,
+    MAX_7_BIT,
+};
+use std::sync::Arc;
+
+const MIDI_CHANNEL_FROM_NIH_PLUG: &str = "MIDI channels from nih_plug must be in range 0..=15";
+
+struct RisPitchBendCc {
+    params: Arc<RisPitchBendCcParams>,
+
+    /// Combines incoming 14-bit CCs.
+    decoder: HighResCcDecoder,
+
+    /// Splits outgoing 14-bit CCs.
+    encoder: HighResCcEncoder,
+}
+
+#[derive(Params)]
+struct RisPitchBendCcParams {
+    #[id = "direction"]
+    pub direction: EnumParam<Direction>,
+
+    #[nested(id_prefix = "cc")]
+    pub cc: CcNumberParam,
+
+    #[id = "resolution"]
+    pub resolution: EnumParam<Resolution>,
+
+    #[id = "range"]
+    pub range: EnumParam<BendRange>,
+
+    #[id = "keep_original"]
+    pub keep_original: BoolParam,
+}
+
+#[derive(Enum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Direction {
+    #[id = "bend_to_cc"]
+    #[name = "Pitch Bend to CC"]
+    BendToCc,
+
+    #[id = "cc_to_bend"]
+    #[name = "CC to Pitch Bend"]
+    CcToBend,
+}
+
+#[derive(Enum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Resolution {
+    #[id = "7_bit"]
+    #[name = "7-bit"]
+    SevenBit,
+
+    /// The CC is sent as an MSB/LSB pair, where the LSB is 32 numbers above the MSB. Only CCs
+    /// 0 to 31 have an LSB, so all other CCs fall back to 7-bit.
+    #[id = "14_bit"]
+    #[name = "14-bit (CC 0-31)"]
+    FourteenBit,
+}
+
+/// Which part of the pitch bend range corresponds to the full range of the CC.
+#[derive(Enum, Clone, Copy, Debug, PartialEq, Eq)]
+enum BendRange {
+    /// The centre of the CC is the centre of the pitch bend.
+    #[id = "full"]
+    #[name = "Full"]
+    Full,
+
+    /// The lowest CC value is the centre of the pitch bend.
+    #[id = "up"]
+    #[name = "Up Only"]
+    Up,
+
+    /// The lowest CC value is the centre of the pitch bend, and higher values bend down.
+    #[id = "down"]
+    #[name = "Down Only"]
+    Down,
+}
+
+/// How events are converted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Conversion {
+    direction: Direction,
+    cc: u8,
+    resolution: Resolution,
+    range: BendRange,
+    keep_original: bool,
+}
+
+impl Default for RisPitchBendCc {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(RisPitchBendCcParams::default()),
+            decoder: HighResCcDecoder::new(),
+            encoder: HighResCcEncoder::new(),
+        }
+    }
+}
+
+impl Default for RisPitchBendCcParams {
+    fn default() -> Self {
+        Self {
+            direction: EnumParam::new("Direction", Direction::BendToCc),
+            cc: CcNumberParam::new("CC", 1),
+            resolution: EnumParam::new("Resolution", Resolution::SevenBit),
+            range: EnumParam::new("Range", BendRange::Full),
+            keep_original: BoolParam::new("Keep Original", false),
+        }
+    }
+}
+
+/// The centre of a raw MIDI value range, e.g. 64 for 7-bit values.
+fn centre(max: u16) -> u16 {
+    max / 2 + 1
+}
+
+/// Converts a raw value into a position from -1 to 1, such that the centre of the raw range
+/// (e.g. 64 for a 7-bit value) is exactly 0.
+fn bipolar_from_raw(raw: u16, max: u16) -> f32 {
+    let centre = centre(max);
+    if raw < centre {
+        (raw as f32 - centre as f32) / centre as f32
+    } else {
+        (raw - centre) as f32 / (max - centre) as f32
+    }
+}
+
+/// The inverse of [`bipolar_from_raw`].
+fn bipolar_to_raw(position: f32, max: u16) -> u16 {
+    let centre = centre(max) as f32;
+    let raw = if position < 0.0 {
+        centre + position * centre
+    } else {
+        centre + position * (max as f32 - centre)
+    };
+
+    raw.round().clamp(0.0, max as f32) as u16
+}
+
+/// Converts a normalized value from nih_plug into its raw MIDI value.
+fn normalized_to_raw(value: f32, max: u16) -> u16 {
+    (value * max as f32).round().clamp(0.0, max as f32) as u16
+}
+
+impl BendRange {
+    /// Converts a raw CC value into a pitch bend position from -1 to 1.
+    fn cc_to_bend(&self, raw: u16, max: u16) -> f32 {
+        match self {
+            BendRange::Full => bipolar_from_raw(raw, max),
+            BendRange::Up => raw as f32 / max as f32,
+            BendRange::Down => -(raw as f32) / max as f32,
+        }
+    }
+
+    /// Converts a pitch bend position from -1 to 1 into a raw CC value.
+    fn bend_to_cc(&self, position: f32, max: u16) -> u16 {
+        let unipolar = match self {
+            BendRange::Full => return bipolar_to_raw(position, max),
+            BendRange::Up => position.max(0.0),
+            BendRange::Down => (-position).max(0.0),
+        };
+
+        (unipolar * max as f32).round() as u16
+    }
+}
+
+impl Conversion {
+    /// The CC number carrying the LSB, if the CC is sent with 14 bits.
+    fn lsb_cc(&self) -> Option<u8> {
+        match self.resolution {
+            Resolution::SevenBit => None,
+            Resolution::FourteenBit => lsb_cc(self.cc),
+        }
+    }
+
+    /// The highest raw value of the CC.
+    fn cc_max(&self) -> u16 {
+        match self.lsb_cc() {
+            Some(_) => MAX_14_BIT,
+            None => MAX_7_BIT,
+        }
+    }
+}
+
+impl RisPitchBendCc {
+    /// Gets the conversion settings from the current parameter values.
+    fn current_conversion(&self) -> Conversion {
+        Conversion {
+            direction: self.params.direction.value(),
+            cc: self.params.cc.value(),
+            resolution: self.params.resolution.value(),
+            range: self.params.range.value(),
+            keep_original: self.params.keep_original.value(),
+        }
+    }
+
+    /// Passes the converted events for `in_event` to `send`.
+    fn process_event(
+        &mut self,
+        in_event: NoteEvent,
+        conversion: Conversion,
+        mut send: impl FnMut(NoteEvent),
+    ) {
+        let converted = match (conversion.direction, in_event) {
+            (
+                Direction::BendToCc,
+                NoteEvent::MidiPitchBend {
+                    timing,
+                    channel,
+                    value,
+                },
+            ) => {
+                let position = bipolar_from_raw(normalized_to_raw(value, MAX_14_BIT), MAX_14_BIT);
+                let raw = conversion.range.bend_to_cc(position, conversion.cc_max());
+
+                match conversion.lsb_cc() {
+                    Some(_) => {
+                        let channel = MidiChannel::try_from_0_based(channel.into())
+                            .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+                        self.encoder
+                            .encode(timing, channel, conversion.cc, raw, &mut send);
+                    }
+                    None => send(NoteEvent::MidiCC {
+                        timing,
+                        channel,
+                        cc: conversion.cc,
+                        value: raw as f32 / MAX_7_BIT as f32,
+                    }),
+                }
+
+                true
+            }
+            (
+                Direction::CcToBend,
+                NoteEvent::MidiCC {
+                    timing,
+                    channel,
+                    cc,
+                    value,
+                },
+            ) => {
+                let raw = match conversion.lsb_cc() {
+                    // Until the LSB arrives, the decoder assumes it to be 0.
+                    Some(_) => self
+                        .decoder
+                        .handle_event(&in_event)
+                        .filter(|decoded| decoded.cc == conversion.cc)
+                        .map(|decoded| decoded.value),
+                    None => (cc == conversion.cc).then(|| normalized_to_raw(value, MAX_7_BIT)),
+                };
+
+                if let Some(raw) = raw {
+                    let position = conversion.range.cc_to_bend(raw, conversion.cc_max());
+                    send(NoteEvent::MidiPitchBend {
+                        timing,
+                        channel,
+                        value: bipolar_to_raw(position, MAX_14_BIT) as f32 / MAX_14_BIT as f32,
+                    });
+                }
+
+                raw.is_some()
+            }
+            _ => false,
+        };
+
+        if !converted || conversion.keep_original {
+            send(in_event);
+        }
+    }
+}
+
+impl Plugin for RisPitchBendCc {
+    const NAME: &'static str = env!("CARGO_PKG_NAME");
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const VENDOR: &'static str = "Simon Leiner";
+    const EMAIL: &'static str = "rismidi@leiner.me";
+
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const DEFAULT_INPUT_CHANNELS: u32 = 0;
+    const DEFAULT_OUTPUT_CHANNELS: u32 = 0;
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::MidiCCs;
+
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn accepts_bus_config(&self, config: &BusConfig) -> bool {
+        let no_aux_busses = AuxiliaryIOConfig {
+            num_busses: 0,
+            num_channels: 0,
+        };
+
+        config.num_input_channels == 0
+            && config.num_output_channels == 0
+            && config.aux_input_busses == no_aux_busses
+            && config.aux_output_busses == no_aux_busses
+    }
+
+    fn reset(&mut self) {
+        self.decoder.reset();
+        self.encoder.reset();
+    }
+
+    fn process(
+        &mut self,
+        _buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        while let Some(in_event) = context.next_event() {
+            let conversion = self.current_conversion();
+            self.process_event(in_event, conversion, |event| context.send_event(event));
+        }
+
+        ProcessStatus::Normal
+    }
+}
+
+impl ClapPlugin for RisPitchBendCc {
+    const CLAP_ID: &'static str = "me.leiner.ris.pitch_bend_cc";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some(env!("CARGO_PKG_DESCRIPTION"));
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] =
+        &[ClapFeature::Custom("MIDI"), ClapFeature::Utility];
+}
+
+impl Vst3Plugin for RisPitchBendCc {
+    const VST3_CLASS_ID: [u8; 16] = *b"risPitchBendCc..";
+    const VST3_CATEGORIES: &'static str = "Fx|Tools";
+}
+
+nih_export_clap!(RisPitchBendCc);
+nih_export_vst3!(RisPitchBendCc);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rismidi::testing::collect_events;
+
+    const BEND_TO_CC: Conversion = Conversion {
+        direction: Direction::BendToCc,
+        cc: 1,
+        resolution: Resolution::SevenBit,
+        range: BendRange::Full,
+        keep_original: false,
+    };
+
+    fn bend(raw: u16) -> NoteEvent {
+        NoteEvent::MidiPitchBend {
+            timing: 0,
+            channel: 0,
+            value: raw as f32 / MAX_14_BIT as f32,
+        }
+    }
+
+    fn cc(cc: u8, raw: u16) -> NoteEvent {
+        NoteEvent::MidiCC {
+            timing: 0,
+            channel: 0,
+            cc,
+            value: raw as f32 / MAX_7_BIT as f32,
+        }
+    }
+
+    #[test]
+    fn centre_and_extremes_are_kept() {
+        let mut processor = RisPitchBendCc::default();
+        let cc_to_bend = Conversion {
+            direction: Direction::CcToBend,
+            ..BEND_TO_CC
+        };
+
+        for (bend_raw, cc_raw) in [(0, 0), (8192, 64), (16383, 127)] {
+            let in_event = bend(bend_raw);
+            let out_events =
+                collect_events(|send| processor.process_event(in_event, BEND_TO_CC, send));
+            assert_eq!(out_events, vec![cc(1, cc_raw)]);
+
+            let in_event = cc(1, cc_raw);
+            let out_events =
+                collect_events(|send| processor.process_event(in_event, cc_to_bend, send));
+            assert_eq!(out_events, vec![bend(bend_raw)]);
+        }
+    }
+
+    #[test]
+    fn fourteen_bit_ccs_are_sent_as_msb_and_lsb() {
+        let mut processor = RisPitchBendCc::default();
+        let conversion = Conversion {
+            resolution: Resolution::FourteenBit,
+            ..BEND_TO_CC
+        };
+
+        let in_event = bend(1000);
+        let out_events = collect_events(|send| processor.process_event(in_event, conversion, send));
+        assert_eq!(out_events, vec![cc(1, 7), cc(33, 104)]);
+    }
+
+    #[test]
+    fn ccs_without_lsb_fall_back_to_seven_bit() {
+        let mut processor = RisPitchBendCc::default();
+        let conversion = Conversion {
+            cc: 40,
+            resolution: Resolution::FourteenBit,
+            ..BEND_TO_CC
+        };
+
+        let in_event = bend(16383);
+        let out_events = collect_events(|send| processor.process_event(in_event, conversion, send));
+        assert_eq!(out_events, vec![cc(40, 127)]);
+
+        let cc_to_bend = Conversion {
+            direction: Direction::CcToBend,
+            ..conversion
+        };
+        let in_event = cc(40, 127);
+        let out_events = collect_events(|send| processor.process_event(in_event, cc_to_bend, send));
+        assert_eq!(out_events, vec![bend(16383)]);
+    }
+
+    #[test]
+    fn fourteen_bit_ccs_are_combined() {
+        let mut processor = RisPitchBendCc::default();
+        let conversion = Conversion {
+            direction: Direction::CcToBend,
+            resolution: Resolution::FourteenBit,
+            ..BEND_TO_CC
+        };
+
+        let in_event = cc(1, 7);
+        let out_events = collect_events(|send| processor.process_event(in_event, conversion, send));
+        assert_eq!(out_events, vec![bend(896)]);
+
+        let in_event = cc(33, 104);
+        let out_events = collect_events(|send| processor.process_event(in_event, conversion, send));
+        assert_eq!(out_events, vec![bend(1000)]);
+
+        let in_event = cc(2, 104);
+        let out_events = collect_events(|send| processor.process_event(in_event, conversion, send));
+        assert_eq!(out_events, vec![cc(2, 104)]);
+    }
+
+    #[test]
+    fn half_ranges_start_at_the_centre() {
+        let mut processor = RisPitchBendCc::default();
+        let up = Conversion {
+            range: BendRange::Up,
+            keep_original: true,
+            ..BEND_TO_CC
+        };
+
+        let in_event = bend(0);
+        let out_events = collect_events(|send| processor.process_event(in_event, up, send));
+        assert_eq!(out_events, vec![cc(1, 0), bend(0)]);
+
+        let in_event = bend(16383);
+        let out_events = collect_events(|send| processor.process_event(in_event, up, send));
+        assert_eq!(out_events, vec![cc(1, 127), bend(16383)]);
+
+        let down = Conversion {
+            direction: Direction::CcToBend,
+            range: BendRange::Down,
+            ..BEND_TO_CC
+        };
+
+        let in_event = cc(1, 0);
+        let out_events = collect_events(|send| processor.process_event(in_event, down, send));
+        assert_eq!(out_events, vec![bend(8192)]);
+
+        let in_event = cc(1, 127);
+        let out_events = collect_events(|send| processor.process_event(in_event, down, send));
+        assert_eq!(out_events, vec![bend(0)]);
+    }
+}
@@ -0,0 +1,389 @@
+use nih_plug::prelude::*;
+use rismidi::{HeldNote, HeldNotes, MidiChannel, NUM_MIDI_CHANNELS, NUM_MIDI_NOTES};
+use std::sync::Arc;
+
+const MIDI_CHANNEL_FROM_NIH_PLUG: &str = "MIDI channels from nih_plug must be in range 0..=15";
+
+/// The highest selectable number of voices.
+const MAX_VOICES: i32 = 32;
+
+struct RisPolyLimit {
+    params: Arc<RisPolyLimitParams>,
+
+    /// The notes which are currently sounding, i.e. which have been sent and not been stolen.
+    voices: HeldNotes,
+
+    /// For every note, whether it has been stolen and its note-off has to be swallowed, see
+    /// [`note_index`].
+    stolen: Vec<bool>,
+}
+
+#[derive(Params)]
+struct RisPolyLimitParams {
+    #[id = "voices"]
+    pub voices: IntParam,
+
+    #[id = "scope"]
+    pub scope: EnumParam<Scope>,
+
+    #[id = "stealing"]
+    pub stealing: EnumParam<Stealing>,
+}
+
+/// Which notes share the available voices.
+#[derive(Enum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Scope {
+    #[id = "channel"]
+    #[name = "Per Channel"]
+    PerChannel,
+
+    #[id = "global"]
+    #[name = "Global"]
+    Global,
+}
+
+/// Which voice is stopped when a note is played while all voices are in use.
+#[derive(Enum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Stealing {
+    #[id = "oldest"]
+    #[name = "Oldest"]
+    Oldest,
+
+    #[id = "newest"]
+    #[name = "Newest"]
+    Newest,
+
+    #[id = "lowest"]
+    #[name = "Lowest"]
+    Lowest,
+
+    #[id = "highest"]
+    #[name = "Highest"]
+    Highest,
+
+    #[id = "quietest"]
+    #[name = "Quietest"]
+    Quietest,
+}
+
+/// How notes are limited.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Limit {
+    voices: usize,
+    scope: Scope,
+    stealing: Stealing,
+}
+
+impl Default for RisPolyLimit {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(RisPolyLimitParams::default()),
+            voices: HeldNotes::new(),
+            stolen: vec![false; NUM_MIDI_NOTES as usize * NUM_MIDI_CHANNELS as usize],
+        }
+    }
+}
+
+impl Default for RisPolyLimitParams {
+    fn default() -> Self {
+        Self {
+            voices: IntParam::new(
+                "Voices",
+                4,
+                IntRange::Linear {
+                    min: 1,
+                    max: MAX_VOICES,
+                },
+            ),
+            scope: EnumParam::new("Scope", Scope::PerChannel),
+            stealing: EnumParam::new("Stealing", Stealing::Oldest),
+        }
+    }
+}
+
+/// The index of a note in [`RisPolyLimit::stolen`].
+fn note_index(channel: MidiChannel, note: u8) -> usize {
+    channel.to_0_based() as usize * NUM_MIDI_NOTES as usize + note as usize
+}
+
+impl Stealing {
+    /// Chooses the voice to steal from `voices`, which are given in the order they were played.
+    fn choose<'a>(&self, mut voices: impl Iterator<Item = &'a HeldNote>) -> Option<HeldNote> {
+        let victim = match self {
+            Stealing::Oldest => voices.next(),
+            Stealing::Newest => voices.last(),
+            Stealing::Lowest => voices.min_by_key(|voice| voice.note),
+            Stealing::Highest => voices.max_by_key(|voice| voice.note),
+            Stealing::Quietest => voices.min_by(|a, b| a.velocity.total_cmp(&b.velocity)),
+        };
+
+        victim.copied()
+    }
+}
+
+impl RisPolyLimit {
+    /// Gets the limit from the current parameter values.
+    fn current_limit(&self) -> Limit {
+        Limit {
+            voices: self.params.voices.value() as usize,
+            scope: self.params.scope.value(),
+            stealing: self.params.stealing.value(),
+        }
+    }
+
+    /// Chooses a voice to steal for a new note on `channel`, if all voices are in use.
+    fn victim(&self, channel: MidiChannel, limit: Limit) -> Option<HeldNote> {
+        match limit.scope {
+            Scope::PerChannel if self.voices.on_channel(channel).count() >= limit.voices => {
+                limit.stealing.choose(self.voices.on_channel(channel))
+            }
+            Scope::Global if self.voices.len() >= limit.voices => {
+                limit.stealing.choose(self.voices.iter())
+            }
+            _ => None,
+        }
+    }
+
+    /// Passes the events resulting from `in_event` to `send`.
+    fn process_event(
+        &mut self,
+        in_event: NoteEvent,
+        limit: Limit,
+        mut send: impl FnMut(NoteEvent),
+    ) {
+        match in_event {
+            NoteEvent::NoteOn {
+                timing,
+                voice_id: _,
+                channel,
+                note,
+                velocity,
+            } if note < NUM_MIDI_NOTES => {
+                let midi_channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+                self.stolen[note_index(midi_channel, note)] = false;
+
+                // A repeated note-on does not need another voice.
+                if !self.voices.is_held(midi_channel, note) {
+                    // The limit may have been lowered since the last note, so several voices
+                    // might have to be stolen.
+                    while let Some(victim) = self.victim(midi_channel, limit) {
+                        self.voices.note_off(victim.channel, victim.note);
+                        self.stolen[note_index(victim.channel, victim.note)] = true;
+                        send(NoteEvent::NoteOff {
+                            timing,
+                            voice_id: None,
+                            channel: victim.channel.to_0_based(),
+                            note: victim.note,
+                            velocity: 0.0,
+                        });
+                    }
+                }
+
+                self.voices.note_on(midi_channel, note, velocity);
+                send(in_event);
+            }
+            NoteEvent::NoteOff { channel, note, .. }
+            | NoteEvent::PolyPressure { channel, note, .. }
+                if note < NUM_MIDI_NOTES =>
+            {
+                let midi_channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+                let stolen = &mut self.stolen[note_index(midi_channel, note)];
+
+                // Stolen notes have already been released.
+                if matches!(in_event, NoteEvent::NoteOff { .. }) {
+                    self.voices.note_off(midi_channel, note);
+                    if std::mem::take(stolen) {
+                        return;
+                    }
+                } else if *stolen {
+                    return;
+                }
+
+                send(in_event);
+            }
+            _ => send(in_event),
+        }
+    }
+}
+
+impl Plugin for RisPolyLimit {
+    const NAME: &'static str = env!("CARGO_PKG_NAME");
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const VENDOR: &'static str = "Simon Leiner";
+    const EMAIL: &'static str = "rismidi@leiner.me";
+
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const DEFAULT_INPUT_CHANNELS: u32 = 0;
+    const DEFAULT_OUTPUT_CHANNELS: u32 = 0;
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::MidiCCs;
+
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn accepts_bus_config(&self, config: &BusConfig) -> bool {
+        let no_aux_busses = AuxiliaryIOConfig {
+            num_busses: 0,
+            num_channels: 0,
+        };
+
+        config.num_input_channels == 0
+            && config.num_output_channels == 0
+            && config.aux_input_busses == no_aux_busses
+            && config.aux_output_busses == no_aux_busses
+    }
+
+    fn reset(&mut self) {
+        self.voices.clear();
+        self.stolen.fill(false);
+    }
+
+    fn process(
+        &mut self,
+        _buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        while let Some(in_event) = context.next_event() {
+            let limit = self.current_limit();
+            self.process_event(in_event, limit, |event| context.send_event(event));
+        }
+
+        ProcessStatus::Normal
+    }
+}
+
+impl ClapPlugin for RisPolyLimit {
+    const CLAP_ID: &'static str = "me.leiner.ris.poly_limit";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some(env!("CARGO_PKG_DESCRIPTION"));
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] =
+        &[ClapFeature::Custom("MIDI"), ClapFeature::Utility];
+}
+
+impl Vst3Plugin for RisPolyLimit {
+    const VST3_CLASS_ID: [u8; 16] = *b"risPolyLimit....";
+    const VST3_CATEGORIES: &'static str = "Fx|Tools";
+}
+
+nih_export_clap!(RisPolyLimit);
+nih_export_vst3!(RisPolyLimit);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rismidi::testing::{collect_events, note_off, note_on};
+
+    const TWO_VOICES: Limit = Limit {
+        voices: 2,
+        scope: Scope::PerChannel,
+        stealing: Stealing::Oldest,
+    };
+
+    #[test]
+    fn stolen_notes_are_released_only_once() {
+        let mut processor = RisPolyLimit::default();
+        processor.process_event(note_on(0, 0, 60, 0.5), TWO_VOICES, |_| ());
+        processor.process_event(note_on(0, 0, 62, 0.5), TWO_VOICES, |_| ());
+
+        let in_event = note_on(0, 0, 64, 0.5);
+        let out_events = collect_events(|send| processor.process_event(in_event, TWO_VOICES, send));
+        assert_eq!(out_events, vec![note_off(0, 0, 60), note_on(0, 0, 64, 0.5)]);
+
+        let in_event = note_off(0, 0, 60);
+        let out_events = collect_events(|send| processor.process_event(in_event, TWO_VOICES, send));
+        assert_eq!(out_events, vec![]);
+
+        let in_event = note_off(0, 0, 62);
+        let out_events = collect_events(|send| processor.process_event(in_event, TWO_VOICES, send));
+        assert_eq!(out_events, vec![note_off(0, 0, 62)]);
+
+        let in_event = note_on(0, 0, 65, 0.5);
+        let out_events = collect_events(|send| processor.process_event(in_event, TWO_VOICES, send));
+        assert_eq!(out_events, vec![note_on(0, 0, 65, 0.5)]);
+    }
+
+    #[test]
+    fn stealing_policies_choose_the_right_voice() {
+        for (stealing, victim) in [
+            (Stealing::Oldest, 64),
+            (Stealing::Newest, 67),
+            (Stealing::Lowest, 60),
+            (Stealing::Highest, 67),
+            (Stealing::Quietest, 62),
+        ] {
+            let mut processor = RisPolyLimit::default();
+            let limit = Limit {
+                voices: 4,
+                stealing,
+                ..TWO_VOICES
+            };
+            for (note, velocity) in [(64, 0.5), (60, 0.5), (62, 0.25), (67, 0.75)] {
+                processor.process_event(note_on(0, 0, note, velocity), limit, |_| ());
+            }
+
+            let in_event = note_on(0, 0, 65, 0.5);
+            let out_events = collect_events(|send| processor.process_event(in_event, limit, send));
+            assert_eq!(
+                out_events,
+                vec![note_off(0, 0, victim), note_on(0, 0, 65, 0.5)],
+                "{stealing:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn voices_are_counted_per_channel_or_globally() {
+        let mut processor = RisPolyLimit::default();
+        let one_voice = Limit {
+            voices: 1,
+            ..TWO_VOICES
+        };
+        processor.process_event(note_on(0, 0, 60, 0.5), one_voice, |_| ());
+
+        let in_event = note_on(0, 1, 60, 0.5);
+        let out_events = collect_events(|send| processor.process_event(in_event, one_voice, send));
+        assert_eq!(out_events, vec![note_on(0, 1, 60, 0.5)]);
+
+        let global = Limit {
+            scope: Scope::Global,
+            ..one_voice
+        };
+
+        let in_event = note_on(0, 2, 60, 0.5);
+        let out_events = collect_events(|send| processor.process_event(in_event, global, send));
+        assert_eq!(
+            out_events,
+            vec![
+                note_off(0, 0, 60),
+                note_off(0, 1, 60),
+                note_on(0, 2, 60, 0.5)
+            ]
+        );
+    }
+
+    #[test]
+    fn repeated_notes_keep_their_voice() {
+        let mut processor = RisPolyLimit::default();
+        processor.process_event(note_on(0, 0, 60, 0.5), TWO_VOICES, |_| ());
+        processor.process_event(note_on(0, 0, 62, 0.5), TWO_VOICES, |_| ());
+
+        let in_event = note_on(0, 0, 60, 0.75);
+        let out_events = collect_events(|send| processor.process_event(in_event, TWO_VOICES, send));
+        assert_eq!(out_events, vec![note_on(0, 0, 60, 0.75)]);
+
+        let in_event = note_on(0, 0, 64, 0.5);
+        let out_events = collect_events(|send| processor.process_event(in_event, TWO_VOICES, send));
+        assert_eq!(out_events, vec![note_off(0, 0, 62), note_on(0, 0, 64, 0.5)]);
+    }
+}
@@ -0,0 +1,367 @@
+use nih_plug::prelude::*;
+use rismidi::{MidiChannel, OptionalMidiChannelParam, Rng, NUM_MIDI_CHANNELS, NUM_MIDI_NOTES};
+use std::sync::Arc;
+
+const MIDI_CHANNEL_FROM_NIH_PLUG: &str = "MIDI channels from nih_plug must be in range 0..=15";
+
+struct RisProbability {
+    params: Arc<RisProbabilityParams>,
+    rng: Rng,
+
+    /// The seed `rng` has been started with, or [`None`] after a reset.
+    seed: Option<i32>,
+
+    /// The state of each note, by [`note_index`].
+    notes: Vec<NoteState>,
+}
+
+#[derive(Params)]
+struct RisProbabilityParams {
+    #[id = "probability"]
+    pub probability: FloatParam,
+
+    #[nested(id_prefix = "channel")]
+    pub channel: OptionalMidiChannelParam,
+
+    #[id = "lowest_note"]
+    pub lowest_note: IntParam,
+
+    #[id = "highest_note"]
+    pub highest_note: IntParam,
+
+    #[id = "seed"]
+    pub seed: IntParam,
+}
+
+/// Which notes may be dropped, and how likely they are to pass.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Dropout {
+    probability: f32,
+    channel: Option<MidiChannel>,
+    lowest_note: u8,
+    highest_note: u8,
+}
+
+/// What happened to the last note-on of a note.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum NoteState {
+    Off,
+    Passed,
+    Dropped,
+}
+
+impl Default for RisProbability {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(RisProbabilityParams::default()),
+            rng: Rng::new(0),
+            seed: None,
+            notes: vec![NoteState::Off; NUM_MIDI_CHANNELS as usize * NUM_MIDI_NOTES as usize],
+        }
+    }
+}
+
+/// Creates a parameter for a MIDI note number.
+fn note_param(name: &str, default: i32) -> IntParam {
+    IntParam::new(
+        name,
+        default,
+        IntRange::Linear {
+            min: 0,
+            max: NUM_MIDI_NOTES as i32 - 1,
+        },
+    )
+}
+
+impl Default for RisProbabilityParams {
+    fn default() -> Self {
+        Self {
+            probability: FloatParam::new(
+                "Probability",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit(" %")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            channel: OptionalMidiChannelParam::new("Channel", None)
+                .with_none_selected_description("All"),
+            lowest_note: note_param("Lowest Note", 0),
+            highest_note: note_param("Highest Note", NUM_MIDI_NOTES as i32 - 1),
+            seed: IntParam::new("Seed", 0, IntRange::Linear { min: 0, max: 999 }),
+        }
+    }
+}
+
+fn note_index(channel: MidiChannel, note: u8) -> usize {
+    channel.to_0_based() as usize * NUM_MIDI_NOTES as usize + note as usize
+}
+
+impl Dropout {
+    /// Returns `true` if notes with the given channel and note number may be dropped.
+    fn applies_to(&self, channel: MidiChannel, note: u8) -> bool {
+        (self.channel.is_none() || self.channel == Some(channel))
+            && (self.lowest_note..=self.highest_note).contains(&note)
+    }
+}
+
+impl RisProbability {
+    /// Gets the dropout settings from the current parameter values.
+    fn current_dropout(&self) -> Dropout {
+        Dropout {
+            probability: self.params.probability.value(),
+            channel: self.params.channel.value(),
+            lowest_note: self.params.lowest_note.value() as u8,
+            highest_note: self.params.highest_note.value() as u8,
+        }
+    }
+
+    /// Starts the random sequence over when the seed changes, so that trying out seeds does not
+    /// require stopping playback.
+    fn apply_seed(&mut self, seed: i32) {
+        if self.seed != Some(seed) {
+            self.seed = Some(seed);
+            self.rng = Rng::new(seed as u64);
+        }
+    }
+
+    /// Passes `in_event` to `send`, unless it belongs to a dropped note.
+    fn process_event(
+        &mut self,
+        in_event: NoteEvent,
+        dropout: Dropout,
+        mut send: impl FnMut(NoteEvent),
+    ) {
+        match in_event {
+            NoteEvent::NoteOn { channel, note, .. } => {
+                let channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+                let state = &mut self.notes[note_index(channel, note)];
+
+                let passes =
+                    !dropout.applies_to(channel, note) || self.rng.next_f32() < dropout.probability;
+                if passes {
+                    *state = NoteState::Passed;
+                    send(in_event);
+                } else if *state != NoteState::Passed {
+                    // If the note is already sounding, its next note-off must still be sent.
+                    *state = NoteState::Dropped;
+                }
+            }
+            NoteEvent::NoteOff { channel, note, .. } => {
+                let channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+                let state =
+                    std::mem::replace(&mut self.notes[note_index(channel, note)], NoteState::Off);
+
+                if state != NoteState::Dropped {
+                    send(in_event);
+                }
+            }
+            NoteEvent::PolyPressure { channel, note, .. } => {
+                let channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+                if self.notes[note_index(channel, note)] != NoteState::Dropped {
+                    send(in_event);
+                }
+            }
+            _ => send(in_event),
+        }
+    }
+}
+
+impl Plugin for RisProbability {
+    const NAME: &'static str = env!("CARGO_PKG_NAME");
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const VENDOR: &'static str = "Simon Leiner";
+    const EMAIL: &'static str = "rismidi@leiner.me";
+
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const DEFAULT_INPUT_CHANNELS: u32 = 0;
+    const DEFAULT_OUTPUT_CHANNELS: u32 = 0;
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::MidiCCs;
+
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn accepts_bus_config(&self, config: &BusConfig) -> bool {
+        let no_aux_busses = AuxiliaryIOConfig {
+            num_busses: 0,
+            num_channels: 0,
+        };
+
+        config.num_input_channels == 0
+            && config.num_output_channels == 0
+            && config.aux_input_busses == no_aux_busses
+            && config.aux_output_busses == no_aux_busses
+    }
+
+    fn reset(&mut self) {
+        self.seed = None;
+        self.apply_seed(self.params.seed.value());
+        self.notes.fill(NoteState::Off);
+    }
+
+    fn process(
+        &mut self,
+        _buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        self.apply_seed(self.params.seed.value());
+
+        while let Some(in_event) = context.next_event() {
+            let dropout = self.current_dropout();
+            self.process_event(in_event, dropout, |event| context.send_event(event));
+        }
+
+        ProcessStatus::Normal
+    }
+}
+
+impl ClapPlugin for RisProbability {
+    const CLAP_ID: &'static str = "me.leiner.ris.probability";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some(env!("CARGO_PKG_DESCRIPTION"));
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] =
+        &[ClapFeature::Custom("MIDI"), ClapFeature::Utility];
+}
+
+impl Vst3Plugin for RisProbability {
+    const VST3_CLASS_ID: [u8; 16] = *b"risProbability..";
+    const VST3_CATEGORIES: &'static str = "Fx|Tools";
+}
+
+nih_export_clap!(RisProbability);
+nih_export_vst3!(RisProbability);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rismidi::testing::{collect_events, note_off, note_on};
+
+    const HALF: Dropout = Dropout {
+        probability: 0.5,
+        channel: None,
+        lowest_note: 0,
+        highest_note: 127,
+    };
+
+    #[test]
+    fn note_offs_of_dropped_notes_are_dropped() {
+        let mut processor = RisProbability::default();
+
+        let mut num_passed = 0;
+        for _ in 0..1000 {
+            let passed =
+                collect_events(|send| processor.process_event(note_on(0, 0, 60, 0.5), HALF, send));
+            let released =
+                collect_events(|send| processor.process_event(note_off(0, 0, 60), HALF, send));
+            assert_eq!(passed.is_empty(), released.is_empty());
+            num_passed += passed.len();
+        }
+        assert!((400..600).contains(&num_passed));
+    }
+
+    #[test]
+    fn notes_outside_of_the_range_and_channel_pass() {
+        let mut processor = RisProbability::default();
+        let never = Dropout {
+            probability: 0.0,
+            channel: Some(MidiChannel::Channel1),
+            lowest_note: 48,
+            highest_note: 72,
+        };
+
+        let in_event = note_on(0, 0, 60, 0.5);
+        let out_events = collect_events(|send| processor.process_event(in_event, never, send));
+        assert_eq!(out_events, vec![]);
+
+        let in_event = note_on(0, 0, 47, 0.5);
+        let out_events = collect_events(|send| processor.process_event(in_event, never, send));
+        assert_eq!(out_events, vec![note_on(0, 0, 47, 0.5)]);
+
+        let in_event = note_on(0, 0, 73, 0.5);
+        let out_events = collect_events(|send| processor.process_event(in_event, never, send));
+        assert_eq!(out_events, vec![note_on(0, 0, 73, 0.5)]);
+
+        let other_channel = note_on(0, 1, 60, 0.5);
+        let out_events = collect_events(|send| processor.process_event(other_channel, never, send));
+        assert_eq!(out_events, vec![other_channel]);
+    }
+
+    #[test]
+    fn dropped_repetitions_keep_the_sounding_note_off() {
+        let mut processor = RisProbability::default();
+        let always = Dropout {
+            probability: 1.0,
+            ..HALF
+        };
+        let never = Dropout {
+            probability: 0.0,
+            ..HALF
+        };
+
+        processor.process_event(note_on(0, 0, 60, 0.5), always, |_| ());
+
+        let in_event = note_on(0, 0, 60, 0.5);
+        let out_events = collect_events(|send| processor.process_event(in_event, never, send));
+        assert_eq!(out_events, vec![]);
+
+        let in_event = note_off(0, 0, 60);
+        let out_events = collect_events(|send| processor.process_event(in_event, never, send));
+        assert_eq!(out_events, vec![note_off(0, 0, 60)]);
+    }
+
+    #[test]
+    fn renders_are_reproducible() {
+        fn render(processor: &mut RisProbability) -> Vec<bool> {
+            processor.reset();
+            (0..100)
+                .map(|_| {
+                    let passed = !collect_events(|send| {
+                        processor.process_event(note_on(0, 0, 60, 0.5), HALF, send)
+                    })
+                    .is_empty();
+                    processor.process_event(note_off(0, 0, 60), HALF, |_| ());
+                    passed
+                })
+                .collect()
+        }
+
+        let mut processor = RisProbability::default();
+        let first_render = render(&mut processor);
+        assert_eq!(render(&mut processor), first_render);
+    }
+
+    #[test]
+    fn changing_the_seed_restarts_the_random_numbers() {
+        fn render(processor: &mut RisProbability, seed: i32) -> Vec<bool> {
+            processor.apply_seed(seed);
+            (0..100)
+                .map(|_| {
+                    let passed = !collect_events(|send| {
+                        processor.process_event(note_on(0, 0, 60, 0.5), HALF, send)
+                    })
+                    .is_empty();
+                    processor.process_event(note_off(0, 0, 60), HALF, |_| ());
+                    passed
+                })
+                .collect()
+        }
+
+        let mut processor = RisProbability::default();
+        let first_render = render(&mut processor, 1);
+        assert_ne!(render(&mut processor, 2), first_render);
+        assert_eq!(render(&mut processor, 1), first_render);
+    }
+}
@@ -0,0 +1,387 @@
+use nih_plug::prelude::*;
+use rismidi::{
+    MidiChannel, Quantization, Scale, ScaleType, NUM_MIDI_CHANNELS, NUM_MIDI_NOTES,
+    NUM_PITCH_CLASSES,
+};
+use std::sync::Arc;
+
+const MIDI_CHANNEL_FROM_NIH_PLUG: &str = "MIDI channels from nih_plug must be in range 0..=15";
+
+const PITCH_CLASS_NAMES: [&str; NUM_PITCH_CLASSES as usize] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+const INTERVAL_NAMES: [&str; NUM_PITCH_CLASSES as usize] = [
+    "Root", "m2", "M2", "m3", "M3", "P4", "TT", "P5", "m6", "M6", "m7", "M7",
+];
+
+/// What has happened to a held note.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mapping {
+    NotHeld,
+    Dropped,
+    Playing(u8),
+}
+
+struct RisQuantizer {
+    params: Arc<RisQuantizerParams>,
+
+    /// The mapping of each incoming note, indexed by [`note_index`]. Note-offs are mapped like
+    /// their note-ons, even if the scale has changed in the meantime.
+    mappings: Vec<Mapping>,
+
+    /// For each outgoing note, the number of held notes mapped to it, indexed by [`note_index`].
+    /// A note-off is only sent once no held note is mapped to it anymore.
+    note_counts: Vec<u8>,
+}
+
+#[derive(Params)]
+struct RisQuantizerParams {
+    #[id = "root"]
+    pub root: IntParam,
+
+    #[id = "scale"]
+    pub scale: EnumParam<ScaleType>,
+
+    #[id = "quantization"]
+    pub quantization: EnumParam<Quantization>,
+
+    #[nested(array)]
+    pub custom_scale: [ScaleDegreeParams; NUM_PITCH_CLASSES as usize],
+}
+
+/// Whether a note is part of the custom scale.
+#[derive(Params)]
+struct ScaleDegreeParams {
+    #[id = "custom"]
+    pub enabled: BoolParam,
+}
+
+impl Default for RisQuantizer {
+    fn default() -> Self {
+        let num_notes = NUM_MIDI_NOTES as usize * NUM_MIDI_CHANNELS as usize;
+
+        Self {
+            params: Arc::new(RisQuantizerParams::default()),
+            mappings: vec![Mapping::NotHeld; num_notes],
+            note_counts: vec![0; num_notes],
+        }
+    }
+}
+
+impl Default for RisQuantizerParams {
+    fn default() -> Self {
+        let major = ScaleType::Major
+            .mask()
+            .expect("major is not a custom scale");
+
+        Self {
+            root: IntParam::new(
+                "Root",
+                0,
+                IntRange::Linear {
+                    min: 0,
+                    max: NUM_PITCH_CLASSES as i32 - 1,
+                },
+            )
+            .with_value_to_string(Arc::new(|value| {
+                PITCH_CLASS_NAMES[value as usize].to_string()
+            }))
+            .with_string_to_value(Arc::new(|string| {
+                PITCH_CLASS_NAMES
+                    .iter()
+                    .position(|name| name.eq_ignore_ascii_case(string.trim()))
+                    .map(|idx| idx as i32)
+            })),
+            scale: EnumParam::new("Scale", ScaleType::Major),
+            quantization: EnumParam::new("Quantization", Quantization::Nearest),
+            custom_scale: std::array::from_fn(|degree| ScaleDegreeParams {
+                enabled: BoolParam::new(
+                    format!("Custom {}", INTERVAL_NAMES[degree]),
+                    major & (1 << degree) != 0,
+                ),
+            }),
+        }
+    }
+}
+
+/// The position of a note in the per-note tables of [`RisQuantizer`].
+fn note_index(channel: MidiChannel, note: u8) -> usize {
+    channel.to_0_based() as usize * NUM_MIDI_NOTES as usize + note as usize
+}
+
+impl RisQuantizer {
+    /// Gets the scale from the current parameter values.
+    fn current_scale(&self) -> Scale {
+        let mask = self
+            .params
+            .scale
+            .value()
+            .mask()
+            .unwrap_or_else(|| self.custom_mask());
+
+        Scale::new(self.params.root.value() as u8, mask)
+            .expect("the root parameter is a pitch class")
+    }
+
+    /// Gets the notes of the custom scale from the current parameter values.
+    fn custom_mask(&self) -> u16 {
+        self.params
+            .custom_scale
+            .iter()
+            .enumerate()
+            .filter(|(_, degree)| degree.enabled.value())
+            .fold(0, |mask, (degree, _)| mask | 1 << degree)
+    }
+
+    fn transform_event(
+        &mut self,
+        in_event: NoteEvent,
+        scale: Scale,
+        quantization: Quantization,
+    ) -> Option<NoteEvent> {
+        match in_event {
+            NoteEvent::NoteOn {
+                timing,
+                voice_id,
+                channel,
+                note,
+                velocity,
+            } if note < NUM_MIDI_NOTES => {
+                let in_channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+
+                // A repeated note-on replaces the previous one, which will not get a note-off
+                // of its own.
+                self.release(in_channel, note);
+
+                let Some(out_note) = scale.quantize(note, quantization) else {
+                    self.mappings[note_index(in_channel, note)] = Mapping::Dropped;
+                    return None;
+                };
+                self.mappings[note_index(in_channel, note)] = Mapping::Playing(out_note);
+                self.note_counts[note_index(in_channel, out_note)] += 1;
+
+                Some(NoteEvent::NoteOn {
+                    timing,
+                    voice_id,
+                    channel,
+                    note: out_note,
+                    velocity,
+                })
+            }
+            NoteEvent::NoteOff {
+                timing,
+                voice_id,
+                channel,
+                note,
+                velocity,
+            } if note < NUM_MIDI_NOTES => {
+                let in_channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+
+                match self.release(in_channel, note) {
+                    // We have never seen this note's note-on, so we cannot do better than
+                    // passing the note-off on.
+                    Mapping::NotHeld => Some(in_event),
+                    Mapping::Dropped => None,
+                    Mapping::Playing(out_note) => {
+                        let still_playing = self.note_counts[note_index(in_channel, out_note)] > 0;
+                        (!still_playing).then_some(NoteEvent::NoteOff {
+                            timing,
+                            voice_id,
+                            channel,
+                            note: out_note,
+                            velocity,
+                        })
+                    }
+                }
+            }
+            NoteEvent::PolyPressure {
+                timing,
+                voice_id,
+                channel,
+                note,
+                pressure,
+            } if note < NUM_MIDI_NOTES => {
+                let in_channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+
+                match self.mappings[note_index(in_channel, note)] {
+                    Mapping::NotHeld => Some(in_event),
+                    Mapping::Dropped => None,
+                    Mapping::Playing(out_note) => Some(NoteEvent::PolyPressure {
+                        timing,
+                        voice_id,
+                        channel,
+                        note: out_note,
+                        pressure,
+                    }),
+                }
+            }
+            _ => Some(in_event),
+        }
+    }
+
+    /// Forgets the mapping of a released note and returns it.
+    fn release(&mut self, channel: MidiChannel, note: u8) -> Mapping {
+        let mapping = std::mem::replace(
+            &mut self.mappings[note_index(channel, note)],
+            Mapping::NotHeld,
+        );
+        if let Mapping::Playing(out_note) = mapping {
+            let count = &mut self.note_counts[note_index(channel, out_note)];
+            *count = count.saturating_sub(1);
+        }
+
+        mapping
+    }
+}
+
+impl Plugin for RisQuantizer {
+    const NAME: &'static str = env!("CARGO_PKG_NAME");
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const VENDOR: &'static str = "Simon Leiner";
+    const EMAIL: &'static str = "rismidi@leiner.me";
+
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const DEFAULT_INPUT_CHANNELS: u32 = 0;
+    const DEFAULT_OUTPUT_CHANNELS: u32 = 0;
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::MidiCCs;
+
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn accepts_bus_config(&self, config: &BusConfig) -> bool {
+        let no_aux_busses = AuxiliaryIOConfig {
+            num_busses: 0,
+            num_channels: 0,
+        };
+
+        config.num_input_channels == 0
+            && config.num_output_channels == 0
+            && config.aux_input_busses == no_aux_busses
+            && config.aux_output_busses == no_aux_busses
+    }
+
+    fn reset(&mut self) {
+        self.mappings.fill(Mapping::NotHeld);
+        self.note_counts.fill(0);
+    }
+
+    fn process(
+        &mut self,
+        _buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        while let Some(in_event) = context.next_event() {
+            let scale = self.current_scale();
+            let quantization = self.params.quantization.value();
+            if let Some(out_event) = self.transform_event(in_event, scale, quantization) {
+                context.send_event(out_event);
+            }
+        }
+
+        ProcessStatus::Normal
+    }
+}
+
+impl ClapPlugin for RisQuantizer {
+    const CLAP_ID: &'static str = "me.leiner.ris.quantizer";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some(env!("CARGO_PKG_DESCRIPTION"));
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] =
+        &[ClapFeature::Custom("MIDI"), ClapFeature::Utility];
+}
+
+impl Vst3Plugin for RisQuantizer {
+    const VST3_CLASS_ID: [u8; 16] = *b"risQuantizer....";
+    const VST3_CATEGORIES: &'static str = "Fx|Tools";
+}
+
+nih_export_clap!(RisQuantizer);
+nih_export_vst3!(RisQuantizer);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rismidi::testing::{note_off, note_on};
+
+    fn c_major() -> Scale {
+        Scale::new(0, ScaleType::Major.mask().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn notes_are_moved_into_the_scale() {
+        let mut processor = RisQuantizer::default();
+
+        assert_eq!(
+            processor.transform_event(note_on(0, 0, 61, 1.0), c_major(), Quantization::Up),
+            Some(note_on(0, 0, 62, 1.0))
+        );
+        assert_eq!(
+            processor.transform_event(note_on(0, 0, 61, 1.0), c_major(), Quantization::Down),
+            Some(note_on(0, 0, 60, 1.0))
+        );
+        assert_eq!(
+            processor.transform_event(note_on(0, 0, 64, 1.0), c_major(), Quantization::Drop),
+            Some(note_on(0, 0, 64, 1.0))
+        );
+    }
+
+    #[test]
+    fn note_offs_are_mapped_like_their_note_ons() {
+        let mut processor = RisQuantizer::default();
+        let a_minor = Scale::new(9, ScaleType::MinorPentatonic.mask().unwrap()).unwrap();
+
+        processor.transform_event(note_on(0, 0, 66, 1.0), c_major(), Quantization::Up);
+        processor.transform_event(note_on(0, 0, 68, 1.0), c_major(), Quantization::Drop);
+
+        assert_eq!(
+            processor.transform_event(note_off(0, 0, 66), a_minor, Quantization::Down),
+            Some(note_off(0, 0, 67))
+        );
+        assert_eq!(
+            processor.transform_event(note_off(0, 0, 68), a_minor, Quantization::Down),
+            None
+        );
+        assert_eq!(
+            processor.transform_event(note_off(0, 0, 68), a_minor, Quantization::Down),
+            Some(note_off(0, 0, 68))
+        );
+    }
+
+    #[test]
+    fn shared_notes_are_released_with_the_last_key() {
+        let mut processor = RisQuantizer::default();
+
+        processor.transform_event(note_on(0, 0, 60, 1.0), c_major(), Quantization::Nearest);
+        processor.transform_event(note_on(0, 0, 61, 1.0), c_major(), Quantization::Nearest);
+
+        assert_eq!(
+            processor.transform_event(note_off(0, 0, 61), c_major(), Quantization::Nearest),
+            None
+        );
+        assert_eq!(
+            processor.transform_event(note_off(0, 0, 60), c_major(), Quantization::Nearest),
+            Some(note_off(0, 0, 60))
+        );
+    }
+
+    #[test]
+    fn custom_scale_defaults_to_major() {
+        let processor = RisQuantizer::default();
+
+        assert_eq!(Some(processor.custom_mask()), ScaleType::Major.mask());
+    }
+}
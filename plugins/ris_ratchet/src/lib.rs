@@ -0,0 +1,530 @@
+use nih_plug::prelude::*;
+use rismidi::{
+    BeatClock, EventScheduler, HasTiming, HeldNotes, MidiChannel, NoteValue, MIN_VELOCITY,
+    NUM_MIDI_CHANNELS, NUM_MIDI_NOTES,
+};
+use std::sync::Arc;
+
+const MIDI_CHANNEL_FROM_NIH_PLUG: &str = "MIDI channels from nih_plug must be in range 0..=15";
+
+/// The maximum number of pending repeats. Every repeat takes up two events: one note-on and one
+/// note-off.
+const SCHEDULER_CAPACITY: usize = 4096;
+
+struct RisRatchet {
+    params: Arc<RisRatchetParams>,
+    sample_rate: f32,
+    clock: BeatClock,
+    scheduler: EventScheduler,
+
+    /// The notes whose keys are currently pressed.
+    held_notes: HeldNotes,
+
+    /// How far each held note has been repeated, by [`note_index`].
+    progress: Vec<Progress>,
+
+    /// The index of the last step played, so that it is not played twice if the host's position
+    /// jitters around a step boundary.
+    last_step: Option<i64>,
+
+    /// Whether the plugin has been reset, so that the pending note-offs need to be sent.
+    flush_pending: bool,
+}
+
+#[derive(Params)]
+struct RisRatchetParams {
+    #[id = "rate"]
+    pub rate: EnumParam<NoteValue>,
+
+    #[id = "mode"]
+    pub mode: EnumParam<RatchetMode>,
+
+    #[id = "ratchets"]
+    pub ratchets: IntParam,
+
+    #[id = "gate"]
+    pub gate: FloatParam,
+
+    #[id = "velocity_decay"]
+    pub velocity_decay: FloatParam,
+}
+
+/// How the number of repeats per step develops while a key is held.
+#[derive(Enum, Clone, Copy, Debug, PartialEq, Eq)]
+enum RatchetMode {
+    #[id = "steady"]
+    #[name = "Steady"]
+    Steady,
+
+    #[id = "accelerate"]
+    #[name = "Accelerate"]
+    Accelerate,
+
+    #[id = "decelerate"]
+    #[name = "Decelerate"]
+    Decelerate,
+}
+
+/// The parameter values that determine how a step is played.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Ratchet {
+    mode: RatchetMode,
+    ratchets: u32,
+    gate: f32,
+    velocity_decay: f32,
+}
+
+/// How far a held note has been repeated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Progress {
+    /// The number of steps played since the key was pressed.
+    steps: u32,
+
+    /// The number of repeats played since the key was pressed.
+    repeats: u32,
+}
+
+impl Default for RisRatchet {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(RisRatchetParams::default()),
+            sample_rate: 44100.0,
+            clock: BeatClock::new(),
+            scheduler: EventScheduler::with_capacity(SCHEDULER_CAPACITY),
+            held_notes: HeldNotes::new(),
+            progress: vec![
+                Progress::default();
+                NUM_MIDI_CHANNELS as usize * NUM_MIDI_NOTES as usize
+            ],
+            last_step: None,
+            flush_pending: false,
+        }
+    }
+}
+
+impl Default for RisRatchetParams {
+    fn default() -> Self {
+        Self {
+            rate: EnumParam::new("Rate", NoteValue::Eighth),
+            mode: EnumParam::new("Mode", RatchetMode::Steady),
+            ratchets: IntParam::new("Ratchets", 1, IntRange::Linear { min: 1, max: 8 }),
+            gate: FloatParam::new(
+                "Gate",
+                0.5,
+                FloatRange::Linear {
+                    min: 0.05,
+                    max: 1.0,
+                },
+            )
+            .with_unit(" %")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            velocity_decay: FloatParam::new(
+                "Velocity Decay",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit(" %")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+        }
+    }
+}
+
+fn note_index(channel: MidiChannel, note: u8) -> usize {
+    channel.to_0_based() as usize * NUM_MIDI_NOTES as usize + note as usize
+}
+
+impl Ratchet {
+    /// The number of repeats within the given step, counted from the first step after the key was
+    /// pressed.
+    fn repeats_in_step(&self, step: u32) -> u32 {
+        match self.mode {
+            RatchetMode::Steady => self.ratchets,
+            RatchetMode::Accelerate => (step + 1).min(self.ratchets),
+            RatchetMode::Decelerate => self.ratchets.saturating_sub(step).max(1),
+        }
+    }
+}
+
+impl RisRatchet {
+    /// Gets the ratchet settings from the current parameter values.
+    fn current_ratchet(&self) -> Ratchet {
+        Ratchet {
+            mode: self.params.mode.value(),
+            ratchets: self.params.ratchets.value() as u32,
+            gate: self.params.gate.value(),
+            velocity_decay: self.params.velocity_decay.value(),
+        }
+    }
+
+    /// Drops all pending repeats of a note.
+    fn cancel_repeats(&mut self, channel: MidiChannel, note: u8) {
+        let channel = channel.to_0_based();
+        self.scheduler.retain(|event| match *event {
+            NoteEvent::NoteOn {
+                channel: event_channel,
+                note: event_note,
+                ..
+            }
+            | NoteEvent::NoteOff {
+                channel: event_channel,
+                note: event_note,
+                ..
+            } => event_channel != channel || event_note != note,
+            _ => true,
+        });
+    }
+
+    /// Keeps track of the held notes and passes `in_event` to `send`.
+    fn process_event(&mut self, in_event: NoteEvent, mut send: impl FnMut(NoteEvent)) {
+        match in_event {
+            NoteEvent::NoteOn {
+                timing,
+                voice_id: _,
+                channel,
+                note,
+                velocity,
+            } => {
+                let channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+
+                // A key which is pressed again starts over.
+                if !self.held_notes.note_on(channel, note, velocity) {
+                    self.cancel_repeats(channel, note);
+                    send(NoteEvent::NoteOff {
+                        timing,
+                        voice_id: None,
+                        channel: channel.to_0_based(),
+                        note,
+                        velocity: 0.0,
+                    });
+                }
+                self.progress[note_index(channel, note)] = Progress::default();
+            }
+            NoteEvent::NoteOff { channel, note, .. } => {
+                let channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+
+                // The note-off ends the repeat which is currently playing, if any.
+                self.held_notes.note_off(channel, note);
+                self.cancel_repeats(channel, note);
+            }
+            _ => (),
+        }
+
+        send(in_event);
+    }
+
+    /// Schedules the repeats of all held notes for the step starting at `timing`.
+    fn play_step(&mut self, timing: u32, step_length: f64, ratchet: Ratchet) {
+        for held in self.held_notes.iter() {
+            let progress = &mut self.progress[note_index(held.channel, held.note)];
+            let channel = held.channel.to_0_based();
+            let note_off = NoteEvent::NoteOff {
+                timing: 0,
+                voice_id: None,
+                channel,
+                note: held.note,
+                velocity: 0.0,
+            };
+
+            let repeats = ratchet.repeats_in_step(progress.steps);
+            if self.scheduler.remaining_capacity() < 2 * repeats as usize + 1 {
+                continue;
+            }
+
+            // The note that was played with the key lasts until the first step.
+            if progress.steps == 0 {
+                let scheduled = self.scheduler.schedule(timing as u64, note_off);
+                nih_debug_assert!(scheduled.is_ok());
+            }
+            progress.steps += 1;
+
+            let interval = step_length / repeats as f64;
+            let max_length = (interval as u64).saturating_sub(1).max(1);
+            let length = ((interval * ratchet.gate as f64) as u64).clamp(1, max_length);
+            for repeat in 0..repeats {
+                progress.repeats += 1;
+                let velocity =
+                    held.velocity * (1.0 - ratchet.velocity_decay).powi(progress.repeats as i32);
+                if velocity < MIN_VELOCITY {
+                    break;
+                }
+
+                let due = timing as u64 + (repeat as f64 * interval) as u64;
+                let note_on = NoteEvent::NoteOn {
+                    timing: 0,
+                    voice_id: None,
+                    channel,
+                    note: held.note,
+                    velocity,
+                };
+                let scheduled = self.scheduler.schedule(due, note_on);
+                nih_debug_assert!(scheduled.is_ok());
+                let scheduled = self.scheduler.schedule(due + length, note_off);
+                nih_debug_assert!(scheduled.is_ok());
+            }
+        }
+    }
+
+    /// Sends all repeats due before `in_event`, then processes `in_event`.
+    fn forward_event(&mut self, in_event: NoteEvent, context: &mut impl ProcessContext<Self>) {
+        while let Some(repeat) = self.scheduler.pop_before(in_event.timing()) {
+            context.send_event(repeat);
+        }
+
+        self.process_event(in_event, |event| context.send_event(event));
+    }
+}
+
+impl Plugin for RisRatchet {
+    const NAME: &'static str = env!("CARGO_PKG_NAME");
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const VENDOR: &'static str = "Simon Leiner";
+    const EMAIL: &'static str = "rismidi@leiner.me";
+
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const DEFAULT_INPUT_CHANNELS: u32 = 0;
+    const DEFAULT_OUTPUT_CHANNELS: u32 = 0;
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::MidiCCs;
+
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn accepts_bus_config(&self, config: &BusConfig) -> bool {
+        let no_aux_busses = AuxiliaryIOConfig {
+            num_busses: 0,
+            num_channels: 0,
+        };
+
+        config.num_input_channels == 0
+            && config.num_output_channels == 0
+            && config.aux_input_busses == no_aux_busses
+            && config.aux_output_busses == no_aux_busses
+    }
+
+    fn initialize(
+        &mut self,
+        _bus_config: &BusConfig,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        self.sample_rate = buffer_config.sample_rate;
+
+        true
+    }
+
+    fn reset(&mut self) {
+        self.clock = BeatClock::new();
+        // We cannot send events from here, so the pending note-offs are sent with the next buffer.
+        self.scheduler
+            .retain(|event| matches!(event, NoteEvent::NoteOff { .. }));
+        self.flush_pending = true;
+        self.held_notes.clear();
+        self.last_step = None;
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        if std::mem::take(&mut self.flush_pending) {
+            while let Some(note_off) = self.scheduler.pop_before(u32::MAX) {
+                context.send_event(note_off.with_timing(0));
+            }
+        }
+
+        let num_samples = buffer.samples() as u32;
+        let transport = context.transport();
+        self.clock.update(
+            transport.playing,
+            transport.pos_beats(),
+            transport.tempo,
+            self.sample_rate,
+        );
+
+        let ratchet = self.current_ratchet();
+        let rate = self.params.rate.value();
+        let step_length = self.clock.beats_to_samples(rate.beats());
+
+        let mut next_event = context.next_event();
+        for step in self.clock.steps(rate, num_samples) {
+            // Notes played at the same time as the step are first repeated in the next step.
+            while let Some(in_event) = next_event.filter(|event| event.timing() < step.timing) {
+                self.forward_event(in_event, context);
+                next_event = context.next_event();
+            }
+
+            if self.last_step == Some(step.index) {
+                continue;
+            }
+            self.last_step = Some(step.index);
+
+            self.play_step(step.timing, step_length, ratchet);
+        }
+
+        while let Some(in_event) = next_event {
+            self.forward_event(in_event, context);
+            next_event = context.next_event();
+        }
+
+        while let Some(repeat) = self.scheduler.pop_before(num_samples) {
+            context.send_event(repeat);
+        }
+        self.scheduler.advance(num_samples);
+        self.clock.advance(num_samples);
+
+        // Pending repeats must be sent, even if there is no more input.
+        ProcessStatus::KeepAlive
+    }
+}
+
+impl ClapPlugin for RisRatchet {
+    const CLAP_ID: &'static str = "me.leiner.ris.ratchet";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some(env!("CARGO_PKG_DESCRIPTION"));
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] =
+        &[ClapFeature::Custom("MIDI"), ClapFeature::Utility];
+}
+
+impl Vst3Plugin for RisRatchet {
+    const VST3_CLASS_ID: [u8; 16] = *b"risRatchet......";
+    const VST3_CATEGORIES: &'static str = "Fx|Tools";
+}
+
+nih_export_clap!(RisRatchet);
+nih_export_vst3!(RisRatchet);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rismidi::testing::{collect_events, note_off, note_on};
+
+    const STEADY: Ratchet = Ratchet {
+        mode: RatchetMode::Steady,
+        ratchets: 2,
+        gate: 0.5,
+        velocity_decay: 0.5,
+    };
+
+    fn play_step(processor: &mut RisRatchet, ratchet: Ratchet) -> Vec<NoteEvent> {
+        processor.play_step(0, 100.0, ratchet);
+        std::iter::from_fn(|| processor.scheduler.pop_before(u32::MAX)).collect()
+    }
+
+    #[test]
+    fn held_notes_are_repeated_with_decay() {
+        let mut processor = RisRatchet::default();
+
+        let in_event = note_on(0, 0, 60, 1.0);
+        let out_events = collect_events(|send| processor.process_event(in_event, send));
+        assert_eq!(out_events, vec![note_on(0, 0, 60, 1.0)]);
+
+        assert_eq!(
+            play_step(&mut processor, STEADY),
+            vec![
+                note_off(0, 0, 60),
+                note_on(0, 0, 60, 0.5),
+                note_off(25, 0, 60),
+                note_on(50, 0, 60, 0.25),
+                note_off(75, 0, 60)
+            ]
+        );
+        assert_eq!(
+            play_step(&mut processor, STEADY),
+            vec![
+                note_on(0, 0, 60, 0.125),
+                note_off(25, 0, 60),
+                note_on(50, 0, 60, 0.0625),
+                note_off(75, 0, 60)
+            ]
+        );
+    }
+
+    #[test]
+    fn accelerating_and_decelerating_change_the_repeats_per_step() {
+        let count_note_ons = |ratchet: Ratchet| {
+            let mut processor = RisRatchet::default();
+            processor.process_event(note_on(0, 0, 60, 1.0), |_| ());
+            (0..4)
+                .map(|_| {
+                    play_step(&mut processor, ratchet)
+                        .into_iter()
+                        .filter(|event| matches!(event, NoteEvent::NoteOn { .. }))
+                        .count()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let accelerate = Ratchet {
+            mode: RatchetMode::Accelerate,
+            ratchets: 3,
+            velocity_decay: 0.0,
+            ..STEADY
+        };
+        assert_eq!(count_note_ons(accelerate), vec![1, 2, 3, 3]);
+
+        let decelerate = Ratchet {
+            mode: RatchetMode::Decelerate,
+            ..accelerate
+        };
+        assert_eq!(count_note_ons(decelerate), vec![3, 2, 1, 1]);
+    }
+
+    #[test]
+    fn released_notes_stop_repeating() {
+        let mut processor = RisRatchet::default();
+        processor.process_event(note_on(0, 0, 60, 1.0), |_| ());
+        processor.play_step(0, 100.0, STEADY);
+
+        let in_event = note_off(10, 0, 60);
+        let out_events = collect_events(|send| processor.process_event(in_event, send));
+        assert_eq!(out_events, vec![note_off(10, 0, 60)]);
+        assert!(processor.scheduler.is_empty());
+        assert_eq!(play_step(&mut processor, STEADY), vec![]);
+    }
+
+    #[test]
+    fn silent_repeats_are_not_played() {
+        let mut processor = RisRatchet::default();
+        let fade_out = Ratchet {
+            ratchets: 8,
+            velocity_decay: 0.9,
+            ..STEADY
+        };
+        processor.process_event(note_on(0, 0, 60, 1.0), |_| ());
+
+        // 0.1 and 0.01 are audible, 0.001 is not.
+        let note_ons = play_step(&mut processor, fade_out)
+            .into_iter()
+            .filter(|event| matches!(event, NoteEvent::NoteOn { .. }))
+            .count();
+        assert_eq!(note_ons, 2);
+    }
+
+    #[test]
+    fn only_note_offs_are_kept_on_reset() {
+        let mut processor = RisRatchet::default();
+        processor.process_event(note_on(0, 0, 60, 1.0), |_| ());
+        processor.play_step(0, 100.0, STEADY);
+
+        processor.reset();
+        assert!(processor.flush_pending);
+        assert_eq!(
+            std::iter::from_fn(|| processor.scheduler.pop_before(u32::MAX)).collect::<Vec<_>>(),
+            vec![note_off(0, 0, 60), note_off(25, 0, 60), note_off(75, 0, 60)]
+        );
+    }
+}
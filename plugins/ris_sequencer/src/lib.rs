@@ -0,0 +1,751 @@
+mod pattern;
+
+use nih_plug::prelude::*;
+use pattern::{Pattern, Step, MAX_STEPS};
+use rismidi::{
+    BeatClock, EventScheduler, HasTiming, HeldNotes, MidiChannel, NoteValue, Rng, NUM_MIDI_NOTES,
+};
+use std::sync::{Arc, RwLock};
+
+const MIDI_CHANNEL_FROM_NIH_PLUG: &str = "MIDI channels from nih_plug must be in range 0..=15";
+
+/// Only the note-off of the current step is scheduled, so there is usually only one of them
+/// pending at a time.
+const SCHEDULER_CAPACITY: usize = 16;
+
+/// The seed for the step probabilities, so that renders are reproducible.
+const RANDOM_SEED: u64 = 0;
+
+/// The sustain pedal, which enters rests while recording.
+const CC_SUSTAIN: u8 = 64;
+
+/// The sustain pedal counts as pressed from this (normalized) value on, i.e. from a MIDI value of
+/// 64.
+const PEDAL_THRESHOLD: f32 = 0.5;
+
+struct RisSequencer {
+    params: Arc<RisSequencerParams>,
+    sample_rate: f32,
+    clock: BeatClock,
+    scheduler: EventScheduler,
+
+    /// A copy of the persisted pattern, so that it can be read without waiting for a lock.
+    pattern: Pattern,
+
+    /// Whether `pattern` contains recorded steps which have not been persisted yet.
+    pattern_changed: bool,
+
+    /// The last key pressed while not recording. The pattern is transposed by its distance to the
+    /// root note.
+    key: Option<u8>,
+
+    /// Whether steps were being recorded during the last event.
+    recording: bool,
+
+    /// The step which is recorded next.
+    record_cursor: usize,
+
+    /// The keys which were passed through while recording, so that their note-offs are passed
+    /// through as well.
+    recorded_keys: HeldNotes,
+
+    /// Whether the sustain pedal is currently pressed.
+    pedal_pressed: bool,
+
+    /// The index of the last step played, so that it is not played twice if the host's position
+    /// jitters around a step boundary.
+    last_step: Option<i64>,
+
+    rng: Rng,
+
+    /// Whether the plugin has been reset, so that the pending note-off needs to be sent.
+    flush_pending: bool,
+
+    /// The values of the edit parameters during the last buffer, or [`None`] after a reset. Only
+    /// changes of these values are applied, so that they do not overwrite recorded steps or
+    /// patterns loaded with the plugin's state.
+    last_edit: Option<StepEdit>,
+}
+
+#[derive(Params)]
+struct RisSequencerParams {
+    #[id = "rate"]
+    pub rate: EnumParam<NoteValue>,
+
+    #[id = "length"]
+    pub length: IntParam,
+
+    #[id = "channel"]
+    pub channel: EnumParam<MidiChannel>,
+
+    #[id = "root"]
+    pub root: IntParam,
+
+    #[id = "record"]
+    pub record: BoolParam,
+
+    #[id = "step_gate"]
+    pub step_gate: FloatParam,
+
+    #[id = "step_probability"]
+    pub step_probability: FloatParam,
+
+    #[id = "edit_step"]
+    pub edit_step: IntParam,
+
+    #[id = "edit_rest"]
+    pub edit_rest: BoolParam,
+
+    #[id = "edit_note"]
+    pub edit_note: IntParam,
+
+    #[id = "edit_velocity"]
+    pub edit_velocity: FloatParam,
+
+    #[id = "edit_gate"]
+    pub edit_gate: FloatParam,
+
+    #[id = "edit_probability"]
+    pub edit_probability: FloatParam,
+
+    #[persist = "pattern"]
+    pub pattern: RwLock<Pattern>,
+}
+
+/// The parameter values that determine how steps are played and recorded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Settings {
+    length: usize,
+    channel: MidiChannel,
+    root: u8,
+    record: bool,
+    step_gate: f32,
+    step_probability: f32,
+}
+
+/// The values of the edit parameters, together with the position of the edited step.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct StepEdit {
+    index: usize,
+    rest: bool,
+    step: Step,
+}
+
+impl Default for RisSequencer {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(RisSequencerParams::default()),
+            sample_rate: 44100.0,
+            clock: BeatClock::new(),
+            scheduler: EventScheduler::with_capacity(SCHEDULER_CAPACITY),
+            pattern: Pattern::default(),
+            pattern_changed: false,
+            key: None,
+            recording: false,
+            record_cursor: 0,
+            recorded_keys: HeldNotes::new(),
+            pedal_pressed: false,
+            last_step: None,
+            rng: Rng::new(RANDOM_SEED),
+            flush_pending: false,
+            last_edit: None,
+        }
+    }
+}
+
+/// Creates a parameter for a value relative to the maximum, shown in percent.
+fn percentage_param(name: &str, default: f32) -> FloatParam {
+    FloatParam::new(name, default, FloatRange::Linear { min: 0.0, max: 1.0 })
+        .with_unit(" %")
+        .with_value_to_string(formatters::v2s_f32_percentage(0))
+        .with_string_to_value(formatters::s2v_f32_percentage())
+}
+
+impl Default for RisSequencerParams {
+    fn default() -> Self {
+        Self {
+            rate: EnumParam::new("Rate", NoteValue::Sixteenth),
+            length: IntParam::new(
+                "Length",
+                16,
+                IntRange::Linear {
+                    min: 16,
+                    max: MAX_STEPS as i32,
+                },
+            )
+            .with_unit(" steps"),
+            channel: EnumParam::new("Channel", MidiChannel::Channel1),
+            root: IntParam::new(
+                "Root Note",
+                60,
+                IntRange::Linear {
+                    min: 0,
+                    max: NUM_MIDI_NOTES as i32 - 1,
+                },
+            ),
+            record: BoolParam::new("Record", false),
+            step_gate: percentage_param("Step Gate", 0.5),
+            step_probability: percentage_param("Step Probability", 1.0),
+            edit_step: IntParam::new(
+                "Edit Step",
+                1,
+                IntRange::Linear {
+                    min: 1,
+                    max: MAX_STEPS as i32,
+                },
+            ),
+            edit_rest: BoolParam::new("Edit Rest", false),
+            // The same values as the steps of the default pattern.
+            edit_note: IntParam::new(
+                "Edit Note",
+                60,
+                IntRange::Linear {
+                    min: 0,
+                    max: NUM_MIDI_NOTES as i32 - 1,
+                },
+            ),
+            edit_velocity: percentage_param("Edit Velocity", 100.0 / 127.0),
+            edit_gate: percentage_param("Edit Gate", 0.5),
+            edit_probability: percentage_param("Edit Probability", 1.0),
+            pattern: RwLock::new(Pattern::default()),
+        }
+    }
+}
+
+fn note_off(timing: u32, channel: MidiChannel, note: u8) -> NoteEvent {
+    NoteEvent::NoteOff {
+        timing,
+        voice_id: None,
+        channel: channel.to_0_based(),
+        note,
+        velocity: 0.0,
+    }
+}
+
+impl RisSequencer {
+    /// Gets the settings from the current parameter values.
+    fn current_settings(&self) -> Settings {
+        Settings {
+            length: self.params.length.value() as usize,
+            channel: self.params.channel.value(),
+            root: self.params.root.value() as u8,
+            record: self.params.record.value(),
+            step_gate: self.params.step_gate.value(),
+            step_probability: self.params.step_probability.value(),
+        }
+    }
+
+    /// Gets the edited step from the current parameter values.
+    fn current_edit(&self) -> StepEdit {
+        StepEdit {
+            index: self.params.edit_step.value() as usize - 1,
+            rest: self.params.edit_rest.value(),
+            step: Step {
+                note: self.params.edit_note.value() as u8,
+                velocity: self.params.edit_velocity.value(),
+                gate: self.params.edit_gate.value(),
+                probability: self.params.edit_probability.value(),
+            },
+        }
+    }
+
+    /// Writes the edit parameters which have changed since the last buffer into the edited step.
+    /// The parameters do not show the values of the selected step, so only the changed values are
+    /// written, and selecting another step changes nothing.
+    fn apply_edit(&mut self, edit: StepEdit) {
+        let Some(last_edit) = self.last_edit.replace(edit) else {
+            return;
+        };
+        if last_edit.index != edit.index || last_edit == edit {
+            return;
+        }
+        let Some(slot) = self.pattern.steps.get_mut(edit.index) else {
+            return;
+        };
+
+        if edit.rest != last_edit.rest {
+            *slot = (!edit.rest).then(|| edit.step.clamped());
+        } else if !edit.rest {
+            let (new, last) = (edit.step, last_edit.step);
+            let step = slot.get_or_insert(new);
+            if new.note != last.note {
+                step.note = new.note;
+            }
+            if new.velocity != last.velocity {
+                step.velocity = new.velocity;
+            }
+            if new.gate != last.gate {
+                step.gate = new.gate;
+            }
+            if new.probability != last.probability {
+                step.probability = new.probability;
+            }
+            *step = step.clamped();
+        }
+        self.pattern_changed = true;
+    }
+
+    /// Exchanges the pattern with the persisted state: recorded steps are stored, and patterns
+    /// loaded by the host are picked up. If the state is locked right now, we try again next
+    /// time.
+    fn sync_pattern(&mut self) {
+        if self.pattern_changed {
+            if let Ok(mut pattern) = self.params.pattern.try_write() {
+                self.pattern.copy_to(&mut pattern);
+                self.pattern_changed = false;
+            }
+        } else if let Ok(pattern) = self.params.pattern.try_read() {
+            pattern.copy_to(&mut self.pattern);
+        }
+    }
+
+    /// Writes `step` at the record cursor and moves on to the next step.
+    fn record(&mut self, step: Option<Step>, settings: Settings) {
+        self.pattern.steps[self.record_cursor] = step;
+        self.record_cursor = (self.record_cursor + 1) % settings.length.clamp(1, MAX_STEPS);
+        self.pattern_changed = true;
+    }
+
+    /// Records or transposes with the notes in `in_event`. All other events are passed to
+    /// `send`.
+    fn process_event(
+        &mut self,
+        in_event: NoteEvent,
+        settings: Settings,
+        mut send: impl FnMut(NoteEvent),
+    ) {
+        // Every recording starts at the first step.
+        if settings.record && !self.recording {
+            self.record_cursor = 0;
+        }
+        self.recording = settings.record;
+
+        match in_event {
+            NoteEvent::NoteOn {
+                timing: _,
+                voice_id: _,
+                channel,
+                note,
+                velocity,
+            } => {
+                if !settings.record {
+                    self.key = Some(note);
+                    return;
+                }
+
+                // While recording, notes are passed through, so that they can be heard.
+                let channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+                self.recorded_keys.note_on(channel, note, velocity);
+                let step = Step {
+                    note,
+                    velocity,
+                    gate: settings.step_gate,
+                    probability: settings.step_probability,
+                };
+                self.record(Some(step), settings);
+                send(in_event);
+            }
+            NoteEvent::NoteOff { channel, note, .. } => {
+                let channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+                if self.recorded_keys.note_off(channel, note).is_some() {
+                    send(in_event);
+                }
+            }
+            NoteEvent::MidiCC { cc, value, .. } if settings.record && cc == CC_SUSTAIN => {
+                let pressed = value >= PEDAL_THRESHOLD;
+                if pressed && !self.pedal_pressed {
+                    self.record(None, settings);
+                }
+                self.pedal_pressed = pressed;
+            }
+            _ => send(in_event),
+        }
+    }
+
+    /// Plays the step with the given index at `timing`. Its note-off is scheduled according to
+    /// the step's gate.
+    fn play_step(
+        &mut self,
+        timing: u32,
+        index: i64,
+        step_length: f64,
+        settings: Settings,
+        mut send: impl FnMut(NoteEvent),
+    ) {
+        if settings.record || self.scheduler.remaining_capacity() == 0 {
+            return;
+        }
+        let Some(step) = self.pattern.step(index, settings.length) else {
+            return;
+        };
+        if self.rng.next_f32() >= step.probability {
+            return;
+        }
+
+        let transpose = self.key.map_or(0, |key| key as i32 - settings.root as i32);
+        let note = step.note as i32 + transpose;
+        if !(0..NUM_MIDI_NOTES as i32).contains(&note) {
+            return;
+        }
+
+        let max_length = (step_length as u64).saturating_sub(1).max(1);
+        let length = ((step_length * step.gate as f64) as u64).clamp(1, max_length);
+        let note_off = note_off(timing, settings.channel, note as u8);
+        let scheduled = self.scheduler.schedule(timing as u64 + length, note_off);
+        nih_debug_assert!(scheduled.is_ok());
+
+        send(NoteEvent::NoteOn {
+            timing,
+            voice_id: None,
+            channel: settings.channel.to_0_based(),
+            note: note as u8,
+            velocity: step.velocity,
+        });
+    }
+
+    /// Sends all note-offs due before `in_event`, then processes `in_event`.
+    fn forward_event(&mut self, in_event: NoteEvent, context: &mut impl ProcessContext<Self>) {
+        while let Some(note_off) = self.scheduler.pop_before(in_event.timing()) {
+            context.send_event(note_off);
+        }
+
+        let settings = self.current_settings();
+        self.process_event(in_event, settings, |event| context.send_event(event));
+    }
+}
+
+impl Plugin for RisSequencer {
+    const NAME: &'static str = env!("CARGO_PKG_NAME");
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const VENDOR: &'static str = "Simon Leiner";
+    const EMAIL: &'static str = "rismidi@leiner.me";
+
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const DEFAULT_INPUT_CHANNELS: u32 = 0;
+    const DEFAULT_OUTPUT_CHANNELS: u32 = 0;
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::MidiCCs;
+
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn accepts_bus_config(&self, config: &BusConfig) -> bool {
+        let no_aux_busses = AuxiliaryIOConfig {
+            num_busses: 0,
+            num_channels: 0,
+        };
+
+        config.num_input_channels == 0
+            && config.num_output_channels == 0
+            && config.aux_input_busses == no_aux_busses
+            && config.aux_output_busses == no_aux_busses
+    }
+
+    fn initialize(
+        &mut self,
+        _bus_config: &BusConfig,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        self.sample_rate = buffer_config.sample_rate;
+
+        true
+    }
+
+    fn reset(&mut self) {
+        // We cannot send events from here, so the pending note-off is sent with the next buffer.
+        self.clock = BeatClock::new();
+        self.recorded_keys.clear();
+        self.pedal_pressed = false;
+        self.last_step = None;
+        self.rng = Rng::new(RANDOM_SEED);
+        self.flush_pending = true;
+        // This is also called after the plugin's state has been loaded, which must not be
+        // overwritten by steps recorded or edited before.
+        self.last_edit = None;
+        self.pattern_changed = false;
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        if std::mem::take(&mut self.flush_pending) {
+            while let Some(note_off) = self.scheduler.pop_before(u32::MAX) {
+                context.send_event(note_off.with_timing(0));
+            }
+        }
+        let edit = self.current_edit();
+        self.apply_edit(edit);
+        self.sync_pattern();
+
+        let num_samples = buffer.samples() as u32;
+        let transport = context.transport();
+        let playing = transport.playing;
+        self.clock.update(
+            playing,
+            transport.pos_beats(),
+            transport.tempo,
+            self.sample_rate,
+        );
+
+        let rate = self.params.rate.value();
+        let step_length = self.clock.beats_to_samples(rate.beats());
+
+        let mut next_event = context.next_event();
+        for step in self.clock.steps(rate, num_samples) {
+            // Keys pressed at the same time as the step already transpose it.
+            while let Some(in_event) = next_event.filter(|event| event.timing() <= step.timing) {
+                self.forward_event(in_event, context);
+                next_event = context.next_event();
+            }
+
+            // The pattern follows the host, so it only plays while the host does.
+            if !playing || self.last_step == Some(step.index) {
+                continue;
+            }
+            self.last_step = Some(step.index);
+
+            while let Some(note_off) = self.scheduler.pop_before(step.timing + 1) {
+                context.send_event(note_off);
+            }
+            let settings = self.current_settings();
+            self.play_step(step.timing, step.index, step_length, settings, |event| {
+                context.send_event(event)
+            });
+        }
+
+        while let Some(in_event) = next_event {
+            self.forward_event(in_event, context);
+            next_event = context.next_event();
+        }
+
+        while let Some(note_off) = self.scheduler.pop_before(num_samples) {
+            context.send_event(note_off);
+        }
+        self.scheduler.advance(num_samples);
+        self.clock.advance(num_samples);
+
+        // The pattern keeps on playing, even if there is no input.
+        ProcessStatus::KeepAlive
+    }
+}
+
+impl ClapPlugin for RisSequencer {
+    const CLAP_ID: &'static str = "me.leiner.ris.sequencer";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some(env!("CARGO_PKG_DESCRIPTION"));
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] =
+        &[ClapFeature::Custom("MIDI"), ClapFeature::Utility];
+}
+
+impl Vst3Plugin for RisSequencer {
+    const VST3_CLASS_ID: [u8; 16] = *b"risSequencer....";
+    const VST3_CATEGORIES: &'static str = "Fx|Tools";
+}
+
+nih_export_clap!(RisSequencer);
+nih_export_vst3!(RisSequencer);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rismidi::testing::{cc, collect_events, note_on};
+    use std::collections::BTreeMap;
+
+    const SETTINGS: Settings = Settings {
+        length: 16,
+        channel: MidiChannel::Channel2,
+        root: 60,
+        record: false,
+        step_gate: 0.25,
+        step_probability: 1.0,
+    };
+
+    const RECORD: Settings = Settings {
+        record: true,
+        ..SETTINGS
+    };
+
+    /// Plays a step of 100 samples and returns its note-on and note-off.
+    fn play_step(processor: &mut RisSequencer, index: i64) -> Vec<NoteEvent> {
+        let mut out_events = Vec::new();
+        processor.play_step(0, index, 100.0, SETTINGS, |event| out_events.push(event));
+        out_events.extend(std::iter::from_fn(|| {
+            processor.scheduler.pop_before(u32::MAX)
+        }));
+        out_events
+    }
+
+    #[test]
+    fn steps_are_transposed_by_the_last_key() {
+        let mut processor = RisSequencer::default();
+        let step_on = |note: u8| note_on(0, 1, note, 100.0 / 127.0);
+
+        assert_eq!(
+            play_step(&mut processor, 0),
+            vec![step_on(60), note_off(50, MidiChannel::Channel2, 60)]
+        );
+
+        let in_event = note_on(0, 0, 67, 1.0);
+        let out_events = collect_events(|send| processor.process_event(in_event, SETTINGS, send));
+        assert_eq!(out_events, vec![]);
+        assert_eq!(
+            play_step(&mut processor, 1),
+            vec![step_on(67), note_off(50, MidiChannel::Channel2, 67)]
+        );
+    }
+
+    #[test]
+    fn steps_and_rests_are_recorded() {
+        let mut processor = RisSequencer::default();
+
+        let in_event = note_on(0, 0, 48, 1.0);
+        let out_events = collect_events(|send| processor.process_event(in_event, RECORD, send));
+        assert_eq!(out_events, vec![note_on(0, 0, 48, 1.0)]);
+        processor.process_event(cc(0, 0, CC_SUSTAIN, 1.0), RECORD, |_| ());
+        processor.process_event(cc(0, 0, CC_SUSTAIN, 0.0), RECORD, |_| ());
+        processor.process_event(note_on(0, 0, 50, 0.5), RECORD, |_| ());
+
+        let recorded = |note, velocity| {
+            Some(Step {
+                note,
+                velocity,
+                gate: 0.25,
+                probability: 1.0,
+            })
+        };
+        assert_eq!(
+            processor.pattern.steps[..3],
+            [recorded(48, 1.0), None, recorded(50, 0.5)]
+        );
+        assert!(processor.pattern_changed);
+
+        // Recording again starts over at the first step.
+        processor.process_event(cc(0, 0, CC_SUSTAIN, 0.0), SETTINGS, |_| ());
+        processor.process_event(note_on(0, 0, 52, 0.5), RECORD, |_| ());
+        assert_eq!(processor.pattern.steps[0], recorded(52, 0.5));
+        assert_eq!(play_step(&mut processor, 1), vec![]);
+    }
+
+    #[test]
+    fn steps_wrap_around_after_the_length() {
+        let mut processor = RisSequencer::default();
+        processor.pattern.steps[1] = None;
+
+        assert_eq!(play_step(&mut processor, 17), vec![]);
+        assert_eq!(play_step(&mut processor, -15), vec![]);
+        assert_eq!(play_step(&mut processor, 18).len(), 2);
+    }
+
+    #[test]
+    fn steps_are_edited_when_their_values_change() {
+        let mut processor = RisSequencer::default();
+        let default_step = Pattern::default().steps[0].unwrap();
+        let mut edit = StepEdit {
+            index: 3,
+            rest: false,
+            step: Step {
+                note: 48,
+                velocity: 0.5,
+                gate: 1.0,
+                probability: 0.75,
+            },
+        };
+
+        // The values which are set up when the plugin starts are not applied.
+        processor.apply_edit(edit);
+        assert_eq!(processor.pattern, Pattern::default());
+
+        edit.rest = true;
+        processor.apply_edit(edit);
+        assert_eq!(processor.pattern.steps[3], None);
+        assert!(processor.pattern_changed);
+
+        // Selecting another step does not change it.
+        edit.index = 4;
+        processor.apply_edit(edit);
+        assert_eq!(processor.pattern.steps[4], Some(default_step));
+
+        // Only the changed value is written, the other values of the step are kept.
+        edit.rest = false;
+        edit.index = 5;
+        processor.apply_edit(edit);
+        edit.step.note = 50;
+        processor.apply_edit(edit);
+        assert_eq!(
+            processor.pattern.steps[5],
+            Some(Step {
+                note: 50,
+                ..default_step
+            })
+        );
+    }
+
+    #[test]
+    fn pattern_is_persisted() {
+        let mut processor = RisSequencer::default();
+        processor.pattern.steps[3] = None;
+        processor.pattern_changed = true;
+        processor.sync_pattern();
+
+        let serialized = processor.params.serialize_fields();
+        let restored = RisSequencerParams::default();
+        restored.deserialize_fields(&serialized);
+
+        assert_eq!(*restored.pattern.read().unwrap(), processor.pattern);
+    }
+
+    #[test]
+    fn loaded_patterns_are_not_overwritten_by_unsaved_steps() {
+        let mut processor = RisSequencer::default();
+        processor.pattern.steps[3] = None;
+        processor.pattern_changed = true;
+
+        // The host loads the default pattern and resets the plugin.
+        processor.reset();
+        processor.sync_pattern();
+        assert_eq!(processor.pattern, Pattern::default());
+    }
+
+    #[test]
+    fn invalid_patterns_are_corrected_on_load() {
+        let mut processor = RisSequencer::default();
+        processor.pattern.steps[MAX_STEPS - 1] = None;
+
+        // A pattern with too few steps and values out of range.
+        let serialized = BTreeMap::from([(
+            "pattern".to_string(),
+            r#"{"steps":[{"note":200,"velocity":2.0,"gate":-1.0,"probability":1.5}]}"#.to_string(),
+        )]);
+        processor.params.deserialize_fields(&serialized);
+        processor.sync_pattern();
+
+        assert_eq!(
+            processor.pattern.steps[0],
+            Some(Step {
+                note: 127,
+                velocity: 1.0,
+                gate: 0.0,
+                probability: 1.0,
+            })
+        );
+        assert_eq!(processor.pattern.steps[1..], [None; MAX_STEPS - 1]);
+        assert_eq!(
+            processor.params.pattern.read().unwrap().steps.len(),
+            MAX_STEPS
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use rismidi::{MIN_VELOCITY, NUM_MIDI_NOTES};
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// The maximum number of steps in a pattern.
+pub const MAX_STEPS: usize = 64;
+
+/// The note played by a single step of the pattern.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Step {
+    /// The MIDI note, before transposition.
+    pub note: u8,
+
+    pub velocity: f32,
+
+    /// Length of the note, relative to the length of a step.
+    pub gate: f32,
+
+    /// How likely the step is to be played.
+    pub probability: f32,
+}
+
+impl Step {
+    /// Limits all values to their valid ranges.
+    pub fn clamped(self) -> Self {
+        Self {
+            note: self.note.min(NUM_MIDI_NOTES - 1),
+            velocity: self.velocity.clamp(MIN_VELOCITY, 1.0),
+            gate: self.gate.clamp(0.0, 1.0),
+            probability: self.probability.clamp(0.0, 1.0),
+        }
+    }
+}
+
+/// The steps played by the sequencer. This is what gets persisted with the plugin's state.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Pattern {
+    /// All [`MAX_STEPS`] steps, even if the pattern is shorter. Rests are [`None`].
+    #[serde(deserialize_with = "deserialize_steps")]
+    pub steps: Vec<Option<Step>>,
+}
+
+impl Pattern {
+    /// Gets the step at `index`, wrapping around after `length` steps.
+    pub fn step(&self, index: i64, length: usize) -> Option<Step> {
+        let length = length.clamp(1, self.steps.len());
+        self.steps[index.rem_euclid(length as i64) as usize]
+    }
+
+    /// Copies all steps into `target` without allocating. Steps which `self` does not have
+    /// become rests in `target`.
+    pub fn copy_to(&self, target: &mut Pattern) {
+        for (index, target_step) in target.steps.iter_mut().enumerate() {
+            *target_step = self.steps.get(index).copied().flatten();
+        }
+    }
+}
+
+/// Reads exactly [`MAX_STEPS`] steps with valid values, even if the state was saved by another
+/// version or edited by hand.
+fn deserialize_steps<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Option<Step>>, D::Error> {
+    let mut steps = Vec::<Option<Step>>::deserialize(deserializer)?;
+    steps.resize(MAX_STEPS, None);
+    for step in steps.iter_mut().flatten() {
+        *step = step.clamped();
+    }
+
+    Ok(steps)
+}
+
+impl Default for Pattern {
+    fn default() -> Self {
+        // Every step plays the middle C, so that the pattern can be transposed right away.
+        let step = Step {
+            note: 60,
+            velocity: 100.0 / 127.0,
+            gate: 0.5,
+            probability: 1.0,
+        };
+
+        Self {
+            steps: vec![Some(step); MAX_STEPS],
+        }
+    }
+}
@@ -0,0 +1,484 @@
+use nih_plug::prelude::*;
+use rismidi::{
+    is_parameter_number_cc, params::OptionalCcNumberParam, MidiChannel, OptionalMidiChannelParam,
+    MAX_14_BIT, MAX_7_BIT, NUM_MIDI_CCS, NUM_MIDI_CHANNELS,
+};
+use std::sync::Arc;
+
+const MIDI_CHANNEL_FROM_NIH_PLUG: &str = "MIDI channels from nih_plug must be in range 0..=15";
+
+/// The CCs which select a bank together with the next program change.
+const CC_BANK_SELECT_MSB: u8 = 0;
+const CC_BANK_SELECT_LSB: u8 = 32;
+
+/// CC 120–127 are channel mode messages such as "All Notes Off".
+const FIRST_CHANNEL_MODE_CC: u8 = 120;
+
+/// Every channel has one controller per CC, followed by pitch bend.
+const NUM_CONTROLLERS: usize = NUM_MIDI_CCS as usize + 1;
+
+/// The controller number used for pitch bend, see [`controller_index`].
+const PITCH_BEND: usize = NUM_MIDI_CCS as usize;
+
+struct RisSmooth {
+    params: Arc<RisSmoothParams>,
+    sample_rate: f32,
+
+    /// The state of each controller, by [`controller_index`].
+    controllers: Vec<Controller>,
+
+    /// The indices of the controllers which have not reached their target yet.
+    active: Vec<usize>,
+
+    /// The number of samples from the start of the current buffer until the next update is due.
+    next_update: u32,
+}
+
+#[derive(Params)]
+struct RisSmoothParams {
+    #[id = "time"]
+    pub time: FloatParam,
+
+    #[id = "update_rate"]
+    pub update_rate: IntParam,
+
+    #[nested(id_prefix = "channel")]
+    pub channel: OptionalMidiChannelParam,
+
+    #[nested(id_prefix = "cc")]
+    pub cc: OptionalCcNumberParam,
+
+    #[id = "pitch_bend"]
+    pub pitch_bend: BoolParam,
+}
+
+/// Which controllers are smoothed, and how fast they may change.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Smoothing {
+    /// The maximum change per update, relative to the full range.
+    max_step: f32,
+    channel: Option<MidiChannel>,
+    cc: Option<u8>,
+    pitch_bend: bool,
+}
+
+/// The state of a single CC or pitch bend on one channel.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Controller {
+    /// The value which was sent last, or [`None`] if no value has been received yet.
+    current: Option<f32>,
+
+    /// The value which was received last.
+    target: f32,
+
+    /// The raw MIDI value which was sent last, so that it is not sent twice.
+    sent: u16,
+
+    /// Whether the controller is in [`RisSmooth::active`].
+    active: bool,
+}
+
+impl Default for RisSmooth {
+    fn default() -> Self {
+        let num_controllers = NUM_MIDI_CHANNELS as usize * NUM_CONTROLLERS;
+
+        Self {
+            params: Arc::new(RisSmoothParams::default()),
+            sample_rate: 44100.0,
+            controllers: vec![Controller::default(); num_controllers],
+            active: Vec::with_capacity(num_controllers),
+            next_update: 0,
+        }
+    }
+}
+
+impl Default for RisSmoothParams {
+    fn default() -> Self {
+        Self {
+            time: FloatParam::new(
+                "Time",
+                100.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 2000.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_step_size(1.0),
+            update_rate: IntParam::new("Update Rate", 200, IntRange::Linear { min: 10, max: 1000 })
+                .with_unit(" /s"),
+            channel: OptionalMidiChannelParam::new("Channel", None)
+                .with_none_selected_description("All"),
+            cc: OptionalCcNumberParam::new("CC", None).with_none_selected_description("All"),
+            pitch_bend: BoolParam::new("Pitch Bend", true),
+        }
+    }
+}
+
+/// The position of a controller in [`RisSmooth::controllers`].
+fn controller_index(channel: MidiChannel, controller: usize) -> usize {
+    channel.to_0_based() as usize * NUM_CONTROLLERS + controller
+}
+
+/// Creates the event which sends `raw` to the controller at `index`.
+fn controller_event(timing: u32, index: usize, raw: u16) -> NoteEvent {
+    let channel = (index / NUM_CONTROLLERS) as u8;
+    match index % NUM_CONTROLLERS {
+        PITCH_BEND => NoteEvent::MidiPitchBend {
+            timing,
+            channel,
+            value: raw as f32 / MAX_14_BIT as f32,
+        },
+        cc => NoteEvent::MidiCC {
+            timing,
+            channel,
+            cc: cc as u8,
+            value: raw as f32 / MAX_7_BIT as f32,
+        },
+    }
+}
+
+impl Controller {
+    /// Converts `value` into a raw MIDI value for the controller at `index`.
+    fn raw(index: usize, value: f32) -> u16 {
+        let max = match index % NUM_CONTROLLERS {
+            PITCH_BEND => MAX_14_BIT,
+            _ => MAX_7_BIT,
+        };
+
+        (value * max as f32).round().clamp(0.0, max as f32) as u16
+    }
+}
+
+impl RisSmooth {
+    /// Gets the smoothing settings from the current parameter values.
+    fn current_smoothing(&self, update_interval: u32) -> Smoothing {
+        let time_samples = self.params.time.value() / 1000.0 * self.sample_rate;
+
+        Smoothing {
+            max_step: if time_samples > 0.0 {
+                update_interval as f32 / time_samples
+            } else {
+                f32::INFINITY
+            },
+            channel: self.params.channel.value(),
+            cc: self.params.cc.value(),
+            pitch_bend: self.params.pitch_bend.value(),
+        }
+    }
+
+    /// Sets the target of a controller, or sends the value right away if there is nothing to
+    /// smooth it from.
+    fn set_target(
+        &mut self,
+        timing: u32,
+        index: usize,
+        value: f32,
+        smoothing: Smoothing,
+        send: &mut impl FnMut(NoteEvent),
+    ) {
+        let controller = &mut self.controllers[index];
+        controller.target = value;
+
+        if controller.current.is_none() || smoothing.max_step >= 1.0 {
+            controller.current = Some(value);
+            controller.sent = Controller::raw(index, value);
+            send(controller_event(timing, index, controller.sent));
+        } else if !controller.active {
+            controller.active = true;
+            self.active.push(index);
+        }
+    }
+
+    /// Smooths the controllers in `in_event`. All other events are passed to `send`.
+    fn process_event(
+        &mut self,
+        in_event: NoteEvent,
+        smoothing: Smoothing,
+        mut send: impl FnMut(NoteEvent),
+    ) {
+        let channel_matches = |channel: u8| {
+            let channel =
+                MidiChannel::try_from_0_based(channel.into()).expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+            (smoothing.channel.is_none() || smoothing.channel == Some(channel)).then_some(channel)
+        };
+
+        match in_event {
+            // Intermediate values would select the wrong bank or parameter.
+            NoteEvent::MidiCC { cc, .. }
+                if cc == CC_BANK_SELECT_MSB
+                    || cc == CC_BANK_SELECT_LSB
+                    || is_parameter_number_cc(cc)
+                    || cc >= FIRST_CHANNEL_MODE_CC =>
+            {
+                send(in_event)
+            }
+            NoteEvent::MidiCC {
+                timing,
+                channel,
+                cc,
+                value,
+            } if smoothing.cc.is_none() || smoothing.cc == Some(cc) => {
+                match channel_matches(channel) {
+                    Some(channel) => {
+                        let index = controller_index(channel, cc as usize);
+                        self.set_target(timing, index, value, smoothing, &mut send);
+                    }
+                    None => send(in_event),
+                }
+            }
+            NoteEvent::MidiPitchBend {
+                timing,
+                channel,
+                value,
+            } if smoothing.pitch_bend => match channel_matches(channel) {
+                Some(channel) => {
+                    let index = controller_index(channel, PITCH_BEND);
+                    self.set_target(timing, index, value, smoothing, &mut send);
+                }
+                None => send(in_event),
+            },
+            _ => send(in_event),
+        }
+    }
+
+    /// Moves all active controllers towards their targets and sends their new values.
+    fn update(&mut self, timing: u32, smoothing: Smoothing, mut send: impl FnMut(NoteEvent)) {
+        let controllers = &mut self.controllers;
+        self.active.retain(|&index| {
+            let controller = &mut controllers[index];
+            let current = controller.current.unwrap_or(controller.target);
+            let next = if (controller.target - current).abs() <= smoothing.max_step {
+                controller.target
+            } else {
+                current + smoothing.max_step.copysign(controller.target - current)
+            };
+            controller.current = Some(next);
+
+            let raw = Controller::raw(index, next);
+            if raw != controller.sent {
+                controller.sent = raw;
+                send(controller_event(timing, index, raw));
+            }
+
+            controller.active = next != controller.target;
+            controller.active
+        });
+    }
+}
+
+impl Plugin for RisSmooth {
+    const NAME: &'static str = env!("CARGO_PKG_NAME");
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const VENDOR: &'static str = "Simon Leiner";
+    const EMAIL: &'static str = "rismidi@leiner.me";
+
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const DEFAULT_INPUT_CHANNELS: u32 = 0;
+    const DEFAULT_OUTPUT_CHANNELS: u32 = 0;
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::MidiCCs;
+
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn accepts_bus_config(&self, config: &BusConfig) -> bool {
+        let no_aux_busses = AuxiliaryIOConfig {
+            num_busses: 0,
+            num_channels: 0,
+        };
+
+        config.num_input_channels == 0
+            && config.num_output_channels == 0
+            && config.aux_input_busses == no_aux_busses
+            && config.aux_output_busses == no_aux_busses
+    }
+
+    fn initialize(
+        &mut self,
+        _bus_config: &BusConfig,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        self.sample_rate = buffer_config.sample_rate;
+
+        true
+    }
+
+    fn reset(&mut self) {
+        self.controllers.fill(Controller::default());
+        self.active.clear();
+        self.next_update = 0;
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        let num_samples = buffer.samples() as u32;
+        let update_interval =
+            ((self.sample_rate / self.params.update_rate.value() as f32).round() as u32).max(1);
+
+        let mut next_event = context.next_event();
+        while self.next_update < num_samples {
+            let timing = self.next_update;
+            while let Some(in_event) = next_event.filter(|event| event.timing() < timing) {
+                let smoothing = self.current_smoothing(update_interval);
+                self.process_event(in_event, smoothing, |event| context.send_event(event));
+                next_event = context.next_event();
+            }
+
+            let smoothing = self.current_smoothing(update_interval);
+            self.update(timing, smoothing, |event| context.send_event(event));
+            self.next_update += update_interval;
+        }
+        self.next_update -= num_samples;
+
+        while let Some(in_event) = next_event {
+            let smoothing = self.current_smoothing(update_interval);
+            self.process_event(in_event, smoothing, |event| context.send_event(event));
+            next_event = context.next_event();
+        }
+
+        // Smoothed values must be sent, even if there is no more input.
+        ProcessStatus::KeepAlive
+    }
+}
+
+impl ClapPlugin for RisSmooth {
+    const CLAP_ID: &'static str = "me.leiner.ris.smooth";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some(env!("CARGO_PKG_DESCRIPTION"));
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] =
+        &[ClapFeature::Custom("MIDI"), ClapFeature::Utility];
+}
+
+impl Vst3Plugin for RisSmooth {
+    const VST3_CLASS_ID: [u8; 16] = *b"risSmooth.......";
+    const VST3_CATEGORIES: &'static str = "Fx|Tools";
+}
+
+nih_export_clap!(RisSmooth);
+nih_export_vst3!(RisSmooth);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rismidi::testing::collect_events;
+
+    const SMOOTHING: Smoothing = Smoothing {
+        max_step: 0.25,
+        channel: None,
+        cc: None,
+        pitch_bend: true,
+    };
+
+    fn cc(timing: u32, cc: u8, raw: u16) -> NoteEvent {
+        NoteEvent::MidiCC {
+            timing,
+            channel: 0,
+            cc,
+            value: raw as f32 / MAX_7_BIT as f32,
+        }
+    }
+
+    fn update(processor: &mut RisSmooth, timing: u32) -> Vec<NoteEvent> {
+        collect_events(|send| processor.update(timing, SMOOTHING, send))
+    }
+
+    #[test]
+    fn values_move_towards_their_target() {
+        let mut processor = RisSmooth::default();
+
+        // The first value has nothing to be smoothed from.
+        let in_event = cc(0, 74, 0);
+        let out_events = collect_events(|send| processor.process_event(in_event, SMOOTHING, send));
+        assert_eq!(out_events, vec![cc(0, 74, 0)]);
+
+        let in_event = cc(5, 74, 127);
+        let out_events = collect_events(|send| processor.process_event(in_event, SMOOTHING, send));
+        assert_eq!(out_events, vec![]);
+
+        assert_eq!(update(&mut processor, 10), vec![cc(10, 74, 32)]);
+        assert_eq!(update(&mut processor, 20), vec![cc(20, 74, 64)]);
+        assert_eq!(update(&mut processor, 30), vec![cc(30, 74, 95)]);
+        assert_eq!(update(&mut processor, 40), vec![cc(40, 74, 127)]);
+        assert_eq!(update(&mut processor, 50), vec![]);
+    }
+
+    #[test]
+    fn controllers_are_smoothed_separately() {
+        let mut processor = RisSmooth::default();
+        let bend = |timing: u32, value: f32| NoteEvent::MidiPitchBend {
+            timing,
+            channel: 0,
+            value,
+        };
+        processor.process_event(cc(0, 1, 127), SMOOTHING, |_| ());
+        processor.process_event(bend(0, 0.5), SMOOTHING, |_| ());
+
+        processor.process_event(cc(0, 1, 95), SMOOTHING, |_| ());
+        processor.process_event(bend(0, 1.0), SMOOTHING, |_| ());
+        assert_eq!(
+            update(&mut processor, 10),
+            vec![cc(10, 1, 95), bend(10, 12287.0 / MAX_14_BIT as f32)]
+        );
+    }
+
+    #[test]
+    fn other_events_are_passed_through() {
+        let mut processor = RisSmooth::default();
+        let only_cc_1 = Smoothing {
+            cc: Some(1),
+            channel: Some(MidiChannel::Channel1),
+            pitch_bend: false,
+            ..SMOOTHING
+        };
+        processor.process_event(cc(0, 1, 0), only_cc_1, |_| ());
+
+        let in_event = cc(0, 1, 127);
+        let out_events = collect_events(|send| processor.process_event(in_event, only_cc_1, send));
+        assert_eq!(out_events, vec![]);
+
+        let in_event = cc(0, 2, 127);
+        let out_events = collect_events(|send| processor.process_event(in_event, only_cc_1, send));
+        assert_eq!(out_events, vec![cc(0, 2, 127)]);
+
+        let other_channel = NoteEvent::MidiCC {
+            timing: 0,
+            channel: 1,
+            cc: 1,
+            value: 1.0,
+        };
+
+        let out_events =
+            collect_events(|send| processor.process_event(other_channel, only_cc_1, send));
+        assert_eq!(out_events, vec![other_channel]);
+    }
+
+    #[test]
+    fn bank_selects_parameter_numbers_and_channel_mode_messages_are_not_smoothed() {
+        let mut processor = RisSmooth::default();
+
+        for number in [0, 32, 101, 100, 6, 38, 96, 120, 123] {
+            processor.process_event(cc(0, number, 0), SMOOTHING, |_| ());
+
+            let in_event = cc(5, number, 127);
+            let out_events =
+                collect_events(|send| processor.process_event(in_event, SMOOTHING, send));
+            assert_eq!(out_events, vec![in_event]);
+        }
+        assert_eq!(update(&mut processor, 10), vec![]);
+    }
+}
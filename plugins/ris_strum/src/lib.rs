@@ -0,0 +1,555 @@
+use nih_plug::prelude::*;
+use rismidi::{
+    EventScheduler, HasTiming, HeldNote, MidiChannel, MIN_VELOCITY, NUM_MIDI_CHANNELS,
+    NUM_MIDI_NOTES,
+};
+use std::sync::Arc;
+
+const MIDI_CHANNEL_FROM_NIH_PLUG: &str = "MIDI channels from nih_plug must be in range 0..=15";
+
+/// The maximum number of pending note-ons and note-offs.
+const SCHEDULER_CAPACITY: usize = 4096;
+
+/// The maximum number of notes in a single chord. Further notes are passed through unchanged.
+const MAX_CHORD_NOTES: usize = 32;
+
+struct RisStrum {
+    params: Arc<RisStrumParams>,
+    sample_rate: f32,
+    scheduler: EventScheduler,
+
+    /// The number of samples since the last reset, up to the start of the current buffer.
+    position: u64,
+
+    /// The notes of the chord that is currently being collected.
+    chord: Vec<ChordNote>,
+
+    /// The time at which the current chord is complete, in samples since the last reset.
+    chord_end: u64,
+
+    /// Whether the next chord is strummed downwards when alternating.
+    alternate_down: bool,
+
+    /// How much the note-on of each strummed note has been delayed, by [`note_index`]. Its
+    /// note-off is delayed by the same time, so that the note keeps its length.
+    note_delay: Vec<Option<u64>>,
+
+    /// Whether the plugin has been reset, so that the pending note-offs need to be sent.
+    flush_pending: bool,
+}
+
+#[derive(Params)]
+struct RisStrumParams {
+    #[id = "time"]
+    pub time: FloatParam,
+
+    #[id = "window"]
+    pub window: FloatParam,
+
+    #[id = "direction"]
+    pub direction: EnumParam<Direction>,
+
+    #[id = "velocity_ramp"]
+    pub velocity_ramp: FloatParam,
+}
+
+/// The order in which the notes of a chord are played.
+#[derive(Enum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Direction {
+    /// From the lowest to the highest note.
+    #[id = "up"]
+    #[name = "Up"]
+    Up,
+
+    /// From the highest to the lowest note.
+    #[id = "down"]
+    #[name = "Down"]
+    Down,
+
+    /// Up and down in turns, starting upwards.
+    #[id = "alternating"]
+    #[name = "Alternating"]
+    Alternating,
+}
+
+/// How chords are strummed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Strum {
+    /// Time between the first and the last note of a chord, in samples.
+    time: u64,
+
+    /// Time after the first note-on in which further note-ons belong to the same chord, in
+    /// samples.
+    window: u64,
+
+    direction: Direction,
+
+    /// Relative velocity change from the first to the last note of a chord.
+    velocity_ramp: f32,
+}
+
+/// A note of the chord that is currently being collected.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ChordNote {
+    note: HeldNote,
+
+    /// The time of the note-on, in samples since the last reset.
+    on_time: u64,
+
+    /// The time of the note-off, if it has already arrived while the chord was being collected.
+    off_time: Option<u64>,
+}
+
+impl Default for RisStrum {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(RisStrumParams::default()),
+            sample_rate: 44100.0,
+            scheduler: EventScheduler::with_capacity(SCHEDULER_CAPACITY),
+            position: 0,
+            chord: Vec::with_capacity(MAX_CHORD_NOTES),
+            chord_end: 0,
+            alternate_down: false,
+            note_delay: vec![None; NUM_MIDI_CHANNELS as usize * NUM_MIDI_NOTES as usize],
+            flush_pending: false,
+        }
+    }
+}
+
+impl Default for RisStrumParams {
+    fn default() -> Self {
+        Self {
+            time: FloatParam::new(
+                "Strum Time",
+                40.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 1000.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_step_size(1.0),
+            window: FloatParam::new(
+                "Chord Window",
+                20.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 100.0,
+                },
+            )
+            .with_unit(" ms")
+            .with_step_size(1.0),
+            direction: EnumParam::new("Direction", Direction::Up),
+            velocity_ramp: FloatParam::new(
+                "Velocity Ramp",
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_unit(" %")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+        }
+    }
+}
+
+fn note_index(channel: MidiChannel, note: u8) -> usize {
+    channel.to_0_based() as usize * NUM_MIDI_NOTES as usize + note as usize
+}
+
+impl RisStrum {
+    /// Gets the strum settings from the current parameter values.
+    fn current_strum(&self) -> Strum {
+        let samples = |ms: f32| (ms as f64 / 1000.0 * self.sample_rate as f64).round() as u64;
+
+        Strum {
+            time: samples(self.params.time.value()),
+            window: samples(self.params.window.value()),
+            direction: self.params.direction.value(),
+            velocity_ramp: self.params.velocity_ramp.value(),
+        }
+    }
+
+    /// Strums the current chord if it is complete before `end`, in samples since the last reset.
+    fn end_chord_before(&mut self, end: u64, strum: Strum) {
+        if !self.chord.is_empty() && self.chord_end < end {
+            self.strum_chord(strum);
+        }
+    }
+
+    /// Schedules the notes of the current chord, starting at the end of its window.
+    fn strum_chord(&mut self, strum: Strum) {
+        let down = match strum.direction {
+            Direction::Up => false,
+            Direction::Down => true,
+            Direction::Alternating => {
+                self.alternate_down = !self.alternate_down;
+                !self.alternate_down
+            }
+        };
+
+        self.chord
+            .sort_unstable_by_key(|chord_note| chord_note.note.note);
+        if down {
+            self.chord.reverse();
+        }
+
+        let last = self.chord.len().saturating_sub(1).max(1) as u64;
+        for (i, chord_note) in self.chord.drain(..).enumerate() {
+            let HeldNote {
+                channel,
+                note,
+                velocity,
+            } = chord_note.note;
+            let progress = i as u64;
+            let due = self.chord_end + strum.time * progress / last;
+            let velocity = velocity * (1.0 + strum.velocity_ramp * progress as f32 / last as f32);
+
+            let note_on = NoteEvent::NoteOn {
+                timing: 0,
+                voice_id: None,
+                channel: channel.to_0_based(),
+                note,
+                velocity: velocity.clamp(MIN_VELOCITY, 1.0),
+            };
+            let scheduled = self.scheduler.schedule(due - self.position, note_on);
+            nih_debug_assert!(scheduled.is_ok());
+
+            let delay = due - chord_note.on_time;
+            if let Some(off_time) = chord_note.off_time {
+                let note_off = NoteEvent::NoteOff {
+                    timing: 0,
+                    voice_id: None,
+                    channel: channel.to_0_based(),
+                    note,
+                    velocity: 0.0,
+                };
+                let scheduled = self
+                    .scheduler
+                    .schedule(off_time + delay - self.position, note_off);
+                nih_debug_assert!(scheduled.is_ok());
+            } else {
+                self.note_delay[note_index(channel, note)] = Some(delay);
+            }
+        }
+    }
+
+    /// Collects or delays the notes in `in_event`. All other events are passed to `send`.
+    fn process_event(
+        &mut self,
+        in_event: NoteEvent,
+        strum: Strum,
+        mut send: impl FnMut(NoteEvent),
+    ) {
+        let time = self.position + in_event.timing() as u64;
+
+        match in_event {
+            NoteEvent::NoteOn {
+                timing: _,
+                voice_id: _,
+                channel,
+                note,
+                velocity,
+            } => {
+                let channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+                if self.chord.iter().any(|chord_note| {
+                    chord_note.note.channel == channel && chord_note.note.note == note
+                }) {
+                    return;
+                }
+                if self.chord.len() == MAX_CHORD_NOTES {
+                    send(in_event);
+                    return;
+                }
+
+                if self.chord.is_empty() {
+                    self.chord_end = time + strum.window;
+                }
+                self.chord.push(ChordNote {
+                    note: HeldNote {
+                        channel,
+                        note,
+                        velocity,
+                    },
+                    on_time: time,
+                    off_time: None,
+                });
+            }
+            NoteEvent::NoteOff { channel, note, .. } => {
+                let channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+                if let Some(chord_note) = self.chord.iter_mut().find(|chord_note| {
+                    chord_note.note.channel == channel && chord_note.note.note == note
+                }) {
+                    chord_note.off_time = Some(time);
+                    return;
+                }
+
+                match self.note_delay[note_index(channel, note)].take() {
+                    Some(delay) if delay > 0 => {
+                        let scheduled = self
+                            .scheduler
+                            .schedule(time + delay - self.position, in_event);
+                        nih_debug_assert!(scheduled.is_ok());
+                    }
+                    _ => send(in_event),
+                }
+            }
+            _ => send(in_event),
+        }
+    }
+}
+
+impl Plugin for RisStrum {
+    const NAME: &'static str = env!("CARGO_PKG_NAME");
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const VENDOR: &'static str = "Simon Leiner";
+    const EMAIL: &'static str = "rismidi@leiner.me";
+
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const DEFAULT_INPUT_CHANNELS: u32 = 0;
+    const DEFAULT_OUTPUT_CHANNELS: u32 = 0;
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::MidiCCs;
+
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn accepts_bus_config(&self, config: &BusConfig) -> bool {
+        let no_aux_busses = AuxiliaryIOConfig {
+            num_busses: 0,
+            num_channels: 0,
+        };
+
+        config.num_input_channels == 0
+            && config.num_output_channels == 0
+            && config.aux_input_busses == no_aux_busses
+            && config.aux_output_busses == no_aux_busses
+    }
+
+    fn initialize(
+        &mut self,
+        _bus_config: &BusConfig,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        self.sample_rate = buffer_config.sample_rate;
+
+        true
+    }
+
+    fn reset(&mut self) {
+        // We cannot send events from here, so the pending note-offs are sent with the next buffer.
+        // Notes that have not been strummed yet are dropped.
+        self.scheduler
+            .retain(|event| matches!(event, NoteEvent::NoteOff { .. }));
+        self.flush_pending = true;
+        self.position = 0;
+        self.chord.clear();
+        self.alternate_down = false;
+        self.note_delay.fill(None);
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        if std::mem::take(&mut self.flush_pending) {
+            while let Some(note_off) = self.scheduler.pop_before(u32::MAX) {
+                context.send_event(note_off.with_timing(0));
+            }
+        }
+
+        let num_samples = buffer.samples() as u32;
+
+        while let Some(in_event) = context.next_event() {
+            let strum = self.current_strum();
+            self.end_chord_before(self.position + in_event.timing() as u64, strum);
+            while let Some(strummed_event) = self.scheduler.pop_before(in_event.timing()) {
+                context.send_event(strummed_event);
+            }
+
+            self.process_event(in_event, strum, |event| context.send_event(event));
+        }
+
+        let strum = self.current_strum();
+        self.end_chord_before(self.position + num_samples as u64, strum);
+        while let Some(strummed_event) = self.scheduler.pop_before(num_samples) {
+            context.send_event(strummed_event);
+        }
+        self.scheduler.advance(num_samples);
+        self.position += num_samples as u64;
+
+        // Pending notes must be sent, even if there is no more input.
+        ProcessStatus::KeepAlive
+    }
+}
+
+impl ClapPlugin for RisStrum {
+    const CLAP_ID: &'static str = "me.leiner.ris.strum";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some(env!("CARGO_PKG_DESCRIPTION"));
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] =
+        &[ClapFeature::Custom("MIDI"), ClapFeature::Utility];
+}
+
+impl Vst3Plugin for RisStrum {
+    const VST3_CLASS_ID: [u8; 16] = *b"risStrum........";
+    const VST3_CATEGORIES: &'static str = "Fx|Tools";
+}
+
+nih_export_clap!(RisStrum);
+nih_export_vst3!(RisStrum);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rismidi::testing::{note_off, note_on};
+
+    const UP: Strum = Strum {
+        time: 100,
+        window: 10,
+        direction: Direction::Up,
+        velocity_ramp: 0.0,
+    };
+
+    /// Processes `in_events` and returns the passed through and strummed events.
+    fn process(processor: &mut RisStrum, in_events: &[NoteEvent], strum: Strum) -> Vec<NoteEvent> {
+        let mut out_events = Vec::new();
+        for &in_event in in_events {
+            processor.end_chord_before(in_event.timing() as u64, strum);
+            processor.process_event(in_event, strum, |event| out_events.push(event));
+        }
+        processor.end_chord_before(u64::MAX, strum);
+        out_events.extend(std::iter::from_fn(|| {
+            processor.scheduler.pop_before(u32::MAX)
+        }));
+        out_events
+    }
+
+    #[test]
+    fn chords_are_strummed_after_the_window() {
+        let mut processor = RisStrum::default();
+        let chord = [
+            note_on(0, 0, 64, 0.5),
+            note_on(5, 0, 60, 0.5),
+            note_on(10, 0, 67, 0.5),
+            note_on(11, 0, 72, 0.5),
+        ];
+
+        assert_eq!(
+            process(&mut processor, &chord, UP),
+            vec![
+                note_on(10, 0, 60, 0.5),
+                // 72 is played after the window, so it starts a new chord.
+                note_on(21, 0, 72, 0.5),
+                note_on(60, 0, 64, 0.5),
+                note_on(110, 0, 67, 0.5),
+            ]
+        );
+    }
+
+    #[test]
+    fn alternating_strums_change_direction() {
+        let mut processor = RisStrum::default();
+        let alternating = Strum {
+            direction: Direction::Alternating,
+            window: 0,
+            ..UP
+        };
+        let chord = [note_on(0, 0, 60, 0.5), note_on(0, 0, 64, 0.5)];
+
+        assert_eq!(
+            process(&mut processor, &chord, alternating),
+            vec![note_on(0, 0, 60, 0.5), note_on(100, 0, 64, 0.5)]
+        );
+        assert_eq!(
+            process(&mut processor, &chord, alternating),
+            vec![note_on(0, 0, 64, 0.5), note_on(100, 0, 60, 0.5)]
+        );
+    }
+
+    #[test]
+    fn velocity_is_ramped() {
+        let mut processor = RisStrum::default();
+        let ramp = Strum {
+            direction: Direction::Down,
+            velocity_ramp: -0.5,
+            ..UP
+        };
+        let chord = [
+            note_on(0, 0, 60, 1.0),
+            note_on(0, 0, 64, 1.0),
+            note_on(0, 0, 67, 1.0),
+        ];
+
+        assert_eq!(
+            process(&mut processor, &chord, ramp),
+            vec![
+                note_on(10, 0, 67, 1.0),
+                note_on(60, 0, 64, 0.75),
+                note_on(110, 0, 60, 0.5)
+            ]
+        );
+    }
+
+    #[test]
+    fn notes_keep_their_length() {
+        let mut processor = RisStrum::default();
+        let events = [
+            note_on(0, 0, 60, 0.5),
+            note_on(0, 0, 64, 0.5),
+            note_off(5, 0, 60),
+            note_off(20, 0, 64),
+        ];
+
+        assert_eq!(
+            process(&mut processor, &events, UP),
+            vec![
+                note_on(10, 0, 60, 0.5),
+                note_off(15, 0, 60),
+                note_on(110, 0, 64, 0.5),
+                note_off(130, 0, 64)
+            ]
+        );
+        assert_eq!(
+            process(&mut processor, &[note_off(200, 0, 64)], UP),
+            vec![note_off(200, 0, 64)]
+        );
+    }
+
+    #[test]
+    fn only_note_offs_are_kept_on_reset() {
+        let mut processor = RisStrum::default();
+        let events = [
+            note_on(0, 0, 60, 0.5),
+            note_on(0, 0, 64, 0.5),
+            note_off(20, 0, 60),
+            note_off(20, 0, 64),
+        ];
+        for in_event in events {
+            processor.end_chord_before(in_event.timing() as u64, UP);
+            processor.process_event(in_event, UP, |_| ());
+        }
+
+        processor.reset();
+        assert!(processor.flush_pending);
+        let pending: Vec<NoteEvent> =
+            std::iter::from_fn(|| processor.scheduler.pop_before(u32::MAX)).collect();
+        assert_eq!(pending, vec![note_off(30, 0, 60), note_off(130, 0, 64)]);
+    }
+}
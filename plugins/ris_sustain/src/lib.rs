@@ -0,0 +1,420 @@
+use nih_plug::prelude::*;
+use rismidi::{HeldNotes, MidiChannel, NUM_MIDI_CHANNELS, NUM_MIDI_NOTES};
+use std::sync::Arc;
+
+const MIDI_CHANNEL_FROM_NIH_PLUG: &str = "MIDI channels from nih_plug must be in range 0..=15";
+
+/// The CC of the sustain (damper) pedal.
+const CC_SUSTAIN: u8 = 64;
+
+/// The CC of the sostenuto pedal.
+const CC_SOSTENUTO: u8 = 66;
+
+/// Pedals count as pressed from this (normalized) value on, i.e. from a MIDI value of 64.
+const PEDAL_THRESHOLD: f32 = 0.5;
+
+struct RisSustain {
+    params: Arc<RisSustainParams>,
+
+    /// The keys which are currently held down.
+    held_keys: HeldNotes,
+
+    /// Whether the sustain pedal is pressed, per channel.
+    sustain: [bool; NUM_MIDI_CHANNELS as usize],
+
+    /// Whether the sostenuto pedal is pressed, per channel.
+    sostenuto: [bool; NUM_MIDI_CHANNELS as usize],
+
+    /// For every note, whether it was sounding when the sostenuto pedal was pressed, see
+    /// [`note_index`].
+    sostenuto_notes: Vec<bool>,
+
+    /// For every note, whether its note-off is being held back by a pedal.
+    sustained: Vec<bool>,
+
+    /// Whether the plugin has been reset, so that all sustained notes need to be released.
+    flush_pending: bool,
+}
+
+#[derive(Params)]
+struct RisSustainParams {
+    #[id = "sustain"]
+    pub sustain: BoolParam,
+
+    #[id = "sostenuto"]
+    pub sostenuto: BoolParam,
+
+    #[id = "keep_pedal_ccs"]
+    pub keep_pedal_ccs: BoolParam,
+}
+
+/// Which pedals are emulated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Pedals {
+    sustain: bool,
+    sostenuto: bool,
+    keep_pedal_ccs: bool,
+}
+
+impl Default for RisSustain {
+    fn default() -> Self {
+        let num_notes = NUM_MIDI_NOTES as usize * NUM_MIDI_CHANNELS as usize;
+
+        Self {
+            params: Arc::new(RisSustainParams::default()),
+            held_keys: HeldNotes::new(),
+            sustain: [false; NUM_MIDI_CHANNELS as usize],
+            sostenuto: [false; NUM_MIDI_CHANNELS as usize],
+            sostenuto_notes: vec![false; num_notes],
+            sustained: vec![false; num_notes],
+            flush_pending: false,
+        }
+    }
+}
+
+impl Default for RisSustainParams {
+    fn default() -> Self {
+        Self {
+            sustain: BoolParam::new("Sustain", true),
+            sostenuto: BoolParam::new("Sostenuto", true),
+            keep_pedal_ccs: BoolParam::new("Keep Pedal CCs", false),
+        }
+    }
+}
+
+/// The index of a note in the per-note tables of [`RisSustain`].
+fn note_index(channel: MidiChannel, note: u8) -> usize {
+    channel.to_0_based() as usize * NUM_MIDI_NOTES as usize + note as usize
+}
+
+fn note_off(timing: u32, channel: MidiChannel, note: u8) -> NoteEvent {
+    NoteEvent::NoteOff {
+        timing,
+        voice_id: None,
+        channel: channel.to_0_based(),
+        note,
+        velocity: 0.0,
+    }
+}
+
+impl RisSustain {
+    /// Gets the emulated pedals from the current parameter values.
+    fn current_pedals(&self) -> Pedals {
+        Pedals {
+            sustain: self.params.sustain.value(),
+            sostenuto: self.params.sostenuto.value(),
+            keep_pedal_ccs: self.params.keep_pedal_ccs.value(),
+        }
+    }
+
+    /// Returns `true` if a pedal keeps `note` sounding after its key has been released.
+    fn is_held_by_pedal(&self, channel: MidiChannel, note: u8) -> bool {
+        self.sustain[channel.to_0_based() as usize]
+            || self.sostenuto_notes[note_index(channel, note)]
+    }
+
+    /// Sends the delayed note-offs for all notes on `channel` which are no longer held by a pedal.
+    fn release_notes(
+        &mut self,
+        timing: u32,
+        channel: MidiChannel,
+        send: &mut impl FnMut(NoteEvent),
+    ) {
+        for note in 0..NUM_MIDI_NOTES {
+            let idx = note_index(channel, note);
+            if self.sustained[idx] && !self.is_held_by_pedal(channel, note) {
+                self.sustained[idx] = false;
+                send(note_off(timing, channel, note));
+            }
+        }
+    }
+
+    /// Sends the delayed note-offs for all notes, regardless of the pedals.
+    fn flush(&mut self, timing: u32, mut send: impl FnMut(NoteEvent)) {
+        for channel_idx in 0..NUM_MIDI_CHANNELS as usize {
+            let channel = MidiChannel::try_from_0_based(channel_idx).expect("channel is in range");
+            for note in 0..NUM_MIDI_NOTES {
+                if std::mem::take(&mut self.sustained[note_index(channel, note)]) {
+                    send(note_off(timing, channel, note));
+                }
+            }
+        }
+    }
+
+    /// Passes the events resulting from `in_event` to `send`.
+    fn process_event(
+        &mut self,
+        in_event: NoteEvent,
+        pedals: Pedals,
+        mut send: impl FnMut(NoteEvent),
+    ) {
+        match in_event {
+            NoteEvent::NoteOn {
+                timing,
+                channel,
+                note,
+                velocity,
+                ..
+            } if note < NUM_MIDI_NOTES => {
+                let channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+
+                // A sustained note which is played again is stopped first, so that the two notes
+                // do not pile up.
+                if std::mem::take(&mut self.sustained[note_index(channel, note)]) {
+                    send(note_off(timing, channel, note));
+                }
+
+                self.held_keys.note_on(channel, note, velocity);
+                send(in_event);
+            }
+            NoteEvent::NoteOff { channel, note, .. } if note < NUM_MIDI_NOTES => {
+                let channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+                let was_held = self.held_keys.note_off(channel, note).is_some();
+
+                if was_held && self.is_held_by_pedal(channel, note) {
+                    self.sustained[note_index(channel, note)] = true;
+                } else {
+                    send(in_event);
+                }
+            }
+            NoteEvent::MidiCC {
+                timing,
+                channel,
+                cc: CC_SUSTAIN,
+                value,
+            } => {
+                let channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+                // If the emulation has been disabled since the pedal was pressed, we still need
+                // to release the notes.
+                let pressed = pedals.sustain && value >= PEDAL_THRESHOLD;
+                self.sustain[channel.to_0_based() as usize] = pressed;
+                if !pressed {
+                    self.release_notes(timing, channel, &mut send);
+                }
+
+                if pedals.keep_pedal_ccs || !pedals.sustain {
+                    send(in_event);
+                }
+            }
+            NoteEvent::MidiCC {
+                timing,
+                channel,
+                cc: CC_SOSTENUTO,
+                value,
+            } => {
+                let channel = MidiChannel::try_from_0_based(channel.into())
+                    .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+                let pressed = pedals.sostenuto && value >= PEDAL_THRESHOLD;
+                let was_pressed =
+                    std::mem::replace(&mut self.sostenuto[channel.to_0_based() as usize], pressed);
+
+                if pressed && !was_pressed {
+                    // The sostenuto pedal only holds the notes which are sounding right now.
+                    for note in 0..NUM_MIDI_NOTES {
+                        let idx = note_index(channel, note);
+                        self.sostenuto_notes[idx] =
+                            self.held_keys.is_held(channel, note) || self.sustained[idx];
+                    }
+                } else if !pressed {
+                    let first = note_index(channel, 0);
+                    self.sostenuto_notes[first..first + NUM_MIDI_NOTES as usize].fill(false);
+                    self.release_notes(timing, channel, &mut send);
+                }
+
+                if pedals.keep_pedal_ccs || !pedals.sostenuto {
+                    send(in_event);
+                }
+            }
+            _ => send(in_event),
+        }
+    }
+}
+
+impl Plugin for RisSustain {
+    const NAME: &'static str = env!("CARGO_PKG_NAME");
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const VENDOR: &'static str = "Simon Leiner";
+    const EMAIL: &'static str = "rismidi@leiner.me";
+
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const DEFAULT_INPUT_CHANNELS: u32 = 0;
+    const DEFAULT_OUTPUT_CHANNELS: u32 = 0;
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::MidiCCs;
+
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn accepts_bus_config(&self, config: &BusConfig) -> bool {
+        let no_aux_busses = AuxiliaryIOConfig {
+            num_busses: 0,
+            num_channels: 0,
+        };
+
+        config.num_input_channels == 0
+            && config.num_output_channels == 0
+            && config.aux_input_busses == no_aux_busses
+            && config.aux_output_busses == no_aux_busses
+    }
+
+    fn reset(&mut self) {
+        // We cannot send events from here, so the sustained notes are released with the next
+        // buffer.
+        self.held_keys.clear();
+        self.sustain = [false; NUM_MIDI_CHANNELS as usize];
+        self.sostenuto = [false; NUM_MIDI_CHANNELS as usize];
+        self.sostenuto_notes.fill(false);
+        self.flush_pending = true;
+    }
+
+    fn process(
+        &mut self,
+        _buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        if std::mem::take(&mut self.flush_pending) {
+            self.flush(0, |event| context.send_event(event));
+        }
+
+        while let Some(in_event) = context.next_event() {
+            let pedals = self.current_pedals();
+            self.process_event(in_event, pedals, |event| context.send_event(event));
+        }
+
+        ProcessStatus::Normal
+    }
+}
+
+impl ClapPlugin for RisSustain {
+    const CLAP_ID: &'static str = "me.leiner.ris.sustain";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some(env!("CARGO_PKG_DESCRIPTION"));
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] =
+        &[ClapFeature::Custom("MIDI"), ClapFeature::Utility];
+}
+
+impl Vst3Plugin for RisSustain {
+    const VST3_CLASS_ID: [u8; 16] = *b"risSustain......";
+    const VST3_CATEGORIES: &'static str = "Fx|Tools";
+}
+
+nih_export_clap!(RisSustain);
+nih_export_vst3!(RisSustain);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rismidi::testing::{cc, collect_events, note_off, note_on};
+
+    const BOTH: Pedals = Pedals {
+        sustain: true,
+        sostenuto: true,
+        keep_pedal_ccs: false,
+    };
+
+    #[test]
+    fn note_offs_wait_for_the_sustain_pedal() {
+        let mut processor = RisSustain::default();
+        processor.process_event(note_on(0, 0, 60, 0.5), BOTH, |_| ());
+
+        let in_event = cc(0, 0, CC_SUSTAIN, 1.0);
+        let out_events = collect_events(|send| processor.process_event(in_event, BOTH, send));
+        assert_eq!(out_events, vec![]);
+
+        let in_event = note_off(0, 0, 60);
+        let out_events = collect_events(|send| processor.process_event(in_event, BOTH, send));
+        assert_eq!(out_events, vec![]);
+
+        // Notes played while the pedal is down are sustained as well.
+        processor.process_event(note_on(0, 0, 64, 0.5), BOTH, |_| ());
+
+        let in_event = note_off(0, 0, 64);
+        let out_events = collect_events(|send| processor.process_event(in_event, BOTH, send));
+        assert_eq!(out_events, vec![]);
+
+        let in_event = cc(0, 0, CC_SUSTAIN, 0.0);
+        let out_events = collect_events(|send| processor.process_event(in_event, BOTH, send));
+        assert_eq!(out_events, vec![note_off(0, 0, 60), note_off(0, 0, 64)]);
+
+        let keep_ccs = Pedals {
+            keep_pedal_ccs: true,
+            ..BOTH
+        };
+
+        let in_event = cc(0, 0, CC_SUSTAIN, 1.0);
+        let out_events = collect_events(|send| processor.process_event(in_event, keep_ccs, send));
+        assert_eq!(out_events, vec![cc(0, 0, CC_SUSTAIN, 1.0)]);
+    }
+
+    #[test]
+    fn sostenuto_holds_only_notes_sounding_when_pressed() {
+        let mut processor = RisSustain::default();
+        processor.process_event(note_on(0, 0, 60, 0.5), BOTH, |_| ());
+        processor.process_event(cc(0, 0, CC_SOSTENUTO, 1.0), BOTH, |_| ());
+        processor.process_event(note_on(0, 0, 64, 0.5), BOTH, |_| ());
+
+        let in_event = note_off(0, 0, 60);
+        let out_events = collect_events(|send| processor.process_event(in_event, BOTH, send));
+        assert_eq!(out_events, vec![]);
+
+        let in_event = note_off(0, 0, 64);
+        let out_events = collect_events(|send| processor.process_event(in_event, BOTH, send));
+        assert_eq!(out_events, vec![note_off(0, 0, 64)]);
+
+        let in_event = cc(0, 0, CC_SOSTENUTO, 0.0);
+        let out_events = collect_events(|send| processor.process_event(in_event, BOTH, send));
+        assert_eq!(out_events, vec![note_off(0, 0, 60)]);
+    }
+
+    #[test]
+    fn sustained_notes_played_again_are_stopped_first() {
+        let mut processor = RisSustain::default();
+        processor.process_event(cc(0, 0, CC_SUSTAIN, 1.0), BOTH, |_| ());
+        processor.process_event(note_on(0, 0, 60, 0.5), BOTH, |_| ());
+        processor.process_event(note_off(0, 0, 60), BOTH, |_| ());
+
+        let in_event = note_on(0, 0, 60, 0.5);
+        let out_events = collect_events(|send| processor.process_event(in_event, BOTH, send));
+        assert_eq!(out_events, vec![note_off(0, 0, 60), note_on(0, 0, 60, 0.5)]);
+
+        let in_event = note_off(0, 0, 60);
+        let out_events = collect_events(|send| processor.process_event(in_event, BOTH, send));
+        assert_eq!(out_events, vec![]);
+
+        let in_event = cc(0, 0, CC_SUSTAIN, 0.0);
+        let out_events = collect_events(|send| processor.process_event(in_event, BOTH, send));
+        assert_eq!(out_events, vec![note_off(0, 0, 60)]);
+    }
+
+    #[test]
+    fn sustained_notes_are_flushed_after_reset() {
+        let mut processor = RisSustain::default();
+        processor.process_event(cc(0, 0, CC_SUSTAIN, 1.0), BOTH, |_| ());
+        processor.process_event(note_on(0, 0, 60, 0.5), BOTH, |_| ());
+        processor.process_event(note_off(0, 0, 60), BOTH, |_| ());
+
+        processor.reset();
+        assert!(processor.flush_pending);
+        let out_events = collect_events(|send| processor.flush(0, send));
+        assert_eq!(out_events, vec![note_off(0, 0, 60)]);
+
+        // The pedal has been released by the reset.
+        processor.process_event(note_on(0, 0, 62, 0.5), BOTH, |_| ());
+
+        let in_event = note_off(0, 0, 62);
+        let out_events = collect_events(|send| processor.process_event(in_event, BOTH, send));
+        assert_eq!(out_events, vec![note_off(0, 0, 62)]);
+    }
+}
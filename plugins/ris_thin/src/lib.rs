@@ -0,0 +1,506 @@
+use nih_plug::prelude::*;
+use rismidi::{
+    is_parameter_number_cc, EventScheduler, HasTiming, MidiChannel, OptionalMidiChannelParam,
+    MAX_14_BIT, MAX_7_BIT, NUM_MIDI_CCS, NUM_MIDI_CHANNELS, NUM_MIDI_NOTES,
+};
+use std::sync::Arc;
+
+const MIDI_CHANNEL_FROM_NIH_PLUG: &str = "MIDI channels from nih_plug must be in range 0..=15";
+
+/// The CCs which select a bank together with the next program change.
+const CC_BANK_SELECT_MSB: u8 = 0;
+const CC_BANK_SELECT_LSB: u8 = 32;
+
+/// CC 120–127 are channel mode messages such as "All Notes Off".
+const FIRST_CHANNEL_MODE_CC: u8 = 120;
+
+/// The controller number used for pitch bend, see [`controller_index`].
+const PITCH_BEND: usize = NUM_MIDI_CCS as usize;
+
+/// The controller number used for channel pressure, see [`controller_index`].
+const CHANNEL_PRESSURE: usize = PITCH_BEND + 1;
+
+/// The first controller number used for poly pressure, see [`controller_index`].
+const POLY_PRESSURE: usize = CHANNEL_PRESSURE + 1;
+
+/// Every channel has one controller per CC, pitch bend, channel pressure and one controller per
+/// note for poly pressure.
+const NUM_CONTROLLERS: usize = POLY_PRESSURE + NUM_MIDI_NOTES as usize;
+
+/// The total number of controllers on all channels.
+const NUM_CHANNEL_CONTROLLERS: usize = NUM_MIDI_CHANNELS as usize * NUM_CONTROLLERS;
+
+struct RisThin {
+    params: Arc<RisThinParams>,
+    sample_rate: f32,
+
+    /// Holds back at most one value per controller until it may be sent.
+    scheduler: EventScheduler,
+
+    /// The number of samples from the last reset until the start of the current buffer.
+    position: u64,
+
+    /// The state of each controller, by [`controller_index`].
+    controllers: Vec<Controller>,
+
+    /// Whether the plugin has been reset, so that the held back values need to be sent.
+    flush_pending: bool,
+}
+
+#[derive(Params)]
+struct RisThinParams {
+    #[id = "max_rate"]
+    pub max_rate: IntParam,
+
+    #[id = "min_change"]
+    pub min_change: IntParam,
+
+    #[nested(id_prefix = "channel")]
+    pub channel: OptionalMidiChannelParam,
+}
+
+/// How much the controllers are thinned out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Thinning {
+    /// The minimum number of samples between two values of the same controller.
+    interval: u64,
+
+    /// The minimum change in 7-bit steps for a value to be sent before the controller settles.
+    min_change: u16,
+
+    channel: Option<MidiChannel>,
+}
+
+/// The state of a single CC, pitch bend or pressure on one channel.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Controller {
+    /// The raw MIDI value which was sent last, or [`None`] if no value has been sent yet.
+    sent: Option<u16>,
+
+    /// The time at which the last value was sent, in samples since the last reset.
+    sent_at: u64,
+
+    /// Whether a newer value is waiting in the scheduler.
+    pending: bool,
+}
+
+impl Default for RisThin {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(RisThinParams::default()),
+            sample_rate: 44100.0,
+            scheduler: EventScheduler::with_capacity(NUM_CHANNEL_CONTROLLERS),
+            position: 0,
+            controllers: vec![Controller::default(); NUM_CHANNEL_CONTROLLERS],
+            flush_pending: false,
+        }
+    }
+}
+
+impl Default for RisThinParams {
+    fn default() -> Self {
+        Self {
+            max_rate: IntParam::new("Max Rate", 100, IntRange::Linear { min: 1, max: 1000 })
+                .with_unit(" /s"),
+            min_change: IntParam::new("Min Change", 1, IntRange::Linear { min: 1, max: 16 }),
+            channel: OptionalMidiChannelParam::new("Channel", None)
+                .with_none_selected_description("All"),
+        }
+    }
+}
+
+/// The position of the controller changed by `event` in [`RisThin::controllers`], together with
+/// the raw MIDI value and its maximum. Returns [`None`] for all other events, including bank
+/// selects, RPN/NRPN messages and channel mode messages, which must never be held back or dropped.
+fn controller_index(event: &NoteEvent) -> Option<(usize, u16, u16)> {
+    let (channel, controller, value, max) = match *event {
+        NoteEvent::MidiCC { cc, .. }
+            if cc == CC_BANK_SELECT_MSB
+                || cc == CC_BANK_SELECT_LSB
+                || is_parameter_number_cc(cc)
+                || cc >= FIRST_CHANNEL_MODE_CC =>
+        {
+            return None
+        }
+        NoteEvent::MidiCC {
+            channel, cc, value, ..
+        } => (channel, cc as usize, value, MAX_7_BIT),
+        NoteEvent::MidiPitchBend { channel, value, .. } => (channel, PITCH_BEND, value, MAX_14_BIT),
+        NoteEvent::MidiChannelPressure {
+            channel, pressure, ..
+        } => (channel, CHANNEL_PRESSURE, pressure, MAX_7_BIT),
+        NoteEvent::PolyPressure {
+            channel,
+            note,
+            pressure,
+            ..
+        } => (channel, POLY_PRESSURE + note as usize, pressure, MAX_7_BIT),
+        _ => return None,
+    };
+
+    let index = channel as usize * NUM_CONTROLLERS + controller;
+    let raw = (value * max as f32).round().clamp(0.0, max as f32) as u16;
+    Some((index, raw, max))
+}
+
+impl RisThin {
+    /// Gets the thinning settings from the current parameter values.
+    fn current_thinning(&self) -> Thinning {
+        Thinning {
+            interval: (self.sample_rate / self.params.max_rate.value() as f32).round() as u64,
+            min_change: self.params.min_change.value() as u16,
+            channel: self.params.channel.value(),
+        }
+    }
+
+    /// Sends all held back values which are due before sample `end` of the current buffer.
+    fn send_due(&mut self, end: u32, mut send: impl FnMut(NoteEvent)) {
+        while let Some(event) = self.scheduler.pop_before(end) {
+            let (index, raw, _) = controller_index(&event).expect("only controllers are scheduled");
+            self.controllers[index] = Controller {
+                sent: Some(raw),
+                sent_at: self.position + event.timing() as u64,
+                pending: false,
+            };
+            send(event);
+        }
+    }
+
+    /// Sends or holds back the controller values in `in_event`. All other events are passed to
+    /// `send`.
+    fn process_event(
+        &mut self,
+        in_event: NoteEvent,
+        thinning: Thinning,
+        mut send: impl FnMut(NoteEvent),
+    ) {
+        let Some((index, raw, max)) = controller_index(&in_event) else {
+            send(in_event);
+            return;
+        };
+        let channel = MidiChannel::try_from_0_based(index / NUM_CONTROLLERS)
+            .expect(MIDI_CHANNEL_FROM_NIH_PLUG);
+        if thinning.channel.is_some() && thinning.channel != Some(channel) {
+            send(in_event);
+            return;
+        }
+
+        // A newer value replaces the one which is held back.
+        if self.controllers[index].pending {
+            self.scheduler
+                .retain(|event| controller_index(event).map(|(i, ..)| i) != Some(index));
+            self.controllers[index].pending = false;
+        }
+
+        let now = self.position + in_event.timing() as u64;
+        let controller = &mut self.controllers[index];
+        let due = match controller.sent {
+            None => now,
+            // Repeated values are only dropped while they would exceed the rate anyway, so that
+            // a value can still be refreshed, e.g. after the receiving device was switched on.
+            Some(sent) if sent == raw => {
+                if now < controller.sent_at + thinning.interval {
+                    return;
+                }
+                now
+            }
+            Some(sent) => {
+                // Small changes are only sent once the controller has not moved for a while.
+                let large = sent.abs_diff(raw) as u32 * MAX_7_BIT as u32
+                    >= thinning.min_change as u32 * max as u32;
+                let settled = if large { now } else { now + thinning.interval };
+                settled.max(controller.sent_at + thinning.interval)
+            }
+        };
+
+        if due <= now {
+            *controller = Controller {
+                sent: Some(raw),
+                sent_at: now,
+                pending: false,
+            };
+            send(in_event);
+        } else {
+            controller.pending = true;
+            let scheduled = self.scheduler.schedule(due - self.position, in_event);
+            nih_debug_assert!(scheduled.is_ok());
+        }
+    }
+}
+
+impl Plugin for RisThin {
+    const NAME: &'static str = env!("CARGO_PKG_NAME");
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const VENDOR: &'static str = "Simon Leiner";
+    const EMAIL: &'static str = "rismidi@leiner.me";
+
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const DEFAULT_INPUT_CHANNELS: u32 = 0;
+    const DEFAULT_OUTPUT_CHANNELS: u32 = 0;
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::MidiCCs;
+
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn accepts_bus_config(&self, config: &BusConfig) -> bool {
+        let no_aux_busses = AuxiliaryIOConfig {
+            num_busses: 0,
+            num_channels: 0,
+        };
+
+        config.num_input_channels == 0
+            && config.num_output_channels == 0
+            && config.aux_input_busses == no_aux_busses
+            && config.aux_output_busses == no_aux_busses
+    }
+
+    fn initialize(
+        &mut self,
+        _bus_config: &BusConfig,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        self.sample_rate = buffer_config.sample_rate;
+
+        true
+    }
+
+    fn reset(&mut self) {
+        // We cannot send events from here, so the held back values are sent with the next buffer.
+        self.flush_pending = true;
+        self.position = 0;
+        self.controllers.fill(Controller::default());
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        if std::mem::take(&mut self.flush_pending) {
+            while let Some(event) = self.scheduler.pop_before(u32::MAX) {
+                context.send_event(event.with_timing(0));
+            }
+        }
+
+        let num_samples = buffer.samples() as u32;
+
+        while let Some(in_event) = context.next_event() {
+            self.send_due(in_event.timing(), |event| context.send_event(event));
+
+            let thinning = self.current_thinning();
+            self.process_event(in_event, thinning, |event| context.send_event(event));
+        }
+
+        self.send_due(num_samples, |event| context.send_event(event));
+        self.scheduler.advance(num_samples);
+        self.position += num_samples as u64;
+
+        // The final values must be sent, even if there is no more input.
+        ProcessStatus::KeepAlive
+    }
+}
+
+impl ClapPlugin for RisThin {
+    const CLAP_ID: &'static str = "me.leiner.ris.thin";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some(env!("CARGO_PKG_DESCRIPTION"));
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] =
+        &[ClapFeature::Custom("MIDI"), ClapFeature::Utility];
+}
+
+impl Vst3Plugin for RisThin {
+    const VST3_CLASS_ID: [u8; 16] = *b"risThin.........";
+    const VST3_CATEGORIES: &'static str = "Fx|Tools";
+}
+
+nih_export_clap!(RisThin);
+nih_export_vst3!(RisThin);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THINNING: Thinning = Thinning {
+        interval: 100,
+        min_change: 1,
+        channel: None,
+    };
+
+    fn cc(timing: u32, raw: u16) -> NoteEvent {
+        NoteEvent::MidiCC {
+            timing,
+            channel: 0,
+            cc: 74,
+            value: raw as f32 / MAX_7_BIT as f32,
+        }
+    }
+
+    fn process(
+        processor: &mut RisThin,
+        in_events: &[NoteEvent],
+        thinning: Thinning,
+    ) -> Vec<NoteEvent> {
+        let mut out_events = Vec::new();
+        for in_event in in_events {
+            processor.send_due(in_event.timing(), |event| out_events.push(event));
+            processor.process_event(*in_event, thinning, |event| out_events.push(event));
+        }
+        processor.send_due(u32::MAX, |event| out_events.push(event));
+        out_events
+    }
+
+    #[test]
+    fn rate_is_limited_and_final_value_is_sent() {
+        let mut processor = RisThin::default();
+        let in_events = [cc(0, 0), cc(10, 10), cc(50, 20), cc(150, 30), cc(250, 40)];
+
+        assert_eq!(
+            process(&mut processor, &in_events, THINNING),
+            vec![cc(0, 0), cc(100, 20), cc(200, 30), cc(300, 40)]
+        );
+    }
+
+    #[test]
+    fn small_changes_are_sent_once_settled() {
+        let mut processor = RisThin::default();
+        let thinning = Thinning {
+            min_change: 4,
+            ..THINNING
+        };
+        let in_events = [cc(0, 0), cc(200, 2), cc(250, 3), cc(300, 10), cc(320, 11)];
+
+        assert_eq!(
+            process(&mut processor, &in_events, thinning),
+            vec![cc(0, 0), cc(300, 10), cc(420, 11)]
+        );
+    }
+
+    #[test]
+    fn controllers_are_limited_separately() {
+        let mut processor = RisThin::default();
+        let bend = |timing: u32| NoteEvent::MidiPitchBend {
+            timing,
+            channel: 0,
+            value: timing as f32 / 1000.0,
+        };
+        let pressure = |timing: u32, note: u8| NoteEvent::PolyPressure {
+            timing,
+            voice_id: None,
+            channel: 0,
+            note,
+            pressure: 0.5,
+        };
+        let in_events = [
+            cc(0, 0),
+            bend(0),
+            pressure(0, 60),
+            pressure(10, 61),
+            cc(20, 1),
+        ];
+
+        assert_eq!(
+            process(&mut processor, &in_events, THINNING),
+            vec![
+                cc(0, 0),
+                bend(0),
+                pressure(0, 60),
+                pressure(10, 61),
+                cc(100, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn other_events_are_passed_through() {
+        let mut processor = RisThin::default();
+        let note_on = NoteEvent::NoteOn {
+            timing: 10,
+            voice_id: None,
+            channel: 0,
+            note: 60,
+            velocity: 0.5,
+        };
+        let other_channel = NoteEvent::MidiCC {
+            timing: 20,
+            channel: 1,
+            cc: 74,
+            value: 1.0,
+        };
+        let only_channel_1 = Thinning {
+            channel: Some(MidiChannel::Channel1),
+            ..THINNING
+        };
+
+        // Repeated values are dropped within the interval.
+        assert_eq!(
+            process(
+                &mut processor,
+                &[cc(0, 5), note_on, cc(10, 5), other_channel, other_channel],
+                only_channel_1
+            ),
+            vec![cc(0, 5), note_on, other_channel, other_channel]
+        );
+    }
+
+    #[test]
+    fn repeated_values_are_sent_after_the_interval() {
+        let mut processor = RisThin::default();
+        let in_events = [cc(0, 5), cc(50, 5), cc(150, 5), cc(200, 5), cc(250, 5)];
+
+        assert_eq!(
+            process(&mut processor, &in_events, THINNING),
+            vec![cc(0, 5), cc(150, 5), cc(250, 5)]
+        );
+    }
+
+    #[test]
+    fn bank_selects_parameter_numbers_and_channel_mode_messages_are_not_thinned() {
+        let mut processor = RisThin::default();
+        let unthinned = |timing: u32, cc: u8| NoteEvent::MidiCC {
+            timing,
+            channel: 0,
+            cc,
+            value: 0.0,
+        };
+        let in_events = [
+            unthinned(0, 0),
+            unthinned(0, 32),
+            unthinned(1, 0),
+            unthinned(1, 32),
+            unthinned(1, 101),
+            unthinned(1, 100),
+            unthinned(1, 6),
+            unthinned(1, 100),
+            unthinned(1, 6),
+            unthinned(1, 96),
+            unthinned(1, 96),
+            unthinned(2, 123),
+            unthinned(3, 123),
+            unthinned(4, 120),
+        ];
+
+        assert_eq!(process(&mut processor, &in_events, THINNING), in_events);
+    }
+
+    #[test]
+    fn held_back_values_are_kept_on_reset() {
+        let mut processor = RisThin::default();
+        process(&mut processor, &[cc(0, 0)], THINNING);
+        processor.process_event(cc(10, 20), THINNING, |_| ());
+
+        processor.reset();
+        assert!(processor.flush_pending);
+        assert_eq!(processor.scheduler.pop_before(u32::MAX), Some(cc(100, 20)));
+    }
+}
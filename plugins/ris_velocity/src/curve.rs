@@ -0,0 +1,113 @@
+use nih_plug::prelude::*;
+
+/// The number of user-defined breakpoints for [`Curve::Breakpoints`].
+pub const NUM_BREAKPOINTS: usize = 4;
+
+/// The shape of a velocity curve. All curves map 0 to 0 and 1 to 1.
+#[derive(Enum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Curve {
+    /// Velocities are not changed.
+    #[id = "linear"]
+    #[name = "Linear"]
+    Linear,
+
+    /// Soft notes get softer, so it takes more force to play loud.
+    #[id = "exponential"]
+    #[name = "Exponential"]
+    Exponential,
+
+    /// Soft notes get louder, so it takes less force to play loud.
+    #[id = "logarithmic"]
+    #[name = "Logarithmic"]
+    Logarithmic,
+
+    /// Soft notes get softer and loud notes get louder.
+    #[id = "s_curve"]
+    #[name = "S-Curve"]
+    SShaped,
+
+    /// Straight lines between user-defined points.
+    #[id = "breakpoints"]
+    #[name = "Breakpoints"]
+    Breakpoints,
+}
+
+impl Curve {
+    /// Applies the curve to a normalized velocity. `amount` (from 0 to 1) sets how far the
+    /// curve bends away from a straight line; it is not used for [`Curve::Breakpoints`].
+    pub fn apply(
+        &self,
+        velocity: f32,
+        amount: f32,
+        breakpoints: &[(f32, f32); NUM_BREAKPOINTS],
+    ) -> f32 {
+        let velocity = velocity.clamp(0.0, 1.0);
+        let exponent = 1.0 + 4.0 * amount;
+
+        match self {
+            Curve::Linear => velocity,
+            Curve::Exponential => velocity.powf(exponent),
+            Curve::Logarithmic => velocity.powf(exponent.recip()),
+            Curve::SShaped => {
+                let rising = velocity.powf(exponent);
+                let falling = (1.0 - velocity).powf(exponent);
+                rising / (rising + falling)
+            }
+            Curve::Breakpoints => interpolate(velocity, breakpoints),
+        }
+    }
+}
+
+/// Interpolates linearly between `(input, output)` points, which are extended by `(0, 0)` and
+/// `(1, 1)`. The points do not need to be sorted.
+fn interpolate(velocity: f32, breakpoints: &[(f32, f32); NUM_BREAKPOINTS]) -> f32 {
+    let mut points = [(0.0, 0.0); NUM_BREAKPOINTS + 2];
+    points[1..=NUM_BREAKPOINTS].copy_from_slice(breakpoints);
+    points[NUM_BREAKPOINTS + 1] = (1.0, 1.0);
+    points.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+
+    points
+        .windows(2)
+        .find(|pair| velocity <= pair[1].0)
+        .map(|pair| {
+            let ((in_start, out_start), (in_end, out_end)) = (pair[0], pair[1]);
+            if in_end > in_start {
+                out_start + (velocity - in_start) / (in_end - in_start) * (out_end - out_start)
+            } else {
+                out_end
+            }
+        })
+        .unwrap_or(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIAGONAL: [(f32, f32); NUM_BREAKPOINTS] =
+        [(0.2, 0.2), (0.4, 0.4), (0.6, 0.6), (0.8, 0.8)];
+
+    #[test]
+    fn curves_keep_their_end_points() {
+        for curve in [
+            Curve::Linear,
+            Curve::Exponential,
+            Curve::Logarithmic,
+            Curve::SShaped,
+            Curve::Breakpoints,
+        ] {
+            assert_eq!(curve.apply(0.0, 0.7, &DIAGONAL), 0.0, "{curve:?}");
+            assert_eq!(curve.apply(1.0, 0.7, &DIAGONAL), 1.0, "{curve:?}");
+            assert_eq!(curve.apply(0.5, 0.0, &DIAGONAL), 0.5, "{curve:?}");
+        }
+    }
+
+    #[test]
+    fn breakpoints_are_interpolated_in_order() {
+        let breakpoints = [(0.75, 1.0), (0.25, 0.0), (0.5, 0.5), (0.5, 0.5)];
+
+        assert_eq!(interpolate(0.1, &breakpoints), 0.0);
+        assert_eq!(interpolate(0.375, &breakpoints), 0.25);
+        assert_eq!(interpolate(0.9, &breakpoints), 1.0);
+    }
+}
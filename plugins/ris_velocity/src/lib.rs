@@ -0,0 +1,353 @@
+mod curve;
+
+use curve::{Curve, NUM_BREAKPOINTS};
+use nih_plug::prelude::*;
+use rismidi::{HasChannel, MidiChannel, OptionalMidiChannelParam, MIN_VELOCITY};
+use std::sync::Arc;
+
+struct RisVelocity {
+    params: Arc<RisVelocityParams>,
+}
+
+#[derive(Params)]
+struct RisVelocityParams {
+    #[nested(id_prefix = "channel")]
+    pub channel: OptionalMidiChannelParam,
+
+    #[id = "fixed"]
+    pub fixed: BoolParam,
+
+    #[id = "fixed_velocity"]
+    pub fixed_velocity: FloatParam,
+
+    #[id = "curve"]
+    pub curve: EnumParam<Curve>,
+
+    #[id = "curve_amount"]
+    pub curve_amount: FloatParam,
+
+    #[nested(array)]
+    pub breakpoints: [BreakpointParams; NUM_BREAKPOINTS],
+
+    #[id = "scale"]
+    pub scale: FloatParam,
+
+    #[id = "offset"]
+    pub offset: FloatParam,
+
+    #[id = "min"]
+    pub min: FloatParam,
+
+    #[id = "max"]
+    pub max: FloatParam,
+}
+
+/// A point of the velocity curve for [`Curve::Breakpoints`].
+#[derive(Params)]
+struct BreakpointParams {
+    #[id = "breakpoint_in"]
+    pub input: FloatParam,
+
+    #[id = "breakpoint_out"]
+    pub output: FloatParam,
+}
+
+/// How velocities are changed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct VelocitySettings {
+    fixed_velocity: Option<f32>,
+    curve: Curve,
+    curve_amount: f32,
+    breakpoints: [(f32, f32); NUM_BREAKPOINTS],
+    scale: f32,
+    offset: f32,
+    min: f32,
+    max: f32,
+}
+
+impl Default for RisVelocity {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(RisVelocityParams::default()),
+        }
+    }
+}
+
+/// Creates a parameter for a velocity, shown in percent.
+fn velocity_param(name: impl Into<String>, default: f32, min: f32, max: f32) -> FloatParam {
+    FloatParam::new(name, default, FloatRange::Linear { min, max })
+        .with_unit(" %")
+        .with_value_to_string(formatters::v2s_f32_percentage(0))
+        .with_string_to_value(formatters::s2v_f32_percentage())
+}
+
+impl Default for RisVelocityParams {
+    fn default() -> Self {
+        Self {
+            channel: OptionalMidiChannelParam::new("Channel", None)
+                .with_none_selected_description("All"),
+            fixed: BoolParam::new("Fixed", false),
+            fixed_velocity: velocity_param("Fixed Velocity", 100.0 / 127.0, 0.0, 1.0),
+            curve: EnumParam::new("Curve", Curve::Linear),
+            curve_amount: velocity_param("Curve Amount", 0.5, 0.0, 1.0),
+            breakpoints: std::array::from_fn(|idx| {
+                let position = (idx + 1) as f32 / (NUM_BREAKPOINTS + 1) as f32;
+                BreakpointParams {
+                    input: velocity_param(format!("Breakpoint {} In", idx + 1), position, 0.0, 1.0),
+                    output: velocity_param(
+                        format!("Breakpoint {} Out", idx + 1),
+                        position,
+                        0.0,
+                        1.0,
+                    ),
+                }
+            }),
+            scale: velocity_param("Scale", 1.0, 0.0, 2.0),
+            offset: velocity_param("Offset", 0.0, -1.0, 1.0),
+            min: velocity_param("Min", 0.0, 0.0, 1.0),
+            max: velocity_param("Max", 1.0, 0.0, 1.0),
+        }
+    }
+}
+
+impl VelocitySettings {
+    /// Computes the new velocity for a note-on.
+    fn apply(&self, velocity: f32) -> f32 {
+        let velocity = match self.fixed_velocity {
+            Some(fixed_velocity) => fixed_velocity,
+            None => {
+                let shaped = self
+                    .curve
+                    .apply(velocity, self.curve_amount, &self.breakpoints);
+                (shaped * self.scale + self.offset)
+                    .max(self.min)
+                    .min(self.max)
+            }
+        };
+
+        velocity.clamp(MIN_VELOCITY, 1.0)
+    }
+}
+
+impl RisVelocity {
+    /// Gets the velocity settings from the current parameter values.
+    fn current_settings(&self) -> VelocitySettings {
+        let params = &self.params;
+
+        VelocitySettings {
+            fixed_velocity: params.fixed.value().then(|| params.fixed_velocity.value()),
+            curve: params.curve.value(),
+            curve_amount: params.curve_amount.value(),
+            breakpoints: std::array::from_fn(|idx| {
+                let breakpoint = &params.breakpoints[idx];
+                (breakpoint.input.value(), breakpoint.output.value())
+            }),
+            scale: params.scale.value(),
+            offset: params.offset.value(),
+            min: params.min.value(),
+            max: params.max.value(),
+        }
+    }
+
+    fn transform_event(
+        &mut self,
+        in_event: NoteEvent,
+        filter_chn: Option<MidiChannel>,
+        settings: VelocitySettings,
+    ) -> NoteEvent {
+        if let (Some(filter_channel), Ok(in_channel)) = (filter_chn, in_event.get_channel()) {
+            if in_channel != filter_channel {
+                return in_event;
+            }
+        }
+
+        match in_event {
+            NoteEvent::NoteOn {
+                timing,
+                voice_id,
+                channel,
+                note,
+                velocity,
+            } => NoteEvent::NoteOn {
+                timing,
+                voice_id,
+                channel,
+                note,
+                velocity: settings.apply(velocity),
+            },
+            _ => in_event,
+        }
+    }
+}
+
+impl Plugin for RisVelocity {
+    const NAME: &'static str = env!("CARGO_PKG_NAME");
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const VENDOR: &'static str = "Simon Leiner";
+    const EMAIL: &'static str = "rismidi@leiner.me";
+
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const DEFAULT_INPUT_CHANNELS: u32 = 0;
+    const DEFAULT_OUTPUT_CHANNELS: u32 = 0;
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::MidiCCs;
+
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn accepts_bus_config(&self, config: &BusConfig) -> bool {
+        let no_aux_busses = AuxiliaryIOConfig {
+            num_busses: 0,
+            num_channels: 0,
+        };
+
+        config.num_input_channels == 0
+            && config.num_output_channels == 0
+            && config.aux_input_busses == no_aux_busses
+            && config.aux_output_busses == no_aux_busses
+    }
+
+    fn process(
+        &mut self,
+        _buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        while let Some(in_event) = context.next_event() {
+            let filter_chn = self.params.channel.value();
+            let settings = self.current_settings();
+            context.send_event(self.transform_event(in_event, filter_chn, settings));
+        }
+
+        ProcessStatus::Normal
+    }
+}
+
+impl ClapPlugin for RisVelocity {
+    const CLAP_ID: &'static str = "me.leiner.ris.velocity";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some(env!("CARGO_PKG_DESCRIPTION"));
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] =
+        &[ClapFeature::Custom("MIDI"), ClapFeature::Utility];
+}
+
+impl Vst3Plugin for RisVelocity {
+    const VST3_CLASS_ID: [u8; 16] = *b"risVelocity.....";
+    const VST3_CATEGORIES: &'static str = "Fx|Tools";
+}
+
+nih_export_clap!(RisVelocity);
+nih_export_vst3!(RisVelocity);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rismidi::testing::note_on;
+
+    const LINEAR: VelocitySettings = VelocitySettings {
+        fixed_velocity: None,
+        curve: Curve::Linear,
+        curve_amount: 0.5,
+        breakpoints: [(0.2, 0.2), (0.4, 0.4), (0.6, 0.6), (0.8, 0.8)],
+        scale: 1.0,
+        offset: 0.0,
+        min: 0.0,
+        max: 1.0,
+    };
+
+    #[test]
+    fn velocities_are_scaled_and_clamped() {
+        let mut processor = RisVelocity::default();
+        let settings = VelocitySettings {
+            scale: 0.5,
+            offset: 0.25,
+            max: 0.5,
+            ..LINEAR
+        };
+
+        assert_eq!(
+            processor.transform_event(note_on(0, 0, 60, 0.25), None, settings),
+            note_on(0, 0, 60, 0.375)
+        );
+        assert_eq!(
+            processor.transform_event(note_on(0, 0, 60, 1.0), None, settings),
+            note_on(0, 0, 60, 0.5)
+        );
+    }
+
+    #[test]
+    fn note_ons_are_never_silent() {
+        let mut processor = RisVelocity::default();
+        let settings = VelocitySettings {
+            curve: Curve::Exponential,
+            ..LINEAR
+        };
+
+        assert_eq!(
+            processor.transform_event(note_on(0, 0, 60, 0.01), None, settings),
+            note_on(0, 0, 60, MIN_VELOCITY)
+        );
+    }
+
+    #[test]
+    fn fixed_velocity_overrides_everything_else() {
+        let mut processor = RisVelocity::default();
+        let settings = VelocitySettings {
+            fixed_velocity: Some(0.75),
+            scale: 0.0,
+            max: 0.1,
+            ..LINEAR
+        };
+
+        assert_eq!(
+            processor.transform_event(note_on(0, 0, 60, 0.2), None, settings),
+            note_on(0, 0, 60, 0.75)
+        );
+    }
+
+    #[test]
+    fn only_the_selected_channel_is_changed() {
+        let mut processor = RisVelocity::default();
+        let settings = VelocitySettings {
+            fixed_velocity: Some(1.0),
+            ..LINEAR
+        };
+
+        assert_eq!(
+            processor.transform_event(
+                note_on(0, 3, 60, 0.2),
+                Some(MidiChannel::Channel4),
+                settings
+            ),
+            note_on(0, 3, 60, 1.0)
+        );
+        assert_eq!(
+            processor.transform_event(
+                note_on(0, 4, 60, 0.2),
+                Some(MidiChannel::Channel4),
+                settings
+            ),
+            note_on(0, 4, 60, 0.2)
+        );
+
+        let note_off = NoteEvent::NoteOff {
+            timing: 0,
+            voice_id: None,
+            channel: 3,
+            note: 60,
+            velocity: 0.2,
+        };
+        assert_eq!(
+            processor.transform_event(note_off, Some(MidiChannel::Channel4), settings),
+            note_off
+        );
+    }
+}
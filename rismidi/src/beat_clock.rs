@@ -0,0 +1,190 @@
+use crate::NoteValue;
+
+/// Keeps track of the musical position, so that events can be aligned to the host's tempo.
+///
+/// While the host's transport is playing, the clock follows the host's position. Otherwise, it
+/// keeps running on its own at the host's tempo.
+///
+/// # Examples
+///
+/// ```
+/// use rismidi::{BeatClock, NoteValue, Step};
+///
+/// let mut clock = BeatClock::new();
+///
+/// // At 120 BPM and 48 kHz, a beat lasts 24000 samples.
+/// clock.update(true, Some(0.75), Some(120.0), 48000.0);
+/// let steps: Vec<Step> = clock.steps(NoteValue::Quarter, 12000).collect();
+/// assert_eq!(steps, vec![Step { index: 1, timing: 6000 }]);
+///
+/// clock.advance(12000);
+/// assert_eq!(clock.pos_beats(), 1.25);
+/// ```
+#[derive(Clone, Debug)]
+pub struct BeatClock {
+    /// The position at the start of the current buffer, in beats.
+    pos_beats: f64,
+
+    samples_per_beat: f64,
+}
+
+/// The start of a step of a given [`NoteValue`], see [`BeatClock::steps`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Step {
+    /// The number of the step, counted from the start of the song. The first step of the song
+    /// has index 0.
+    pub index: i64,
+
+    /// The step's start in samples, relative to the start of the current buffer.
+    pub timing: u32,
+}
+
+/// An iterator over the [`Step`]s within a buffer, see [`BeatClock::steps`].
+#[derive(Clone, Debug)]
+pub struct Steps {
+    next_index: i64,
+    end_index: i64,
+    step_beats: f64,
+    pos_beats: f64,
+    samples_per_beat: f64,
+    num_samples: u32,
+}
+
+impl BeatClock {
+    /// Creates a new [`BeatClock`] at the start of the song.
+    pub fn new() -> Self {
+        Self {
+            pos_beats: 0.0,
+            samples_per_beat: 60.0 / NoteValue::FALLBACK_TEMPO * 44100.0,
+        }
+    }
+
+    /// Updates the clock with the host's transport information. This should be called at the start
+    /// of every buffer.
+    pub fn update(
+        &mut self,
+        playing: bool,
+        host_pos_beats: Option<f64>,
+        tempo: Option<f64>,
+        sample_rate: f32,
+    ) {
+        let tempo = tempo.unwrap_or(NoteValue::FALLBACK_TEMPO);
+        self.samples_per_beat = 60.0 / tempo * sample_rate as f64;
+
+        if let (true, Some(pos_beats)) = (playing, host_pos_beats) {
+            self.pos_beats = pos_beats;
+        }
+    }
+
+    /// Moves the clock to the start of the next buffer, given that the current one is
+    /// `num_samples` long.
+    pub fn advance(&mut self, num_samples: u32) {
+        self.pos_beats += num_samples as f64 / self.samples_per_beat;
+    }
+
+    /// The position at the start of the current buffer, in beats.
+    pub fn pos_beats(&self) -> f64 {
+        self.pos_beats
+    }
+
+    /// The number of samples per beat at the current tempo.
+    pub fn samples_per_beat(&self) -> f64 {
+        self.samples_per_beat
+    }
+
+    /// Converts a duration in beats into samples at the current tempo.
+    pub fn beats_to_samples(&self, beats: f64) -> f64 {
+        beats * self.samples_per_beat
+    }
+
+    /// Iterates over all steps of length `note_value` which start within the current buffer of
+    /// `num_samples` samples.
+    pub fn steps(&self, note_value: NoteValue, num_samples: u32) -> Steps {
+        let step_beats = note_value.beats();
+        let end_beats = self.pos_beats + num_samples as f64 / self.samples_per_beat;
+
+        Steps {
+            next_index: (self.pos_beats / step_beats).ceil() as i64,
+            end_index: (end_beats / step_beats).ceil() as i64,
+            step_beats,
+            pos_beats: self.pos_beats,
+            samples_per_beat: self.samples_per_beat,
+            num_samples,
+        }
+    }
+}
+
+impl Default for BeatClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Iterator for Steps {
+    type Item = Step;
+
+    fn next(&mut self) -> Option<Step> {
+        if self.next_index >= self.end_index {
+            return None;
+        }
+
+        let index = self.next_index;
+        self.next_index += 1;
+
+        let offset_beats = index as f64 * self.step_beats - self.pos_beats;
+        let timing = (offset_beats * self.samples_per_beat).floor().max(0.0) as u32;
+
+        Some(Step {
+            index,
+            timing: timing.min(self.num_samples.saturating_sub(1)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_keeps_running_when_transport_is_stopped() {
+        let mut clock = BeatClock::new();
+        clock.update(true, Some(4.0), Some(60.0), 1000.0);
+        clock.advance(500);
+
+        clock.update(false, Some(0.0), Some(60.0), 1000.0);
+        assert_eq!(clock.pos_beats(), 4.5);
+
+        let steps: Vec<Step> = clock.steps(NoteValue::Eighth, 1000).collect();
+        assert_eq!(
+            steps,
+            vec![
+                Step {
+                    index: 9,
+                    timing: 0
+                },
+                Step {
+                    index: 10,
+                    timing: 500
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn steps_at_the_end_of_a_buffer_are_not_repeated() {
+        let mut clock = BeatClock::new();
+        clock.update(true, Some(0.0), Some(60.0), 1000.0);
+
+        let mut indices = Vec::new();
+        for _ in 0..4 {
+            indices.extend(
+                clock
+                    .steps(NoteValue::Sixteenth, 300)
+                    .map(|step| step.index),
+            );
+            clock.advance(300);
+        }
+
+        assert_eq!(indices, vec![0, 1, 2, 3, 4]);
+    }
+}
@@ -19,6 +19,9 @@ pub enum RismidiError {
     /// message does not have a channel
     MsgHasNoChannel,
 
+    /// no more events can be scheduled
+    SchedulerFull,
+
     /// the plugin host has returned an unknown value for user input
     UnknownInput,
 }
@@ -0,0 +1,139 @@
+use nih_plug::midi::NoteEvent;
+
+/// This trait represents the fact that a type is placed at a specific point in time within a
+/// buffer.
+pub trait HasTiming {
+    /// Sets the timing (in samples, relative to the start of the buffer).
+    fn set_timing(&mut self, new_timing: u32);
+
+    /// Returns the same event, but with its timing overwritten.
+    fn with_timing(self, timing: u32) -> Self;
+}
+
+impl HasTiming for NoteEvent {
+    fn set_timing(&mut self, new_timing: u32) {
+        match self {
+            NoteEvent::NoteOn {
+                timing,
+                voice_id: _,
+                channel: _,
+                note: _,
+                velocity: _,
+            } => *timing = new_timing,
+            NoteEvent::NoteOff {
+                timing,
+                voice_id: _,
+                channel: _,
+                note: _,
+                velocity: _,
+            } => *timing = new_timing,
+            NoteEvent::Choke {
+                timing,
+                voice_id: _,
+                channel: _,
+                note: _,
+            } => *timing = new_timing,
+            NoteEvent::VoiceTerminated {
+                timing,
+                voice_id: _,
+                channel: _,
+                note: _,
+            } => *timing = new_timing,
+            NoteEvent::PolyModulation {
+                timing,
+                voice_id: _,
+                poly_modulation_id: _,
+                normalized_offset: _,
+            } => *timing = new_timing,
+            NoteEvent::MonoAutomation {
+                timing,
+                poly_modulation_id: _,
+                normalized_value: _,
+            } => *timing = new_timing,
+            NoteEvent::PolyPressure {
+                timing,
+                voice_id: _,
+                channel: _,
+                note: _,
+                pressure: _,
+            } => *timing = new_timing,
+            NoteEvent::PolyVolume {
+                timing,
+                voice_id: _,
+                channel: _,
+                note: _,
+                gain: _,
+            } => *timing = new_timing,
+            NoteEvent::PolyPan {
+                timing,
+                voice_id: _,
+                channel: _,
+                note: _,
+                pan: _,
+            } => *timing = new_timing,
+            NoteEvent::PolyTuning {
+                timing,
+                voice_id: _,
+                channel: _,
+                note: _,
+                tuning: _,
+            } => *timing = new_timing,
+            NoteEvent::PolyVibrato {
+                timing,
+                voice_id: _,
+                channel: _,
+                note: _,
+                vibrato: _,
+            } => *timing = new_timing,
+            NoteEvent::PolyExpression {
+                timing,
+                voice_id: _,
+                channel: _,
+                note: _,
+                expression: _,
+            } => *timing = new_timing,
+            NoteEvent::PolyBrightness {
+                timing,
+                voice_id: _,
+                channel: _,
+                note: _,
+                brightness: _,
+            } => *timing = new_timing,
+            NoteEvent::MidiChannelPressure {
+                timing,
+                channel: _,
+                pressure: _,
+            } => *timing = new_timing,
+            NoteEvent::MidiPitchBend {
+                timing,
+                channel: _,
+                value: _,
+            } => *timing = new_timing,
+            NoteEvent::MidiCC {
+                timing,
+                channel: _,
+                cc: _,
+                value: _,
+            } => *timing = new_timing,
+            NoteEvent::MidiProgramChange {
+                timing,
+                channel: _,
+                program: _,
+            } => *timing = new_timing,
+            // Every event type that nih_plug knows about has a timing, so this is only reached for
+            // event types that have been added after this was written.
+            _ => {
+                let old_timing = self.timing();
+                if old_timing >= new_timing {
+                    self.subtract_timing(old_timing - new_timing);
+                }
+            }
+        }
+    }
+
+    fn with_timing(mut self, timing: u32) -> Self {
+        self.set_timing(timing);
+
+        self
+    }
+}
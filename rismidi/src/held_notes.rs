@@ -0,0 +1,235 @@
+use crate::{MidiChannel, NUM_MIDI_CHANNELS, NUM_MIDI_NOTES};
+use nih_plug::midi::NoteEvent;
+
+/// A note which is currently held.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HeldNote {
+    /// The channel the note was played on.
+    pub channel: MidiChannel,
+
+    /// The MIDI note number.
+    pub note: u8,
+
+    /// The (normalized) velocity of the note-on.
+    pub velocity: f32,
+}
+
+/// Keeps track of the notes that are currently held, in the order they were played.
+///
+/// [`HeldNotes`] never allocates after it has been created, so it is safe to use from
+/// [`nih_plug::prelude::Plugin::process`].
+///
+/// # Examples
+///
+/// ```
+/// use nih_plug::prelude::*;
+/// use rismidi::{HeldNotes, MidiChannel};
+///
+/// let mut held_notes = HeldNotes::new();
+/// held_notes.handle_event(&NoteEvent::NoteOn {
+///     timing: 0,
+///     voice_id: None,
+///     channel: 0,
+///     note: 60,
+///     velocity: 0.8,
+/// });
+/// assert!(held_notes.is_held(MidiChannel::Channel1, 60));
+///
+/// held_notes.handle_event(&NoteEvent::NoteOff {
+///     timing: 10,
+///     voice_id: None,
+///     channel: 0,
+///     note: 60,
+///     velocity: 0.0,
+/// });
+/// assert!(held_notes.is_empty());
+/// ```
+pub struct HeldNotes {
+    /// The held notes, in the order they were played.
+    notes: Vec<HeldNote>,
+
+    /// One bit per note and channel, to quickly find out whether a note is held.
+    is_held: [u128; NUM_MIDI_CHANNELS as usize],
+}
+
+impl HeldNotes {
+    /// Creates a new [`HeldNotes`] without any held notes.
+    pub fn new() -> Self {
+        Self {
+            notes: Vec::with_capacity(NUM_MIDI_NOTES as usize * NUM_MIDI_CHANNELS as usize),
+            is_held: [0; NUM_MIDI_CHANNELS as usize],
+        }
+    }
+
+    /// Updates the held notes according to a note-on or note-off event. All other events are
+    /// ignored.
+    pub fn handle_event(&mut self, event: &NoteEvent) {
+        match *event {
+            NoteEvent::NoteOn {
+                timing: _,
+                voice_id: _,
+                channel,
+                note,
+                velocity,
+            } => {
+                if let Ok(channel) = MidiChannel::try_from_0_based(channel.into()) {
+                    self.note_on(channel, note, velocity);
+                }
+            }
+            NoteEvent::NoteOff {
+                timing: _,
+                voice_id: _,
+                channel,
+                note,
+                velocity: _,
+            } => {
+                if let Ok(channel) = MidiChannel::try_from_0_based(channel.into()) {
+                    self.note_off(channel, note);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Marks a note as held. Returns `false` if it was already held; in that case, it is moved
+    /// to the end of the playing order and its velocity is updated. Invalid notes are ignored.
+    pub fn note_on(&mut self, channel: MidiChannel, note: u8, velocity: f32) -> bool {
+        if note >= NUM_MIDI_NOTES {
+            return false;
+        }
+
+        let was_held = self.note_off(channel, note).is_some();
+
+        self.notes.push(HeldNote {
+            channel,
+            note,
+            velocity,
+        });
+        self.is_held[channel.to_0_based() as usize] |= 1 << note;
+
+        !was_held
+    }
+
+    /// Marks a note as released. Returns the released note, if it was held.
+    pub fn note_off(&mut self, channel: MidiChannel, note: u8) -> Option<HeldNote> {
+        if !self.is_held(channel, note) {
+            return None;
+        }
+
+        self.is_held[channel.to_0_based() as usize] &= !(1 << note);
+        let index = self
+            .notes
+            .iter()
+            .position(|held| held.channel == channel && held.note == note)?;
+
+        Some(self.notes.remove(index))
+    }
+
+    /// Returns `true` if the given note is currently held.
+    pub fn is_held(&self, channel: MidiChannel, note: u8) -> bool {
+        note < NUM_MIDI_NOTES && self.is_held[channel.to_0_based() as usize] & (1 << note) != 0
+    }
+
+    /// Iterates over all held notes, in the order they were played.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &HeldNote> + '_ {
+        self.notes.iter()
+    }
+
+    /// Iterates over the held notes on the given channel, in the order they were played.
+    pub fn on_channel(
+        &self,
+        channel: MidiChannel,
+    ) -> impl DoubleEndedIterator<Item = &HeldNote> + '_ {
+        self.notes
+            .iter()
+            .filter(move |held| held.channel == channel)
+    }
+
+    /// The number of held notes.
+    pub fn len(&self) -> usize {
+        self.notes.len()
+    }
+
+    /// Returns `true` if no notes are held.
+    pub fn is_empty(&self) -> bool {
+        self.notes.is_empty()
+    }
+
+    /// Releases all notes on the given channel.
+    pub fn release_channel(&mut self, channel: MidiChannel) {
+        self.notes.retain(|held| held.channel != channel);
+        self.is_held[channel.to_0_based() as usize] = 0;
+    }
+
+    /// Releases all notes.
+    pub fn clear(&mut self) {
+        self.notes.clear();
+        self.is_held = [0; NUM_MIDI_CHANNELS as usize];
+    }
+}
+
+impl Default for HeldNotes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notes_are_kept_in_playing_order() {
+        let mut held_notes = HeldNotes::new();
+        assert!(held_notes.note_on(MidiChannel::Channel1, 64, 1.0));
+        assert!(held_notes.note_on(MidiChannel::Channel2, 60, 1.0));
+        assert!(held_notes.note_on(MidiChannel::Channel1, 67, 1.0));
+        assert!(!held_notes.note_on(MidiChannel::Channel1, 64, 0.5));
+
+        let notes: Vec<(MidiChannel, u8)> = held_notes
+            .iter()
+            .map(|held| (held.channel, held.note))
+            .collect();
+        assert_eq!(
+            notes,
+            vec![
+                (MidiChannel::Channel2, 60),
+                (MidiChannel::Channel1, 67),
+                (MidiChannel::Channel1, 64)
+            ]
+        );
+    }
+
+    #[test]
+    fn notes_are_tracked_per_channel() {
+        let mut held_notes = HeldNotes::new();
+        held_notes.note_on(MidiChannel::Channel1, 127, 1.0);
+        held_notes.note_on(MidiChannel::Channel16, 0, 1.0);
+
+        assert_eq!(held_notes.note_off(MidiChannel::Channel2, 127), None);
+        assert!(held_notes.is_held(MidiChannel::Channel1, 127));
+        assert!(!held_notes.is_held(MidiChannel::Channel1, 0));
+        assert_eq!(
+            held_notes.note_off(MidiChannel::Channel16, 0),
+            Some(HeldNote {
+                channel: MidiChannel::Channel16,
+                note: 0,
+                velocity: 1.0
+            })
+        );
+        assert_eq!(held_notes.len(), 1);
+    }
+
+    #[test]
+    fn channels_can_be_released() {
+        let mut held_notes = HeldNotes::new();
+        held_notes.note_on(MidiChannel::Channel1, 60, 1.0);
+        held_notes.note_on(MidiChannel::Channel2, 60, 1.0);
+        held_notes.note_on(MidiChannel::Channel1, 64, 1.0);
+
+        held_notes.release_channel(MidiChannel::Channel1);
+        assert!(!held_notes.is_held(MidiChannel::Channel1, 60));
+        assert!(held_notes.is_held(MidiChannel::Channel2, 60));
+        assert_eq!(held_notes.len(), 1);
+    }
+}
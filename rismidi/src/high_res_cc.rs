@@ -0,0 +1,280 @@
+use crate::{MidiChannel, MAX_14_BIT, MAX_7_BIT, NUM_HIGH_RES_CCS, NUM_MIDI_CHANNELS};
+use nih_plug::midi::NoteEvent;
+
+/// The highest value of a 14-bit MIDI controller.
+pub const MAX_HIGH_RES_CC_VALUE: u16 = MAX_14_BIT;
+
+/// The value of a 14-bit MIDI controller, combined from its MSB and LSB.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HighResCc {
+    /// The timing of the event which completed the value.
+    pub timing: u32,
+
+    /// The channel the controller was sent on.
+    pub channel: MidiChannel,
+
+    /// The number of the controller carrying the MSB, from 0 to 31. The LSB is carried by the
+    /// controller 32 numbers above.
+    pub cc: u8,
+
+    /// The raw value, from 0 to [`MAX_HIGH_RES_CC_VALUE`].
+    pub value: u16,
+}
+
+impl HighResCc {
+    /// The value, normalized to the range from 0 to 1.
+    pub fn normalized_value(&self) -> f32 {
+        self.value as f32 / MAX_HIGH_RES_CC_VALUE as f32
+    }
+}
+
+/// Gets the number of the controller carrying the LSB for the controller `msb_cc`, if the MIDI
+/// specification pairs it with one.
+///
+/// # Examples
+///
+/// ```
+/// use rismidi::lsb_cc;
+///
+/// assert_eq!(lsb_cc(1), Some(33));
+/// assert_eq!(lsb_cc(64), None);
+/// ```
+pub fn lsb_cc(msb_cc: u8) -> Option<u8> {
+    (msb_cc < NUM_HIGH_RES_CCS).then_some(msb_cc + NUM_HIGH_RES_CCS)
+}
+
+/// Converts a normalized value from nih_plug into a raw 7-bit MIDI value.
+fn normalized_to_7_bit(value: f32) -> u8 {
+    (value * MAX_7_BIT as f32)
+        .round()
+        .clamp(0.0, MAX_7_BIT as f32) as u8
+}
+
+/// Combines the MSB and LSB of 14-bit MIDI controllers (CC 0–31 and 32–63) into a single value.
+///
+/// The decoder keeps track of the last MSB and LSB for every channel and controller. As required
+/// by the MIDI specification, receiving an MSB resets the LSB to 0. A new value is reported for
+/// every MSB and LSB, so controllers which only send the MSB still work as expected.
+///
+/// # Examples
+///
+/// ```
+/// use nih_plug::prelude::*;
+/// use rismidi::{HighResCcDecoder, MidiChannel};
+///
+/// let mut decoder = HighResCcDecoder::new();
+/// let msb = NoteEvent::MidiCC {
+///     timing: 0,
+///     channel: 0,
+///     cc: 1,
+///     value: 64.0 / 127.0,
+/// };
+/// let lsb = NoteEvent::MidiCC {
+///     timing: 0,
+///     channel: 0,
+///     cc: 33,
+///     value: 1.0 / 127.0,
+/// };
+///
+/// assert_eq!(decoder.handle_event(&msb).unwrap().value, 8192);
+/// let decoded = decoder.handle_event(&lsb).unwrap();
+/// assert_eq!(decoded.channel, MidiChannel::Channel1);
+/// assert_eq!(decoded.cc, 1);
+/// assert_eq!(decoded.value, 8193);
+/// ```
+pub struct HighResCcDecoder {
+    /// The last MSB and LSB, per channel and controller.
+    values: [[(u8, u8); NUM_HIGH_RES_CCS as usize]; NUM_MIDI_CHANNELS as usize],
+}
+
+impl HighResCcDecoder {
+    /// Creates a new [`HighResCcDecoder`], assuming all controllers to be 0.
+    pub fn new() -> Self {
+        Self {
+            values: [[(0, 0); NUM_HIGH_RES_CCS as usize]; NUM_MIDI_CHANNELS as usize],
+        }
+    }
+
+    /// Updates the decoder according to a CC event. If the event is the MSB or LSB of a 14-bit
+    /// controller, the controller's new value is returned. All other events are ignored.
+    pub fn handle_event(&mut self, event: &NoteEvent) -> Option<HighResCc> {
+        let NoteEvent::MidiCC {
+            timing,
+            channel,
+            cc,
+            value,
+        } = *event
+        else {
+            return None;
+        };
+
+        let channel = MidiChannel::try_from_0_based(channel.into()).ok()?;
+        let msb_cc = cc % NUM_HIGH_RES_CCS;
+        let (msb, lsb) = match cc / NUM_HIGH_RES_CCS {
+            0 => (normalized_to_7_bit(value), 0),
+            1 => (
+                self.values[channel.to_0_based() as usize][msb_cc as usize].0,
+                normalized_to_7_bit(value),
+            ),
+            _ => return None,
+        };
+        self.values[channel.to_0_based() as usize][msb_cc as usize] = (msb, lsb);
+
+        Some(HighResCc {
+            timing,
+            channel,
+            cc: msb_cc,
+            value: (msb as u16) << 7 | lsb as u16,
+        })
+    }
+
+    /// Gets the last value of a 14-bit controller.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cc` is not a 14-bit controller (0–31).
+    pub fn value(&self, channel: MidiChannel, cc: u8) -> u16 {
+        let (msb, lsb) = self.values[channel.to_0_based() as usize][cc as usize];
+        (msb as u16) << 7 | lsb as u16
+    }
+
+    /// Forgets all values, e.g. when the plugin is reset.
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+impl Default for HighResCcDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Splits 14-bit controller values into their MSB and LSB events.
+///
+/// The MSB is always sent before the LSB, as receivers reset the LSB whenever they get an MSB. If
+/// only the LSB of a controller changes, the MSB is not sent again.
+///
+/// # Examples
+///
+/// ```
+/// use nih_plug::prelude::*;
+/// use rismidi::{HighResCcEncoder, MidiChannel};
+///
+/// let mut encoder = HighResCcEncoder::new();
+/// let mut events = Vec::new();
+/// encoder.encode(0, MidiChannel::Channel1, 1, 8193, |event| events.push(event));
+/// encoder.encode(10, MidiChannel::Channel1, 1, 8194, |event| events.push(event));
+///
+/// let ccs: Vec<_> = events
+///     .iter()
+///     .map(|event| match event {
+///         NoteEvent::MidiCC { cc, value, .. } => (*cc, (value * 127.0).round() as u8),
+///         _ => unreachable!(),
+///     })
+///     .collect();
+/// assert_eq!(ccs, [(1, 64), (33, 1), (33, 2)]);
+/// ```
+pub struct HighResCcEncoder {
+    /// The last MSB sent, per channel and controller.
+    sent_msb: [[Option<u8>; NUM_HIGH_RES_CCS as usize]; NUM_MIDI_CHANNELS as usize],
+}
+
+impl HighResCcEncoder {
+    /// Creates a new [`HighResCcEncoder`] which has not sent anything yet.
+    pub fn new() -> Self {
+        Self {
+            sent_msb: [[None; NUM_HIGH_RES_CCS as usize]; NUM_MIDI_CHANNELS as usize],
+        }
+    }
+
+    /// Passes the events for setting the 14-bit controller `cc` to `value` to `send`. Values
+    /// above [`MAX_HIGH_RES_CC_VALUE`] are limited to it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cc` is not a 14-bit controller (0–31).
+    pub fn encode(
+        &mut self,
+        timing: u32,
+        channel: MidiChannel,
+        cc: u8,
+        value: u16,
+        mut send: impl FnMut(NoteEvent),
+    ) {
+        let lsb_cc = lsb_cc(cc).expect("only CC 0-31 have an LSB");
+        let value = value.min(MAX_HIGH_RES_CC_VALUE);
+        let msb = (value >> 7) as u8;
+        let lsb = value & MAX_7_BIT;
+
+        let sent_msb = &mut self.sent_msb[channel.to_0_based() as usize][cc as usize];
+        if *sent_msb != Some(msb) {
+            *sent_msb = Some(msb);
+            send(NoteEvent::MidiCC {
+                timing,
+                channel: channel.to_0_based(),
+                cc,
+                value: msb as f32 / MAX_7_BIT as f32,
+            });
+        }
+
+        send(NoteEvent::MidiCC {
+            timing,
+            channel: channel.to_0_based(),
+            cc: lsb_cc,
+            value: lsb as f32 / MAX_7_BIT as f32,
+        });
+    }
+
+    /// Forgets which values have been sent, so that the next value is sent in full.
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+impl Default for HighResCcEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cc(cc: u8, raw: u8) -> NoteEvent {
+        NoteEvent::MidiCC {
+            timing: 0,
+            channel: 0,
+            cc,
+            value: raw as f32 / MAX_7_BIT as f32,
+        }
+    }
+
+    #[test]
+    fn msb_resets_lsb() {
+        let mut decoder = HighResCcDecoder::new();
+        decoder.handle_event(&cc(7, 100));
+        decoder.handle_event(&cc(39, 42));
+        assert_eq!(decoder.value(MidiChannel::Channel1, 7), 100 << 7 | 42);
+
+        let decoded = decoder.handle_event(&cc(7, 101)).unwrap();
+        assert_eq!(decoded.value, 101 << 7);
+        assert_eq!(decoder.handle_event(&cc(64, 127)), None);
+        assert_eq!(decoder.value(MidiChannel::Channel2, 7), 0);
+    }
+
+    #[test]
+    fn encoded_values_are_decoded_again() {
+        let mut encoder = HighResCcEncoder::new();
+        let mut decoder = HighResCcDecoder::new();
+
+        for value in [0, 1, 127, 128, 8192, 12345, MAX_HIGH_RES_CC_VALUE] {
+            let mut decoded = None;
+            encoder.encode(0, MidiChannel::Channel3, 5, value, |event| {
+                decoded = decoder.handle_event(&event);
+            });
+            assert_eq!(decoded.map(|decoded| decoded.value), Some(value));
+        }
+    }
+}
@@ -2,12 +2,37 @@
 #![deny(rustdoc::broken_intra_doc_links)]
 #![doc = include_str!("../README.md")]
 
+mod beat_clock;
 mod error;
 mod has_channel;
+mod has_timing;
+mod held_notes;
+mod high_res_cc;
 mod midi;
+mod note_value;
+mod parameter_number;
 pub mod params;
+mod random;
+mod scheduler;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 
+pub use beat_clock::{BeatClock, Step, Steps};
 pub use error::RismidiError;
 pub use has_channel::HasChannel;
-pub use midi::{constants::*, MidiChannel};
+pub use has_timing::HasTiming;
+pub use held_notes::{HeldNote, HeldNotes};
+pub use high_res_cc::{
+    lsb_cc, HighResCc, HighResCcDecoder, HighResCcEncoder, MAX_HIGH_RES_CC_VALUE,
+};
+pub use midi::{
+    constants::*, controller_by_name, controller_name, MidiChannel, Quantization, Scale, ScaleType,
+};
+pub use note_value::NoteValue;
+pub use parameter_number::{
+    is_parameter_number_cc, DataChange, ParameterChange, ParameterNumber, ParameterNumberDecoder,
+    ParameterNumberEncoder,
+};
 pub use params::OptionalMidiChannelParam;
+pub use random::Rng;
+pub use scheduler::EventScheduler;
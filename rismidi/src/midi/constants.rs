@@ -1,5 +1,25 @@
 /// The number of MIDI notes existing according to the MIDI v1 specification.
 pub const NUM_MIDI_NOTES: u8 = 128;
 
+/// The number of MIDI controllers (CCs) existing according to the MIDI v1 specification.
+pub const NUM_MIDI_CCS: u8 = 128;
+
 /// The number of MIDI channels existing according to the MIDI v1 specification.
 pub const NUM_MIDI_CHANNELS: u8 = 16;
+
+/// The number of pitch classes (C, C#, D, ...) in an octave.
+pub const NUM_PITCH_CLASSES: u8 = 12;
+
+/// The number of MIDI controllers which can be paired with a second controller for sending 14-bit
+/// values (CC 0–31, paired with CC 32–63).
+pub const NUM_HIGH_RES_CCS: u8 = 32;
+
+/// The highest raw value of a 7-bit MIDI value, e.g. a CC, velocity or pressure.
+pub const MAX_7_BIT: u16 = 127;
+
+/// The highest raw value of a 14-bit MIDI value, i.e. pitch bend or a CC sent as MSB and LSB.
+pub const MAX_14_BIT: u16 = 16383;
+
+/// The lowest normalized velocity of a note-on. Quieter note-ons would be sent with a MIDI velocity
+/// of 0, which devices interpret as a note-off.
+pub const MIN_VELOCITY: f32 = 1.0 / 127.0;
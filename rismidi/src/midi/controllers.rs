@@ -0,0 +1,166 @@
+use crate::NUM_MIDI_CCS;
+
+/// The names of the MIDI controllers, as assigned by the MIDI v1 specification.
+const CONTROLLER_NAMES: [Option<&str>; NUM_MIDI_CCS as usize] = [
+    Some("Bank Select"),
+    Some("Mod Wheel"),
+    Some("Breath"),
+    None,
+    Some("Foot"),
+    Some("Portamento Time"),
+    Some("Data Entry"),
+    Some("Volume"),
+    Some("Balance"),
+    None,
+    Some("Pan"),
+    Some("Expression"),
+    Some("Effect 1"),
+    Some("Effect 2"),
+    None,
+    None,
+    Some("General Purpose 1"),
+    Some("General Purpose 2"),
+    Some("General Purpose 3"),
+    Some("General Purpose 4"),
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some("Bank Select LSB"),
+    Some("Mod Wheel LSB"),
+    Some("Breath LSB"),
+    None,
+    Some("Foot LSB"),
+    Some("Portamento Time LSB"),
+    Some("Data Entry LSB"),
+    Some("Volume LSB"),
+    Some("Balance LSB"),
+    None,
+    Some("Pan LSB"),
+    Some("Expression LSB"),
+    Some("Effect 1 LSB"),
+    Some("Effect 2 LSB"),
+    None,
+    None,
+    Some("General Purpose 1 LSB"),
+    Some("General Purpose 2 LSB"),
+    Some("General Purpose 3 LSB"),
+    Some("General Purpose 4 LSB"),
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some("Sustain"),
+    Some("Portamento"),
+    Some("Sostenuto"),
+    Some("Soft Pedal"),
+    Some("Legato"),
+    Some("Hold 2"),
+    Some("Sound Variation"),
+    Some("Resonance"),
+    Some("Release Time"),
+    Some("Attack Time"),
+    Some("Cutoff"),
+    Some("Decay Time"),
+    Some("Vibrato Rate"),
+    Some("Vibrato Depth"),
+    Some("Vibrato Delay"),
+    Some("Sound Controller 10"),
+    Some("General Purpose 5"),
+    Some("General Purpose 6"),
+    Some("General Purpose 7"),
+    Some("General Purpose 8"),
+    Some("Portamento Control"),
+    None,
+    None,
+    None,
+    Some("High Resolution Velocity"),
+    None,
+    None,
+    Some("Reverb"),
+    Some("Tremolo"),
+    Some("Chorus"),
+    Some("Detune"),
+    Some("Phaser"),
+    Some("Data Increment"),
+    Some("Data Decrement"),
+    Some("NRPN LSB"),
+    Some("NRPN MSB"),
+    Some("RPN LSB"),
+    Some("RPN MSB"),
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some("All Sound Off"),
+    Some("Reset All Controllers"),
+    Some("Local Control"),
+    Some("All Notes Off"),
+    Some("Omni Off"),
+    Some("Omni On"),
+    Some("Mono On"),
+    Some("Poly On"),
+];
+
+/// Gets the name of a MIDI controller, if the MIDI specification assigns one.
+///
+/// # Examples
+///
+/// ```
+/// use rismidi::controller_name;
+///
+/// assert_eq!(controller_name(64), Some("Sustain"));
+/// assert_eq!(controller_name(3), None);
+/// assert_eq!(controller_name(128), None);
+/// ```
+pub fn controller_name(cc: u8) -> Option<&'static str> {
+    CONTROLLER_NAMES.get(cc as usize).copied().flatten()
+}
+
+/// Finds a MIDI controller by its name, ignoring case.
+///
+/// # Examples
+///
+/// ```
+/// use rismidi::controller_by_name;
+///
+/// assert_eq!(controller_by_name("mod wheel"), Some(1));
+/// assert_eq!(controller_by_name("Wobble"), None);
+/// ```
+pub fn controller_by_name(name: &str) -> Option<u8> {
+    let name = name.trim();
+    CONTROLLER_NAMES
+        .iter()
+        .position(|known| matches!(known, Some(known) if known.eq_ignore_ascii_case(name)))
+        .map(|cc| cc as u8)
+}
@@ -1,4 +1,8 @@
 pub mod constants;
+pub mod controllers;
 pub mod midi_channel;
+pub mod scale;
 
+pub use controllers::{controller_by_name, controller_name};
 pub use midi_channel::MidiChannel;
+pub use scale::{Quantization, Scale, ScaleType};
@@ -0,0 +1,257 @@
+use crate::{RismidiError, NUM_MIDI_NOTES, NUM_PITCH_CLASSES};
+use nih_plug::prelude::*;
+
+/// A set of pitch classes, e.g. the notes of C major.
+///
+/// The notes of a scale are given as a 12-bit mask relative to its root: bit 0 stands for the
+/// root itself, bit 1 for the minor second above it, and so on.
+///
+/// # Examples
+///
+/// ```
+/// use rismidi::{Quantization, Scale, ScaleType};
+///
+/// // D dorian
+/// let scale = Scale::new(2, ScaleType::Dorian.mask().unwrap()).unwrap();
+/// assert!(scale.contains(65)); // F
+/// assert!(!scale.contains(66)); // F#
+/// assert_eq!(scale.quantize(66, Quantization::Up), Some(67));
+/// assert_eq!(scale.quantize(66, Quantization::Drop), None);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Scale {
+    root: u8,
+    mask: u16,
+}
+
+/// The scales known to rismidi.
+/// It can be used as a plugin parameter with [`nih_plug::params::EnumParam`].
+#[derive(Enum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScaleType {
+    /// The major scale (ionian mode)
+    #[id = "major"]
+    #[name = "Major"]
+    Major,
+
+    /// The natural minor scale (aeolian mode)
+    #[id = "minor"]
+    #[name = "Minor"]
+    Minor,
+
+    /// The harmonic minor scale
+    #[id = "harmonic_minor"]
+    #[name = "Harmonic Minor"]
+    HarmonicMinor,
+
+    /// The (ascending) melodic minor scale
+    #[id = "melodic_minor"]
+    #[name = "Melodic Minor"]
+    MelodicMinor,
+
+    /// The dorian mode
+    #[id = "dorian"]
+    #[name = "Dorian"]
+    Dorian,
+
+    /// The phrygian mode
+    #[id = "phrygian"]
+    #[name = "Phrygian"]
+    Phrygian,
+
+    /// The lydian mode
+    #[id = "lydian"]
+    #[name = "Lydian"]
+    Lydian,
+
+    /// The mixolydian mode
+    #[id = "mixolydian"]
+    #[name = "Mixolydian"]
+    Mixolydian,
+
+    /// The locrian mode
+    #[id = "locrian"]
+    #[name = "Locrian"]
+    Locrian,
+
+    /// The major pentatonic scale
+    #[id = "major_pentatonic"]
+    #[name = "Major Pentatonic"]
+    MajorPentatonic,
+
+    /// The minor pentatonic scale
+    #[id = "minor_pentatonic"]
+    #[name = "Minor Pentatonic"]
+    MinorPentatonic,
+
+    /// A user-defined scale
+    #[id = "custom"]
+    #[name = "Custom"]
+    Custom,
+}
+
+/// How notes outside of a [`Scale`] are moved into it, see [`Scale::quantize`].
+/// It can be used as a plugin parameter with [`nih_plug::params::EnumParam`].
+#[derive(Enum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Quantization {
+    /// Move to the closest note of the scale. Ties are resolved downwards.
+    #[id = "nearest"]
+    #[name = "Nearest"]
+    Nearest,
+
+    /// Move to the next higher note of the scale.
+    #[id = "up"]
+    #[name = "Up"]
+    Up,
+
+    /// Move to the next lower note of the scale.
+    #[id = "down"]
+    #[name = "Down"]
+    Down,
+
+    /// Drop notes outside of the scale.
+    #[id = "drop"]
+    #[name = "Drop"]
+    Drop,
+}
+
+impl ScaleType {
+    /// Gets the notes of the scale as a mask relative to its root, see [`Scale`]. Returns
+    /// [`None`] for [`ScaleType::Custom`].
+    pub fn mask(&self) -> Option<u16> {
+        let mask = match self {
+            ScaleType::Major => 0b1010_1011_0101,
+            ScaleType::Minor => 0b0101_1010_1101,
+            ScaleType::HarmonicMinor => 0b1001_1010_1101,
+            ScaleType::MelodicMinor => 0b1010_1010_1101,
+            ScaleType::Dorian => 0b0110_1010_1101,
+            ScaleType::Phrygian => 0b0101_1010_1011,
+            ScaleType::Lydian => 0b1010_1101_0101,
+            ScaleType::Mixolydian => 0b0110_1011_0101,
+            ScaleType::Locrian => 0b0101_0110_1011,
+            ScaleType::MajorPentatonic => 0b0010_1001_0101,
+            ScaleType::MinorPentatonic => 0b0100_1010_1001,
+            ScaleType::Custom => return None,
+        };
+
+        Some(mask)
+    }
+}
+
+impl Scale {
+    /// A mask containing all 12 pitch classes.
+    pub const CHROMATIC: u16 = 0b1111_1111_1111;
+
+    /// Creates a new [`Scale`]. `root` is the pitch class of the root, where 0 is C. Only the
+    /// lower 12 bits of `mask` are used.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rismidi::Scale;
+    ///
+    /// assert!(Scale::new(11, Scale::CHROMATIC).is_ok());
+    /// assert!(Scale::new(12, Scale::CHROMATIC).is_err());
+    /// ```
+    pub fn new(root: u8, mask: u16) -> Result<Scale, RismidiError> {
+        if root < NUM_PITCH_CLASSES {
+            Ok(Scale {
+                root,
+                mask: mask & Self::CHROMATIC,
+            })
+        } else {
+            Err(RismidiError::UIntOutOfBounds {
+                found: root.into(),
+                min: 0,
+                max: (NUM_PITCH_CLASSES - 1).into(),
+            })
+        }
+    }
+
+    /// The pitch class of the root, where 0 is C.
+    pub fn root(&self) -> u8 {
+        self.root
+    }
+
+    /// The notes of the scale, relative to its root.
+    pub fn mask(&self) -> u16 {
+        self.mask
+    }
+
+    /// Returns `true` if the given MIDI note is part of the scale.
+    pub fn contains(&self, note: u8) -> bool {
+        let degree = (note % NUM_PITCH_CLASSES + NUM_PITCH_CLASSES - self.root) % NUM_PITCH_CLASSES;
+        self.mask & (1 << degree) != 0
+    }
+
+    /// Moves a MIDI note into the scale. Notes within the scale are kept as they are.
+    /// If there is no matching note of the scale within the range of MIDI notes (or if the note
+    /// is dropped), [`None`] is returned.
+    pub fn quantize(&self, note: u8, quantization: Quantization) -> Option<u8> {
+        if note >= NUM_MIDI_NOTES {
+            return None;
+        }
+        if self.contains(note) {
+            return Some(note);
+        }
+
+        let above = (note + 1..NUM_MIDI_NOTES).find(|&candidate| self.contains(candidate));
+        let below = (0..note).rev().find(|&candidate| self.contains(candidate));
+
+        match quantization {
+            Quantization::Nearest => match (below, above) {
+                (Some(below), Some(above)) if above - note < note - below => Some(above),
+                (Some(below), _) => Some(below),
+                (None, above) => above,
+            },
+            Quantization::Up => above,
+            Quantization::Down => below,
+            Quantization::Drop => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modes_are_rotations_of_the_major_scale() {
+        let c_major = Scale::new(0, ScaleType::Major.mask().unwrap()).unwrap();
+        let modes = [
+            (2, ScaleType::Dorian),
+            (4, ScaleType::Phrygian),
+            (5, ScaleType::Lydian),
+            (7, ScaleType::Mixolydian),
+            (9, ScaleType::Minor),
+            (11, ScaleType::Locrian),
+        ];
+
+        for (root, scale_type) in modes {
+            let mode = Scale::new(root, scale_type.mask().unwrap()).unwrap();
+            for note in 0..NUM_MIDI_NOTES {
+                assert_eq!(
+                    mode.contains(note),
+                    c_major.contains(note),
+                    "{scale_type:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn quantization_stays_within_midi_notes() {
+        // Only B is part of the scale.
+        let scale = Scale::new(11, 0b1).unwrap();
+
+        assert_eq!(scale.quantize(126, Quantization::Up), None);
+        assert_eq!(scale.quantize(126, Quantization::Nearest), Some(119));
+        assert_eq!(scale.quantize(5, Quantization::Down), None);
+        assert_eq!(scale.quantize(5, Quantization::Nearest), Some(11));
+        assert_eq!(
+            Scale::new(0, 0)
+                .unwrap()
+                .quantize(60, Quantization::Nearest),
+            None
+        );
+    }
+}
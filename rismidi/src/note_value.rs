@@ -0,0 +1,150 @@
+use nih_plug::prelude::*;
+
+/// A musical note value, used for durations that are synchronized to the host's tempo.
+/// It can be used as a plugin parameter with [`nih_plug::params::EnumParam`].
+///
+/// # Examples
+///
+/// ```
+/// use nih_plug::prelude::*;
+/// use rismidi::NoteValue;
+///
+/// #[derive(Params)]
+/// struct MyPluginParams {
+///     pub rate: EnumParam<NoteValue>,
+/// }
+/// ```
+#[derive(Enum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NoteValue {
+    /// A whole note
+    #[id = "1/1"]
+    #[name = "1/1"]
+    Whole,
+
+    /// A dotted half note
+    #[id = "1/2d"]
+    #[name = "1/2 dotted"]
+    HalfDotted,
+
+    /// A half note
+    #[id = "1/2"]
+    #[name = "1/2"]
+    Half,
+
+    /// A dotted quarter note
+    #[id = "1/4d"]
+    #[name = "1/4 dotted"]
+    QuarterDotted,
+
+    /// A half note triplet
+    #[id = "1/2t"]
+    #[name = "1/2 triplet"]
+    HalfTriplet,
+
+    /// A quarter note
+    #[id = "1/4"]
+    #[name = "1/4"]
+    Quarter,
+
+    /// A dotted eighth note
+    #[id = "1/8d"]
+    #[name = "1/8 dotted"]
+    EighthDotted,
+
+    /// A quarter note triplet
+    #[id = "1/4t"]
+    #[name = "1/4 triplet"]
+    QuarterTriplet,
+
+    /// An eighth note
+    #[id = "1/8"]
+    #[name = "1/8"]
+    Eighth,
+
+    /// A dotted sixteenth note
+    #[id = "1/16d"]
+    #[name = "1/16 dotted"]
+    SixteenthDotted,
+
+    /// An eighth note triplet
+    #[id = "1/8t"]
+    #[name = "1/8 triplet"]
+    EighthTriplet,
+
+    /// A sixteenth note
+    #[id = "1/16"]
+    #[name = "1/16"]
+    Sixteenth,
+
+    /// A sixteenth note triplet
+    #[id = "1/16t"]
+    #[name = "1/16 triplet"]
+    SixteenthTriplet,
+
+    /// A thirty-second note
+    #[id = "1/32"]
+    #[name = "1/32"]
+    ThirtySecond,
+}
+
+impl NoteValue {
+    /// The tempo (in BPM) to assume if the plugin host does not report one.
+    pub const FALLBACK_TEMPO: f64 = 120.0;
+
+    /// Gets the duration of the note value in beats, i.e. quarter notes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rismidi::NoteValue;
+    ///
+    /// assert_eq!(NoteValue::Whole.beats(), 4.0);
+    /// assert_eq!(NoteValue::EighthDotted.beats(), 0.75);
+    /// ```
+    pub fn beats(&self) -> f64 {
+        match self {
+            NoteValue::Whole => 4.0,
+            NoteValue::HalfDotted => 3.0,
+            NoteValue::Half => 2.0,
+            NoteValue::QuarterDotted => 1.5,
+            NoteValue::HalfTriplet => 4.0 / 3.0,
+            NoteValue::Quarter => 1.0,
+            NoteValue::EighthDotted => 0.75,
+            NoteValue::QuarterTriplet => 2.0 / 3.0,
+            NoteValue::Eighth => 0.5,
+            NoteValue::SixteenthDotted => 0.375,
+            NoteValue::EighthTriplet => 1.0 / 3.0,
+            NoteValue::Sixteenth => 0.25,
+            NoteValue::SixteenthTriplet => 1.0 / 6.0,
+            NoteValue::ThirtySecond => 0.125,
+        }
+    }
+
+    /// Gets the duration of the note value in samples at the given tempo (in BPM).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rismidi::NoteValue;
+    ///
+    /// // At 120 BPM, a quarter note lasts for half a second.
+    /// assert_eq!(NoteValue::Quarter.samples(120.0, 48000.0), 24000.0);
+    /// ```
+    pub fn samples(&self, tempo: f64, sample_rate: f32) -> f64 {
+        self.beats() * 60.0 / tempo * sample_rate as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn note_values_are_sorted_by_duration() {
+        let durations: Vec<f64> = (0..NoteValue::variants().len())
+            .map(|idx| NoteValue::from_index(idx).beats())
+            .collect();
+
+        assert!(durations.windows(2).all(|pair| pair[0] > pair[1]));
+    }
+}
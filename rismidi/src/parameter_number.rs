@@ -0,0 +1,429 @@
+use crate::{MidiChannel, MAX_7_BIT, NUM_MIDI_CHANNELS};
+use nih_plug::midi::NoteEvent;
+
+const CC_DATA_ENTRY_MSB: u8 = 6;
+const CC_DATA_ENTRY_LSB: u8 = 38;
+const CC_DATA_INCREMENT: u8 = 96;
+const CC_DATA_DECREMENT: u8 = 97;
+const CC_NRPN_LSB: u8 = 98;
+const CC_NRPN_MSB: u8 = 99;
+const CC_RPN_LSB: u8 = 100;
+const CC_RPN_MSB: u8 = 101;
+
+/// The number of a registered (RPN) or non-registered (NRPN) parameter, from 0 to 16383.
+///
+/// Registered parameters are defined by the MIDI specification, non-registered parameters are
+/// defined by the manufacturer of a device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParameterNumber {
+    /// A registered parameter number (RPN).
+    Registered(u16),
+
+    /// A non-registered parameter number (NRPN).
+    NonRegistered(u16),
+}
+
+impl ParameterNumber {
+    /// The pitch bend range, with the semitones in the MSB and the cents in the LSB.
+    pub const PITCH_BEND_SENSITIVITY: Self = Self::Registered(0);
+
+    /// The fine tuning, in steps of 100/8192 cents from -100 to +100 cents.
+    pub const FINE_TUNING: Self = Self::Registered(1);
+
+    /// The coarse tuning, in semitones (MSB only), centred around 64.
+    pub const COARSE_TUNING: Self = Self::Registered(2);
+
+    /// The range of the modulation wheel.
+    pub const MODULATION_DEPTH_RANGE: Self = Self::Registered(5);
+
+    /// The MPE configuration message, with the number of member channels in the MSB.
+    pub const MPE_CONFIGURATION: Self = Self::Registered(6);
+
+    /// The number which deselects any parameter, so that further data entry is ignored.
+    const NULL_NUMBER: u16 = 0x3FFF;
+
+    /// The controllers for the MSB and LSB of this kind of parameter number.
+    fn ccs(&self) -> (u8, u8) {
+        match self {
+            ParameterNumber::Registered(_) => (CC_RPN_MSB, CC_RPN_LSB),
+            ParameterNumber::NonRegistered(_) => (CC_NRPN_MSB, CC_NRPN_LSB),
+        }
+    }
+
+    fn number(&self) -> u16 {
+        match *self {
+            ParameterNumber::Registered(number) | ParameterNumber::NonRegistered(number) => number,
+        }
+    }
+}
+
+/// How a parameter is changed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataChange {
+    /// The parameter is set to a 14-bit value. If only the MSB has been received, the LSB is 0.
+    Set(u16),
+
+    /// The parameter is increased by one step.
+    Increment,
+
+    /// The parameter is decreased by one step.
+    Decrement,
+}
+
+/// A change of a registered or non-registered parameter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParameterChange {
+    /// The timing of the event which caused the change.
+    pub timing: u32,
+
+    /// The channel the parameter was changed on.
+    pub channel: MidiChannel,
+
+    /// The parameter which was changed.
+    pub parameter: ParameterNumber,
+
+    /// How the parameter was changed.
+    pub change: DataChange,
+}
+
+/// Returns `true` if the controller `cc` is used for selecting or changing registered and
+/// non-registered parameters.
+///
+/// # Examples
+///
+/// ```
+/// use rismidi::is_parameter_number_cc;
+///
+/// assert!(is_parameter_number_cc(101));
+/// assert!(is_parameter_number_cc(6));
+/// assert!(!is_parameter_number_cc(7));
+/// ```
+pub fn is_parameter_number_cc(cc: u8) -> bool {
+    matches!(
+        cc,
+        CC_DATA_ENTRY_MSB | CC_DATA_ENTRY_LSB | CC_DATA_INCREMENT..=CC_RPN_MSB
+    )
+}
+
+/// Converts a normalized value from nih_plug into a raw 7-bit MIDI value.
+fn normalized_to_7_bit(value: f32) -> u8 {
+    (value * MAX_7_BIT as f32)
+        .round()
+        .clamp(0.0, MAX_7_BIT as f32) as u8
+}
+
+fn combine(msb: u8, lsb: u8) -> u16 {
+    (msb as u16) << 7 | lsb as u16
+}
+
+/// The kind of parameter number which was selected last on a channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Selection {
+    Registered,
+    NonRegistered,
+}
+
+/// The state of the parameter number state machine for a single channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct ChannelState {
+    rpn: (u8, u8),
+    nrpn: (u8, u8),
+    selection: Option<Selection>,
+    data: (u8, u8),
+}
+
+impl ChannelState {
+    const INITIAL: Self = Self {
+        rpn: (127, 127),
+        nrpn: (127, 127),
+        selection: None,
+        data: (0, 0),
+    };
+
+    fn selected(&self) -> Option<ParameterNumber> {
+        let parameter = match self.selection? {
+            Selection::Registered => ParameterNumber::Registered(combine(self.rpn.0, self.rpn.1)),
+            Selection::NonRegistered => {
+                ParameterNumber::NonRegistered(combine(self.nrpn.0, self.nrpn.1))
+            }
+        };
+
+        (parameter.number() != ParameterNumber::NULL_NUMBER).then_some(parameter)
+    }
+}
+
+/// Recognizes registered (RPN) and non-registered (NRPN) parameter changes in a stream of CCs.
+///
+/// A parameter is selected with CC 101/100 (RPN) or CC 99/98 (NRPN) and then changed with data
+/// entry (CC 6 and 38) or data increment/decrement (CC 96/97). The decoder keeps track of the
+/// selected parameter for every channel. Like [`crate::HighResCcDecoder`], it reports a change for
+/// every data entry MSB and LSB, and an MSB resets the LSB to 0.
+///
+/// # Examples
+///
+/// ```
+/// use nih_plug::prelude::*;
+/// use rismidi::{DataChange, MidiChannel, ParameterNumber, ParameterNumberDecoder};
+///
+/// let cc = |cc, raw: u8| NoteEvent::MidiCC {
+///     timing: 0,
+///     channel: 0,
+///     cc,
+///     value: raw as f32 / 127.0,
+/// };
+///
+/// // Set the pitch bend range to 12 semitones.
+/// let mut decoder = ParameterNumberDecoder::new();
+/// assert_eq!(decoder.handle_event(&cc(101, 0)), None);
+/// assert_eq!(decoder.handle_event(&cc(100, 0)), None);
+///
+/// let change = decoder.handle_event(&cc(6, 12)).unwrap();
+/// assert_eq!(change.parameter, ParameterNumber::PITCH_BEND_SENSITIVITY);
+/// assert_eq!(change.change, DataChange::Set(12 << 7));
+/// ```
+pub struct ParameterNumberDecoder {
+    channels: [ChannelState; NUM_MIDI_CHANNELS as usize],
+}
+
+impl ParameterNumberDecoder {
+    /// Creates a new [`ParameterNumberDecoder`] without any selected parameters.
+    pub fn new() -> Self {
+        Self {
+            channels: [ChannelState::INITIAL; NUM_MIDI_CHANNELS as usize],
+        }
+    }
+
+    /// Updates the decoder according to a CC event. If the event changes the selected parameter,
+    /// the change is returned. All other events are ignored.
+    pub fn handle_event(&mut self, event: &NoteEvent) -> Option<ParameterChange> {
+        let NoteEvent::MidiCC {
+            timing,
+            channel,
+            cc,
+            value,
+        } = *event
+        else {
+            return None;
+        };
+
+        let channel = MidiChannel::try_from_0_based(channel.into()).ok()?;
+        let state = &mut self.channels[channel.to_0_based() as usize];
+        let value = normalized_to_7_bit(value);
+
+        let change = match cc {
+            CC_RPN_MSB | CC_RPN_LSB | CC_NRPN_MSB | CC_NRPN_LSB => {
+                let (number, selection) = match cc {
+                    CC_RPN_MSB | CC_RPN_LSB => (&mut state.rpn, Selection::Registered),
+                    _ => (&mut state.nrpn, Selection::NonRegistered),
+                };
+                match cc {
+                    CC_RPN_MSB | CC_NRPN_MSB => number.0 = value,
+                    _ => number.1 = value,
+                }
+                state.selection = Some(selection);
+                state.data = (0, 0);
+                return None;
+            }
+            CC_DATA_ENTRY_MSB => {
+                state.data = (value, 0);
+                DataChange::Set(combine(value, 0))
+            }
+            CC_DATA_ENTRY_LSB => {
+                state.data.1 = value;
+                DataChange::Set(combine(state.data.0, value))
+            }
+            CC_DATA_INCREMENT => DataChange::Increment,
+            CC_DATA_DECREMENT => DataChange::Decrement,
+            _ => return None,
+        };
+
+        Some(ParameterChange {
+            timing,
+            channel,
+            parameter: state.selected()?,
+            change,
+        })
+    }
+
+    /// Gets the parameter which is currently selected on `channel`, if any.
+    pub fn selected(&self, channel: MidiChannel) -> Option<ParameterNumber> {
+        self.channels[channel.to_0_based() as usize].selected()
+    }
+
+    /// Forgets all selected parameters, e.g. when the plugin is reset.
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+impl Default for ParameterNumberDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Generates the CCs for changing registered (RPN) and non-registered (NRPN) parameters.
+///
+/// The parameter number is only sent if a different parameter has been selected before. Call
+/// [`ParameterNumberEncoder::deselect`] after the last change to prevent later data entry CCs from
+/// changing the parameter by accident.
+///
+/// # Examples
+///
+/// ```
+/// use nih_plug::prelude::*;
+/// use rismidi::{DataChange, MidiChannel, ParameterNumber, ParameterNumberEncoder};
+///
+/// let mut encoder = ParameterNumberEncoder::new();
+/// let mut events = Vec::new();
+/// let channel = MidiChannel::Channel1;
+/// let parameter = ParameterNumber::PITCH_BEND_SENSITIVITY;
+/// encoder.encode(0, channel, parameter, DataChange::Set(12 << 7), |event| events.push(event));
+/// encoder.deselect(0, channel, |event| events.push(event));
+///
+/// let ccs: Vec<_> = events
+///     .iter()
+///     .map(|event| match event {
+///         NoteEvent::MidiCC { cc, value, .. } => (*cc, (value * 127.0).round() as u8),
+///         _ => unreachable!(),
+///     })
+///     .collect();
+/// assert_eq!(ccs, [(101, 0), (100, 0), (6, 12), (38, 0), (101, 127), (100, 127)]);
+/// ```
+pub struct ParameterNumberEncoder {
+    /// The parameter selected last, per channel.
+    selected: [Option<ParameterNumber>; NUM_MIDI_CHANNELS as usize],
+}
+
+impl ParameterNumberEncoder {
+    /// Creates a new [`ParameterNumberEncoder`] which has not selected any parameters yet.
+    pub fn new() -> Self {
+        Self {
+            selected: [None; NUM_MIDI_CHANNELS as usize],
+        }
+    }
+
+    /// Passes the CCs for applying `change` to `parameter` to `send`. Parameter numbers and values
+    /// above 16383 are limited to it.
+    pub fn encode(
+        &mut self,
+        timing: u32,
+        channel: MidiChannel,
+        parameter: ParameterNumber,
+        change: DataChange,
+        mut send: impl FnMut(NoteEvent),
+    ) {
+        let mut send_cc = |cc: u8, raw: u16| {
+            send(NoteEvent::MidiCC {
+                timing,
+                channel: channel.to_0_based(),
+                cc,
+                value: raw as f32 / MAX_7_BIT as f32,
+            })
+        };
+
+        let selected = &mut self.selected[channel.to_0_based() as usize];
+        if *selected != Some(parameter) {
+            *selected = Some(parameter);
+            let number = parameter.number().min(ParameterNumber::NULL_NUMBER);
+            let (msb_cc, lsb_cc) = parameter.ccs();
+            send_cc(msb_cc, number >> 7);
+            send_cc(lsb_cc, number & MAX_7_BIT);
+        }
+
+        match change {
+            DataChange::Set(value) => {
+                let value = value.min(ParameterNumber::NULL_NUMBER);
+                send_cc(CC_DATA_ENTRY_MSB, value >> 7);
+                send_cc(CC_DATA_ENTRY_LSB, value & MAX_7_BIT);
+            }
+            DataChange::Increment => send_cc(CC_DATA_INCREMENT, 0),
+            DataChange::Decrement => send_cc(CC_DATA_DECREMENT, 0),
+        }
+    }
+
+    /// Passes the CCs for deselecting the current parameter on `channel` to `send`.
+    pub fn deselect(&mut self, timing: u32, channel: MidiChannel, mut send: impl FnMut(NoteEvent)) {
+        self.selected[channel.to_0_based() as usize] = None;
+        for cc in [CC_RPN_MSB, CC_RPN_LSB] {
+            send(NoteEvent::MidiCC {
+                timing,
+                channel: channel.to_0_based(),
+                cc,
+                value: 1.0,
+            });
+        }
+    }
+
+    /// Forgets which parameters have been selected, so that the next change selects its parameter
+    /// again.
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+impl Default for ParameterNumberEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cc(cc: u8, raw: u8) -> NoteEvent {
+        NoteEvent::MidiCC {
+            timing: 0,
+            channel: 2,
+            cc,
+            value: raw as f32 / MAX_7_BIT as f32,
+        }
+    }
+
+    #[test]
+    fn data_entry_needs_a_selected_parameter() {
+        let mut decoder = ParameterNumberDecoder::new();
+        assert_eq!(decoder.handle_event(&cc(6, 1)), None);
+
+        decoder.handle_event(&cc(99, 1));
+        decoder.handle_event(&cc(98, 2));
+        let change = decoder.handle_event(&cc(96, 0)).unwrap();
+        assert_eq!(change.channel, MidiChannel::Channel3);
+        assert_eq!(change.parameter, ParameterNumber::NonRegistered(1 << 7 | 2));
+        assert_eq!(change.change, DataChange::Increment);
+
+        decoder.handle_event(&cc(101, 127));
+        decoder.handle_event(&cc(100, 127));
+        assert_eq!(decoder.selected(MidiChannel::Channel3), None);
+        assert_eq!(decoder.handle_event(&cc(6, 1)), None);
+    }
+
+    #[test]
+    fn encoded_changes_are_decoded_again() {
+        let mut encoder = ParameterNumberEncoder::new();
+        let mut decoder = ParameterNumberDecoder::new();
+        let channel = MidiChannel::Channel5;
+
+        for (parameter, change) in [
+            (ParameterNumber::MPE_CONFIGURATION, DataChange::Set(15 << 7)),
+            (ParameterNumber::NonRegistered(1234), DataChange::Set(5678)),
+            (ParameterNumber::NonRegistered(1234), DataChange::Decrement),
+            (ParameterNumber::FINE_TUNING, DataChange::Set(8192)),
+        ] {
+            let mut decoded = None;
+            encoder.encode(0, channel, parameter, change, |event| {
+                decoded = decoder.handle_event(&event).or(decoded);
+            });
+            assert_eq!(
+                decoded,
+                Some(ParameterChange {
+                    timing: 0,
+                    channel,
+                    parameter,
+                    change
+                })
+            );
+        }
+    }
+}
@@ -0,0 +1,297 @@
+use crate::{controller_by_name, controller_name, RismidiError, NUM_MIDI_CCS};
+use nih_plug::prelude::*;
+use std::{
+    fmt::{Debug, Display},
+    sync::Arc,
+};
+
+/// A plugin parameter modelling the selection of a MIDI controller (CC) number.
+///
+/// The plugin host shows the number along with the controller's name, e.g. "7 Volume", and the
+/// user can enter either of them. See [`OptionalCcNumberParam`] for a parameter which can also
+/// select no controller at all.
+///
+/// # Examples
+///
+/// ```
+/// use nih_plug::prelude::*;
+/// use rismidi::params::{CcNumberParam, OptionalCcNumberParam};
+///
+/// #[derive(Params)]
+/// struct MyPluginParams {
+///     #[nested(id_prefix = "cc")]
+///     pub cc: CcNumberParam,
+///
+///     #[nested(id_prefix = "filter_cc")]
+///     pub filter_cc: OptionalCcNumberParam,
+/// }
+///
+/// let params = MyPluginParams {
+///     cc: CcNumberParam::new("CC", 1),
+///     filter_cc: OptionalCcNumberParam::new("Filter CC", None)
+///         .with_none_selected_description("Any"),
+/// };
+/// assert_eq!(params.cc.value(), 1);
+/// assert_eq!(params.cc.description(), "1 Mod Wheel");
+/// assert_eq!(params.filter_cc.value(), None);
+/// assert_eq!(params.filter_cc.description(), "Any");
+/// ```
+#[derive(Params)]
+pub struct CcNumberParam {
+    /// The selected CC number.
+    #[id = ""]
+    inner: IntParam,
+}
+
+impl CcNumberParam {
+    /// Creates a new [`CcNumberParam`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `default` is not a valid CC number.
+    pub fn new(name: impl Into<String>, default: u8) -> Self {
+        assert!(default < NUM_MIDI_CCS, "invalid default CC number");
+
+        let inner = IntParam::new(
+            name,
+            default.into(),
+            IntRange::Linear {
+                min: 0,
+                max: (NUM_MIDI_CCS - 1).into(),
+            },
+        )
+        .with_value_to_string(Arc::new(|inner_val| {
+            selection_to_string(Some(inner_val as u8), "")
+        }))
+        .with_string_to_value(Arc::new(|string| {
+            let cc = try_selection_from_string(string, None).ok()??;
+            Some(cc.into())
+        }));
+
+        Self { inner }
+    }
+
+    /// The field's current plain value, after monophonic modulation has been applied.
+    #[inline]
+    pub fn value(&self) -> u8 {
+        // The parameter's range only contains valid CC numbers.
+        self.inner.value() as u8
+    }
+
+    /// Returns the [`String`] representation for the current value.
+    pub fn description(&self) -> String {
+        let normalized = self.inner.modulated_normalized_value();
+        self.inner.normalized_value_to_string(normalized, true)
+    }
+}
+
+/// A plugin parameter modelling either the selection of a MIDI controller (CC) number or the
+/// explicit selection of no controller. See [`CcNumberParam`] for details.
+#[derive(Params)]
+pub struct OptionalCcNumberParam {
+    /// The selected CC number, or -1 if no controller is selected.
+    #[id = ""]
+    inner: IntParam,
+
+    /// We store the default here in addition to `inner.default` to avoid runtime conversions.
+    default: Option<u8>,
+
+    /// The parameter description visible in the plugin host if no controller is selected.
+    no_cc_description: &'static str,
+}
+
+impl OptionalCcNumberParam {
+    /// The inner value which means that no controller is selected.
+    const NO_CC: i32 = -1;
+
+    /// The default description of the "no controller selected" state.
+    pub const DEFAULT_NO_CC_DESCRIPTION: &'static str = "None";
+
+    /// Creates a new [`OptionalCcNumberParam`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `default` is not a valid CC number.
+    pub fn new(name: impl Into<String>, default: Option<u8>) -> Self {
+        if let Some(cc) = default {
+            assert!(cc < NUM_MIDI_CCS, "invalid default CC number");
+        }
+
+        let instance = Self {
+            inner: IntParam::new(
+                name,
+                Self::selection_to_inner(default),
+                IntRange::Linear {
+                    min: Self::NO_CC,
+                    max: (NUM_MIDI_CCS - 1).into(),
+                },
+            ),
+            default,
+            no_cc_description: "",
+        };
+
+        instance.with_none_selected_description(Self::DEFAULT_NO_CC_DESCRIPTION)
+    }
+
+    /// The field's current plain value, after monophonic modulation has been applied.
+    #[inline]
+    pub fn value(&self) -> Option<u8> {
+        Self::try_selection_from_inner(self.inner.value()).unwrap_or(self.default)
+    }
+
+    /// Returns the [`String`] representation for the current value.
+    pub fn description(&self) -> String {
+        let normalized = self.inner.modulated_normalized_value();
+        self.inner.normalized_value_to_string(normalized, true)
+    }
+
+    /// Sets the description of the "no controller selected" position, e.g. "Any". Usually, this
+    /// will be shown to the user by the plugin host.
+    pub fn with_none_selected_description(mut self, description: &'static str) -> Self {
+        self.no_cc_description = description;
+
+        self.with_updated_callbacks()
+    }
+
+    fn with_updated_callbacks(mut self) -> Self {
+        let no_cc_description = self.no_cc_description;
+        self.inner = self
+            .inner
+            .with_value_to_string(Arc::new(move |inner_val| {
+                let selection = Self::try_selection_from_inner(inner_val).unwrap_or_default();
+                selection_to_string(selection, no_cc_description)
+            }))
+            .with_string_to_value(Arc::new(move |string| {
+                let selection = try_selection_from_string(string, Some(no_cc_description)).ok()?;
+                Some(Self::selection_to_inner(selection))
+            }));
+
+        self
+    }
+
+    /// Tries to convert the representation of [`Self::inner`] into an [`Option<u8>`].
+    fn try_selection_from_inner(inner_val: i32) -> Result<Option<u8>, RismidiError> {
+        match inner_val {
+            Self::NO_CC => Ok(None),
+            cc if (0..NUM_MIDI_CCS.into()).contains(&cc) => Ok(Some(cc as u8)),
+            _ => Err(RismidiError::IntOutOfBounds {
+                found: inner_val,
+                min: Self::NO_CC,
+                max: (NUM_MIDI_CCS - 1).into(),
+            }),
+        }
+    }
+
+    /// Converts an [`Option<u8>`] into the appropriate representation for [`Self::inner`].
+    fn selection_to_inner(from: Option<u8>) -> i32 {
+        match from {
+            Some(cc) => cc.into(),
+            None => Self::NO_CC,
+        }
+    }
+}
+
+/// Converts an [`Option<u8>`] into the string representation to show to the user.
+fn selection_to_string(selection: Option<u8>, no_cc_msg: &str) -> String {
+    match selection {
+        None => no_cc_msg.to_string(),
+        Some(cc) => match controller_name(cc) {
+            Some(name) => format!("{cc} {name}"),
+            None => format!("{cc}"),
+        },
+    }
+}
+
+/// Tries to convert the string representation of a user selection into an [`Option<u8>`].
+/// The user may enter the CC number, the controller's name or both. Selecting no controller is
+/// only possible if there is a `no_cc_msg`.
+fn try_selection_from_string(
+    description: &str,
+    no_cc_msg: Option<&str>,
+) -> Result<Option<u8>, RismidiError> {
+    let string = description.trim();
+
+    if matches!(no_cc_msg, Some(no_cc_msg) if string.eq_ignore_ascii_case(no_cc_msg)) {
+        return Ok(None);
+    }
+
+    let number = string
+        .split_whitespace()
+        .next()
+        .and_then(|number| number.parse::<i32>().ok());
+    match number {
+        Some(number) => u8::try_from(number)
+            .ok()
+            .filter(|&cc| cc < NUM_MIDI_CCS)
+            .map(Some)
+            .ok_or(RismidiError::IntOutOfBounds {
+                found: number,
+                min: 0,
+                max: (NUM_MIDI_CCS - 1).into(),
+            }),
+        None => controller_by_name(string)
+            .map(Some)
+            .ok_or(RismidiError::UnknownInput),
+    }
+}
+
+impl Display for CcNumberParam {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", selection_to_string(Some(self.value()), ""))
+    }
+}
+
+impl Debug for CcNumberParam {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CcNumberParam")
+            .field("cc", &self.value())
+            .finish()
+    }
+}
+
+impl Display for OptionalCcNumberParam {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let string = selection_to_string(self.value(), self.no_cc_description);
+        write!(f, "{}", string)
+    }
+}
+
+impl Debug for OptionalCcNumberParam {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OptionalCcNumberParam")
+            .field("cc", &self.value())
+            .field("default", &self.default)
+            .field("no_cc_msg", &self.no_cc_description)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn description_shows_controller_names() {
+        for (cc, description) in [(1, "1 Mod Wheel"), (3, "3"), (64, "64 Sustain")] {
+            let param = CcNumberParam::new("test", cc);
+            assert_eq!(param.description(), description);
+        }
+
+        let param = OptionalCcNumberParam::new("test", None).with_none_selected_description("Any");
+        assert_eq!(param.description(), "Any");
+    }
+
+    #[test]
+    fn selection_from_number_or_name() {
+        let parse = |string| try_selection_from_string(string, Some("Any"));
+
+        assert_eq!(parse("7"), Ok(Some(7)));
+        assert_eq!(parse(" 7 Volume "), Ok(Some(7)));
+        assert_eq!(parse("volume"), Ok(Some(7)));
+        assert_eq!(parse("any"), Ok(None));
+        assert_eq!(parse("Wobble"), Err(RismidiError::UnknownInput));
+        assert!(parse("128").is_err());
+        assert!(parse("-1").is_err());
+        assert!(try_selection_from_string("Any", None).is_err());
+    }
+}
@@ -1,5 +1,7 @@
 //! More plugin parameters in addition to [`nih_plug::params`].
 
+mod cc_number;
 mod optional_midi_channel;
 
+pub use cc_number::{CcNumberParam, OptionalCcNumberParam};
 pub use optional_midi_channel::OptionalMidiChannelParam;
@@ -0,0 +1,72 @@
+/// A small and fast pseudo-random number generator (xorshift64*).
+///
+/// It is seeded explicitly, so that plugins using it produce the same output every time a
+/// project is rendered. It is not suitable for cryptographic purposes.
+///
+/// # Examples
+///
+/// ```
+/// use rismidi::Rng;
+///
+/// let mut rng = Rng::new(42);
+/// let mut same_seed = Rng::new(42);
+///
+/// for _ in 0..10 {
+///     assert_eq!(rng.next_f32(), same_seed.next_f32());
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// Creates a new [`Rng`] from the given seed.
+    pub fn new(seed: u64) -> Self {
+        // xorshift must not be seeded with zero, so we scramble the seed.
+        let state = seed ^ 0x9e37_79b9_7f4a_7c15;
+
+        Self {
+            state: if state == 0 {
+                0x9e37_79b9_7f4a_7c15
+            } else {
+                state
+            },
+        }
+    }
+
+    /// Returns the next pseudo-random [`u64`].
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// Returns a pseudo-random [`f32`] in the range `0.0..1.0`.
+    pub fn next_f32(&mut self) -> f32 {
+        // An f32 has 24 bits of precision, so we only use the upper 24 bits.
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Returns a pseudo-random [`usize`] in the range `0..bound`. `bound` must not be zero.
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn floats_are_in_range() {
+        let mut rng = Rng::new(0);
+
+        for _ in 0..10000 {
+            let value = rng.next_f32();
+            assert!((0.0..1.0).contains(&value));
+        }
+    }
+}
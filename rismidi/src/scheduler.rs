@@ -0,0 +1,211 @@
+use crate::{HasTiming, RismidiError};
+use nih_plug::prelude::*;
+
+/// A queue for [`NoteEvent`]s that should be sent at a later point in time, possibly after the end
+/// of the current buffer.
+///
+/// The scheduler never allocates after it has been created, so it is safe to use from
+/// [`Plugin::process`]. All due times are measured in samples, relative to the start of the current
+/// buffer. Call [`EventScheduler::advance`] at the end of every buffer to move on to the next one.
+///
+/// # Examples
+///
+/// ```
+/// use nih_plug::prelude::*;
+/// use rismidi::EventScheduler;
+///
+/// let mut scheduler = EventScheduler::with_capacity(16);
+/// let note_on = NoteEvent::NoteOn {
+///     timing: 0,
+///     voice_id: None,
+///     channel: 0,
+///     note: 60,
+///     velocity: 0.8,
+/// };
+///
+/// // Send the event 100 samples into the next buffer of 64 samples each.
+/// scheduler.schedule(164, note_on).unwrap();
+/// assert_eq!(scheduler.pop_before(64), None);
+///
+/// scheduler.advance(64);
+/// assert_eq!(scheduler.pop_before(64), None);
+///
+/// scheduler.advance(64);
+/// let due_event = scheduler.pop_before(64).unwrap();
+/// assert_eq!(due_event.timing(), 36);
+/// ```
+pub struct EventScheduler {
+    /// The pending events, sorted by descending due time. This way, the next event can be popped
+    /// from the back of the vector. Events with the same due time are kept in insertion order.
+    events: Vec<ScheduledEvent>,
+}
+
+struct ScheduledEvent {
+    /// Time at which the event is due, in samples relative to the start of the current buffer.
+    due: u64,
+    event: NoteEvent,
+}
+
+impl EventScheduler {
+    /// Creates a new [`EventScheduler`] which can hold up to `capacity` pending events.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            events: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// The maximum number of pending events.
+    pub fn capacity(&self) -> usize {
+        self.events.capacity()
+    }
+
+    /// The number of pending events.
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Returns `true` if there are no pending events.
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// The number of events that can still be scheduled before the scheduler is full.
+    pub fn remaining_capacity(&self) -> usize {
+        self.capacity() - self.len()
+    }
+
+    /// Schedules `event` to be sent `due` samples after the start of the current buffer. The
+    /// event's original timing is ignored.
+    ///
+    /// If the scheduler is full, the event will be dropped and an error will be returned.
+    pub fn schedule(&mut self, due: u64, event: NoteEvent) -> Result<(), RismidiError> {
+        if self.remaining_capacity() == 0 {
+            return Err(RismidiError::SchedulerFull);
+        }
+
+        let index = self.events.partition_point(|pending| pending.due > due);
+        self.events.insert(index, ScheduledEvent { due, event });
+
+        Ok(())
+    }
+
+    /// Removes the next pending event if it is due before sample `end` of the current buffer.
+    /// The returned event's timing is set to its due time.
+    pub fn pop_before(&mut self, end: u32) -> Option<NoteEvent> {
+        match self.events.last() {
+            Some(next) if next.due < end as u64 => {
+                let ScheduledEvent { due, event } = self.events.pop()?;
+                Some(event.with_timing(due as u32))
+            }
+            _ => None,
+        }
+    }
+
+    /// Moves on to the next buffer, given that the current one is `num_samples` long.
+    ///
+    /// Events that have not been popped from the current buffer will be due at its start.
+    pub fn advance(&mut self, num_samples: u32) {
+        for pending in self.events.iter_mut() {
+            pending.due = pending.due.saturating_sub(num_samples as u64);
+        }
+    }
+
+    /// Moves the pending events for which `matches` returns `true` to `due`, if they are due later.
+    /// They will be sent before all other events due at that time.
+    pub fn bring_forward(&mut self, due: u64, mut matches: impl FnMut(&NoteEvent) -> bool) {
+        // The events due later are at the front.
+        let mut index = 0;
+        while index < self.events.len() && self.events[index].due > due {
+            if matches(&self.events[index].event) {
+                let ScheduledEvent { event, .. } = self.events.remove(index);
+                let position = self.events.partition_point(|pending| pending.due >= due);
+                self.events.insert(position, ScheduledEvent { due, event });
+            } else {
+                index += 1;
+            }
+        }
+    }
+
+    /// Only keeps the pending events for which `keep` returns `true`.
+    pub fn retain(&mut self, mut keep: impl FnMut(&NoteEvent) -> bool) {
+        self.events.retain(|pending| keep(&pending.event));
+    }
+
+    /// Drops all pending events.
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note_on(note: u8) -> NoteEvent {
+        NoteEvent::NoteOn {
+            timing: 0,
+            voice_id: None,
+            channel: 0,
+            note,
+            velocity: 1.0,
+        }
+    }
+
+    #[test]
+    fn events_are_popped_in_order_of_due_time() {
+        let mut scheduler = EventScheduler::with_capacity(8);
+        scheduler.schedule(30, note_on(3)).unwrap();
+        scheduler.schedule(10, note_on(1)).unwrap();
+        scheduler.schedule(20, note_on(2)).unwrap();
+        scheduler.schedule(10, note_on(4)).unwrap();
+
+        let popped: Vec<(u32, NoteEvent)> = std::iter::from_fn(|| scheduler.pop_before(64))
+            .map(|event| (event.timing(), event))
+            .collect();
+
+        let expected: Vec<(u32, NoteEvent)> = [(10, 1), (10, 4), (20, 2), (30, 3)]
+            .into_iter()
+            .map(|(timing, note)| (timing, note_on(note).with_timing(timing)))
+            .collect();
+        assert_eq!(popped, expected);
+    }
+
+    #[test]
+    fn events_can_be_brought_forward() {
+        let mut scheduler = EventScheduler::with_capacity(8);
+        scheduler.schedule(30, note_on(3)).unwrap();
+        scheduler.schedule(10, note_on(1)).unwrap();
+        scheduler.schedule(20, note_on(2)).unwrap();
+        scheduler.schedule(40, note_on(4)).unwrap();
+
+        scheduler.bring_forward(20, |event| {
+            matches!(
+                event,
+                NoteEvent::NoteOn {
+                    note: 1 | 3 | 4,
+                    ..
+                }
+            )
+        });
+        let popped: Vec<(u32, NoteEvent)> = std::iter::from_fn(|| scheduler.pop_before(64))
+            .map(|event| (event.timing(), event))
+            .collect();
+
+        let expected: Vec<(u32, NoteEvent)> = [(10, 1), (20, 3), (20, 4), (20, 2)]
+            .into_iter()
+            .map(|(timing, note)| (timing, note_on(note).with_timing(timing)))
+            .collect();
+        assert_eq!(popped, expected);
+    }
+
+    #[test]
+    fn full_scheduler_rejects_events() {
+        let mut scheduler = EventScheduler::with_capacity(1);
+        assert_eq!(scheduler.schedule(0, note_on(1)), Ok(()));
+        assert_eq!(
+            scheduler.schedule(0, note_on(2)),
+            Err(RismidiError::SchedulerFull)
+        );
+        assert_eq!(scheduler.len(), 1);
+    }
+}
@@ -0,0 +1,88 @@
+//! Shorthands for testing the event processing of plugins without a plugin host.
+//!
+//! # Examples
+//!
+//! ```
+//! use nih_plug::prelude::*;
+//! use rismidi::testing::{collect_events, note_off, note_on};
+//!
+//! fn process_event(in_event: NoteEvent, mut send: impl FnMut(NoteEvent)) {
+//!     send(in_event);
+//!     if let NoteEvent::NoteOn { timing, channel, note, .. } = in_event {
+//!         send(note_off(timing + 10, channel, note));
+//!     }
+//! }
+//!
+//! let out_events = collect_events(|send| process_event(note_on(0, 1, 60, 0.5), send));
+//! assert_eq!(out_events, vec![note_on(0, 1, 60, 0.5), note_off(10, 1, 60)]);
+//! ```
+
+use nih_plug::prelude::*;
+
+/// Creates a note-on without a voice ID.
+pub fn note_on(timing: u32, channel: u8, note: u8, velocity: f32) -> NoteEvent {
+    NoteEvent::NoteOn {
+        timing,
+        voice_id: None,
+        channel,
+        note,
+        velocity,
+    }
+}
+
+/// Creates a note-off with a release velocity of 0 and without a voice ID.
+pub fn note_off(timing: u32, channel: u8, note: u8) -> NoteEvent {
+    NoteEvent::NoteOff {
+        timing,
+        voice_id: None,
+        channel,
+        note,
+        velocity: 0.0,
+    }
+}
+
+/// Creates a poly pressure event without a voice ID.
+pub fn poly_pressure(timing: u32, channel: u8, note: u8, pressure: f32) -> NoteEvent {
+    NoteEvent::PolyPressure {
+        timing,
+        voice_id: None,
+        channel,
+        note,
+        pressure,
+    }
+}
+
+/// Creates a channel pressure event.
+pub fn channel_pressure(timing: u32, channel: u8, pressure: f32) -> NoteEvent {
+    NoteEvent::MidiChannelPressure {
+        timing,
+        channel,
+        pressure,
+    }
+}
+
+/// Creates a pitch bend event. A `value` of 0.5 means no bend.
+pub fn pitch_bend(timing: u32, channel: u8, value: f32) -> NoteEvent {
+    NoteEvent::MidiPitchBend {
+        timing,
+        channel,
+        value,
+    }
+}
+
+/// Creates a CC event.
+pub fn cc(timing: u32, channel: u8, cc: u8, value: f32) -> NoteEvent {
+    NoteEvent::MidiCC {
+        timing,
+        channel,
+        cc,
+        value,
+    }
+}
+
+/// Calls `process` with a callback for sending events, and returns all events that were sent.
+pub fn collect_events(process: impl FnOnce(&mut dyn FnMut(NoteEvent))) -> Vec<NoteEvent> {
+    let mut out_events = Vec::new();
+    process(&mut |event| out_events.push(event));
+    out_events
+}